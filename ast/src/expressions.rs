@@ -1,13 +1,25 @@
 use crate::ast::{Expression, Node};
-use crate::statements;
-use lexer::Token;
+use crate::{patterns, statements};
+use lexer::{Symbol, Token};
+use std::cell::Cell;
 use std::rc::Rc;
 
+// Where a variable lives: in slot `index` of the frame of the function
+// `depth` functions out from where it is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
 // Identifier
 #[derive(Debug)]
 pub struct Identifier {
     pub token: Token,
-    pub value: String,
+    pub value: Symbol,
+    // Filled in by the resolver. Globals have no slot and are looked up by
+    // name.
+    pub slot: Cell<Option<Slot>>,
 }
 
 impl Node for Identifier {
@@ -89,8 +101,13 @@ impl Expression for If {
 #[derive(Debug)]
 pub struct FunctionLiteral {
     pub token: Token,
-    pub parameters: Option<Vec<Identifier>>,
+    pub parameters: Option<Rc<Vec<patterns::Parameter>>>,
+    pub rest: Option<Identifier>,
     pub body: Option<Rc<statements::BlockStatement>>,
+    // Number of slots in the function's frame, filled in by the resolver.
+    pub locals: Cell<usize>,
+    // Declared with `fn*`, calling it makes a generator.
+    pub generator: bool,
 }
 
 impl Node for FunctionLiteral {
@@ -107,6 +124,14 @@ pub struct CallExpression {
     pub token: Token,
     pub function: Option<Box<dyn Expression>>,
     pub arguments: Option<Vec<Box<dyn Expression>>>,
+    pub named_arguments: Vec<NamedArgument>,
+}
+
+// f(y: 2)
+#[derive(Debug)]
+pub struct NamedArgument {
+    pub name: Identifier,
+    pub value: Box<dyn Expression>,
 }
 
 impl Node for CallExpression {
@@ -135,3 +160,185 @@ impl Node for Infix {
 impl Expression for Infix {
     fn expression_node(&self) {}
 }
+
+// StringLiteral
+#[derive(Debug)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for StringLiteral {
+    fn expression_node(&self) {}
+}
+
+// ArrayLiteral
+#[derive(Debug)]
+pub struct ArrayLiteral {
+    pub token: Token,
+    pub elements: Vec<Box<dyn Expression>>,
+}
+
+impl Node for ArrayLiteral {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for ArrayLiteral {
+    fn expression_node(&self) {}
+}
+
+// HashLiteral
+#[derive(Debug)]
+pub struct HashLiteral {
+    pub token: Token,
+    pub pairs: Vec<(Box<dyn Expression>, Box<dyn Expression>)>,
+}
+
+impl Node for HashLiteral {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for HashLiteral {
+    fn expression_node(&self) {}
+}
+
+// Index
+#[derive(Debug)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub left: Option<Box<dyn Expression>>,
+    pub index: Option<Box<dyn Expression>>,
+}
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for IndexExpression {
+    fn expression_node(&self) {}
+}
+
+// try { ... } catch (e) { ... } finally { ... }
+#[derive(Debug)]
+pub struct Try {
+    pub token: Token,
+    pub body: Option<statements::BlockStatement>,
+    // The name the caught error is bound to, and the block that handles it.
+    pub parameter: Option<Identifier>,
+    pub handler: Option<statements::BlockStatement>,
+    pub finalizer: Option<statements::BlockStatement>,
+}
+
+impl Node for Try {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for Try {
+    fn expression_node(&self) {}
+}
+
+// x?
+#[derive(Debug)]
+pub struct Propagate {
+    pub token: Token,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Node for Propagate {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for Propagate {
+    fn expression_node(&self) {}
+}
+
+// Member
+#[derive(Debug)]
+pub struct Member {
+    pub token: Token,
+    pub object: Option<Box<dyn Expression>>,
+    pub property: Identifier,
+}
+
+impl Node for Member {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for Member {
+    fn expression_node(&self) {}
+}
+
+// Super
+//
+// `super.name`, the method of the superclass bound to `self`. The class and
+// the instance are read from the variables `super` and `self`, which every
+// method of a subclass has.
+#[derive(Debug)]
+pub struct Super {
+    pub token: Token,
+    pub method: Identifier,
+    pub class: Identifier,
+    pub receiver: Identifier,
+}
+
+impl Node for Super {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for Super {
+    fn expression_node(&self) {}
+}
+
+// StructLiteral
+#[derive(Debug)]
+pub struct StructLiteral {
+    pub token: Token,
+    pub struct_type: Option<Box<dyn Expression>>,
+    pub fields: Vec<(Identifier, Box<dyn Expression>)>,
+    // A struct given as `...base`, whose fields fill in the ones not listed.
+    pub base: Option<Box<dyn Expression>>,
+}
+
+impl Node for StructLiteral {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for StructLiteral {
+    fn expression_node(&self) {}
+}
+
+// Match
+#[derive(Debug)]
+pub struct Match {
+    pub token: Token,
+    pub subject: Option<Box<dyn Expression>>,
+    pub arms: Vec<MatchArm>,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: patterns::MatchPattern,
+    pub body: Box<dyn Expression>,
+}
+
+impl Node for Match {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+impl Expression for Match {
+    fn expression_node(&self) {}
+}
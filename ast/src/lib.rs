@@ -1,3 +1,4 @@
 pub mod ast;
 pub mod expressions;
+pub mod patterns;
 pub mod statements;
@@ -0,0 +1,152 @@
+use crate::ast::{Expression, Node};
+use crate::expressions;
+use lexer::{Symbol, Token};
+use std::fmt::{Display, Formatter};
+
+// Pattern
+//
+// The left hand side of a binding. A plain identifier is the common case,
+// arrays and hashes can be taken apart and nested inside each other.
+#[derive(Debug)]
+pub enum Pattern {
+    Identifier(expressions::Identifier),
+    Array(ArrayPattern),
+    Hash(HashPattern),
+}
+
+impl Node for Pattern {
+    fn token_literal(&self) -> String {
+        match self {
+            Pattern::Identifier(identifier) => identifier.token_literal(),
+            Pattern::Array(array) => array.token_literal(),
+            Pattern::Hash(hash) => hash.token_literal(),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Pattern::Identifier(identifier) => write!(f, "{}", identifier.value),
+            Pattern::Array(array) => {
+                let mut elements: Vec<String> =
+                    array.elements.iter().map(|e| e.to_string()).collect();
+                if let Some(rest) = &array.rest {
+                    elements.push(format!("...{}", rest.value));
+                }
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Pattern::Hash(hash) => {
+                let entries: Vec<String> = hash
+                    .entries
+                    .iter()
+                    .map(|entry| match &entry.pattern {
+                        Pattern::Identifier(identifier)
+                            if identifier.value == entry.key.as_str() =>
+                        {
+                            entry.key.clone()
+                        }
+                        pattern => format!("{}: {}", entry.key, pattern),
+                    })
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+// [a, b, ...rest]
+#[derive(Debug)]
+pub struct ArrayPattern {
+    pub token: Token,
+    pub elements: Vec<Pattern>,
+    pub rest: Option<expressions::Identifier>,
+}
+
+impl Node for ArrayPattern {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+
+// {name, age: years}
+#[derive(Debug)]
+pub struct HashPattern {
+    pub token: Token,
+    pub entries: Vec<HashPatternEntry>,
+}
+
+#[derive(Debug)]
+pub struct HashPatternEntry {
+    pub key: String,
+    pub pattern: Pattern,
+}
+
+impl Node for HashPattern {
+    fn token_literal(&self) -> String {
+        return format!("[{}]", self.token);
+    }
+}
+
+// MatchPattern
+//
+// What an arm of a `match` compares the value with: `_`, a name that binds
+// the value, a literal, or a variant of an enum with patterns for its
+// fields, as in `Shape.Rect(w, _)`.
+#[derive(Debug)]
+pub enum MatchPattern {
+    Wildcard(Token),
+    Binding(expressions::Identifier),
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+    Variant(VariantPattern),
+}
+
+impl MatchPattern {
+    // Whether the pattern matches every value.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, MatchPattern::Wildcard(_) | MatchPattern::Binding(_))
+    }
+}
+
+#[derive(Debug)]
+pub struct VariantPattern {
+    pub token: Token,
+    pub enum_name: expressions::Identifier,
+    pub variant: expressions::Identifier,
+    pub fields: Vec<MatchPattern>,
+}
+
+// Parameter
+//
+// A single function parameter, `x`, `[a, b]` or `y = 10`.
+#[derive(Debug)]
+pub struct Parameter {
+    pub pattern: Pattern,
+    pub default: Option<Box<dyn Expression>>,
+}
+
+impl Parameter {
+    pub fn name(&self) -> Option<Symbol> {
+        match &self.pattern {
+            Pattern::Identifier(identifier) => Some(identifier.value),
+            _ => None,
+        }
+    }
+}
+
+impl Node for Parameter {
+    fn token_literal(&self) -> String {
+        self.pattern.token_literal()
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self.default {
+            Some(_) => write!(f, "{} = ...", self.pattern),
+            None => write!(f, "{}", self.pattern),
+        }
+    }
+}
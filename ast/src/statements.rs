@@ -1,5 +1,6 @@
 use crate::ast::{Expression, Node, Statement};
-use crate::expressions;
+use crate::expressions::{FunctionLiteral, Identifier};
+use crate::patterns;
 use lexer::Token;
 
 // Program
@@ -27,8 +28,10 @@ impl Statement for ProgramStatement {
 #[derive(Debug)]
 pub struct LetStatement {
     pub token: Token,
-    pub name: expressions::Identifier,
+    pub pattern: patterns::Pattern,
     pub value: Option<Box<dyn Expression>>,
+    // Set by `export let`, the names it binds can be read by importers.
+    pub exported: bool,
 }
 
 impl Node for LetStatement {
@@ -57,6 +60,175 @@ impl Statement for ReturnStatement {
     fn statement_node(&self) {}
 }
 
+// Throw
+#[derive(Debug)]
+pub struct ThrowStatement {
+    pub token: Token,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Node for ThrowStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for ThrowStatement {
+    fn statement_node(&self) {}
+}
+
+// Import
+#[derive(Debug)]
+pub struct ImportStatement {
+    pub token: Token,
+    pub path: String,
+    pub alias: Identifier,
+}
+
+impl Node for ImportStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for ImportStatement {
+    fn statement_node(&self) {}
+}
+
+// Struct
+#[derive(Debug)]
+pub struct StructStatement {
+    pub token: Token,
+    pub name: Identifier,
+    pub fields: Vec<Identifier>,
+    pub exported: bool,
+}
+
+impl Node for StructStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for StructStatement {
+    fn statement_node(&self) {}
+}
+
+// Enum
+#[derive(Debug)]
+pub struct EnumStatement {
+    pub token: Token,
+    pub name: Identifier,
+    pub variants: Vec<Variant>,
+    pub exported: bool,
+}
+
+// A variant and the names of its fields, `Rect(w, h)`. Variants without
+// fields are written without parentheses.
+#[derive(Debug)]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<Identifier>,
+}
+
+impl Node for EnumStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for EnumStatement {
+    fn statement_node(&self) {}
+}
+
+// For
+//
+// `for (x in xs) { ... }`, binding each value of `xs` to the pattern in
+// turn.
+#[derive(Debug)]
+pub struct ForStatement {
+    pub token: Token,
+    pub pattern: patterns::Pattern,
+    pub iterable: Option<Box<dyn Expression>>,
+    pub body: BlockStatement,
+}
+
+impl Node for ForStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for ForStatement {
+    fn statement_node(&self) {}
+}
+
+// Yield
+#[derive(Debug)]
+pub struct YieldStatement {
+    pub token: Token,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Node for YieldStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for YieldStatement {
+    fn statement_node(&self) {}
+}
+
+// Class
+#[derive(Debug)]
+pub struct ClassStatement {
+    pub token: Token,
+    pub name: Identifier,
+    pub superclass: Option<Identifier>,
+    pub methods: Vec<ClassMethod>,
+    pub exported: bool,
+}
+
+// A method, `fn area() { ... }`. It is called with `self` bound to the
+// instance, and `init` is called on each new instance.
+#[derive(Debug)]
+pub struct ClassMethod {
+    pub name: Identifier,
+    pub function: FunctionLiteral,
+}
+
+impl Node for ClassStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for ClassStatement {
+    fn statement_node(&self) {}
+}
+
+// Assign
+//
+// `object.field = value`, which only instances of classes allow.
+#[derive(Debug)]
+pub struct AssignStatement {
+    pub token: Token,
+    pub object: Option<Box<dyn Expression>>,
+    pub property: Identifier,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Node for AssignStatement {
+    fn token_literal(&self) -> String {
+        return format!("{}", self.token);
+    }
+}
+
+impl Statement for AssignStatement {
+    fn statement_node(&self) {}
+}
+
 // Expression
 #[derive(Debug)]
 pub struct ExpressionStatement {
@@ -0,0 +1,47 @@
+// Times reading a variable that holds an array, for arrays of different
+// sizes. Arrays are shared rather than copied, so the time per read should
+// stay the same however large the array is.
+//
+// The reads are packed into a few long function bodies. Each call creates
+// an environment, and enough of those trigger the cycle collector, which
+// does have to scan the array; that is not what is being measured here.
+//
+// Run with `cargo bench`.
+
+use evaluator::{Environment, Evaluator, Object};
+use lexer::{Lexer, Symbol};
+use parser::Parser;
+use std::rc::Rc;
+use std::time::Instant;
+
+const READS_PER_CALL: usize = 1_000;
+const CALLS: usize = 100;
+const SIZES: [usize; 4] = [1, 100, 10_000, 1_000_000];
+
+fn main() {
+    let input = format!(
+        "let read = fn(n) {{ if (n == 0) {{ 0 }} else {{ {} read(n - 1) }} }}; read({});",
+        "xs; ".repeat(READS_PER_CALL),
+        CALLS
+    );
+    let mut parser = Parser::new(Lexer::new(input.into_bytes()));
+    let program = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+    println!("{:>12} {:>14}", "array size", "ns per read");
+    for size in SIZES {
+        let env = Environment::new();
+        let xs: Vec<Object> = (0..size as i64).map(Object::Integer).collect();
+        env.borrow_mut()
+            .set(Symbol::intern("xs"), Object::Array(Rc::new(xs)));
+
+        let mut evaluator = Evaluator::new();
+        let start = Instant::now();
+        let result = evaluator.eval(&program, env);
+        let elapsed = start.elapsed();
+        assert_eq!(result.to_string(), "0");
+
+        let per_read = elapsed.as_nanos() / (READS_PER_CALL * CALLS) as u128;
+        println!("{:>12} {:>14}", size, per_read);
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Write;
+
+// Instructions are a single opcode byte followed by its operands, which are
+// all big endian u16s.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    // Pushes constants[operand].
+    Constant,
+    Null,
+    True,
+    False,
+    Pop,
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+
+    // Jumps to an absolute offset in the current function.
+    Jump,
+    // Pops the condition and jumps when it is not truthy.
+    JumpIfFalse,
+
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    // Locals captured by a closure live in a shared cell. `MakeCell` moves
+    // the slot's value into a new cell, the other two read and write
+    // through it.
+    MakeCell,
+    GetCell,
+    SetCell,
+    GetUpvalue,
+
+    // Builds an array from the top `operand` values.
+    Array,
+    // Builds a hash from the top `operand` key/value pairs.
+    Hash,
+    // Fails unless the value on top of the stack can be used as a hash key.
+    CheckHashKey,
+    Index,
+
+    // Creates a closure over constants[operand], capturing the function's
+    // upvalues from the current frame.
+    Closure,
+    // Names the closure on top of the stack after constants[operand],
+    // unless it already has a name.
+    SetName,
+    // Calls described by call_sites[operand] of the current function.
+    Call,
+    // As `Call`, replacing the current frame instead of pushing a new one.
+    TailCall,
+    Return,
+
+    // Jumps to the second operand when the parameter in slot operand was
+    // passed, skipping the code that evaluates its default.
+    JumpIfArg,
+    // Fails unless the parameter in the first operand's slot was passed,
+    // the second operand is its index in the parameter list.
+    RequireArg,
+    // Pops an array, checks it against array_patterns[operand] and pushes
+    // the rest of the array, if the pattern has one, then its elements in
+    // reverse.
+    DestructureArray,
+    // Fails unless the value on top of the stack is a hash. The operand
+    // is the constant describing the pattern, for errors.
+    CheckHash,
+    // Takes the key constants[first operand] out of the hash on top of the
+    // stack and pushes its value. The second operand is as for `CheckHash`.
+    HashEntry,
+}
+
+const OPCODES: [Opcode; 39] = [
+    Opcode::Constant,
+    Opcode::Null,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::Jump,
+    Opcode::JumpIfFalse,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::MakeCell,
+    Opcode::GetCell,
+    Opcode::SetCell,
+    Opcode::GetUpvalue,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::CheckHashKey,
+    Opcode::Index,
+    Opcode::Closure,
+    Opcode::SetName,
+    Opcode::Call,
+    Opcode::TailCall,
+    Opcode::Return,
+    Opcode::JumpIfArg,
+    Opcode::RequireArg,
+    Opcode::DestructureArray,
+    Opcode::CheckHash,
+    Opcode::HashEntry,
+];
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    pub fn operand_count(self) -> usize {
+        match self {
+            Opcode::Constant
+            | Opcode::Jump
+            | Opcode::JumpIfFalse
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::GetLocal
+            | Opcode::SetLocal
+            | Opcode::MakeCell
+            | Opcode::GetCell
+            | Opcode::SetCell
+            | Opcode::GetUpvalue
+            | Opcode::Array
+            | Opcode::Hash
+            | Opcode::Closure
+            | Opcode::SetName
+            | Opcode::Call
+            | Opcode::TailCall
+            | Opcode::DestructureArray
+            | Opcode::CheckHash => 1,
+            Opcode::JumpIfArg | Opcode::RequireArg | Opcode::HashEntry => 2,
+            _ => 0,
+        }
+    }
+}
+
+pub fn make(op: Opcode, operands: &[u16]) -> Vec<u8> {
+    let mut instruction = vec![op as u8];
+    for operand in operands {
+        instruction.extend_from_slice(&operand.to_be_bytes());
+    }
+    instruction
+}
+
+pub fn read_u16(instructions: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([instructions[offset], instructions[offset + 1]])
+}
+
+// One instruction per line, prefixed with its offset.
+pub fn disassemble(instructions: &[u8]) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+    while offset < instructions.len() {
+        let op = match Opcode::from_byte(instructions[offset]) {
+            Some(op) => op,
+            None => {
+                let _ = writeln!(out, "{:04} unknown opcode {}", offset, instructions[offset]);
+                offset += 1;
+                continue;
+            }
+        };
+        let _ = write!(out, "{:04} {:?}", offset, op);
+        for i in 0..op.operand_count() {
+            let _ = write!(out, " {}", read_u16(instructions, offset + 1 + i * 2));
+        }
+        out.push('\n');
+        offset += 1 + op.operand_count() * 2;
+    }
+    out
+}
@@ -0,0 +1,804 @@
+pub mod code;
+mod scope;
+
+use ast::ast::{Expression, Statement};
+use ast::{expressions, patterns, statements};
+use code::Opcode;
+use lexer::{Span, Symbol};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// Builtins the evaluator provides that the VM does not.
+const UNSUPPORTED_BUILTINS: [&str; 3] = ["gc", "ok", "err"];
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Integer(i64),
+    String(String),
+    Function(Rc<CompiledFunction>),
+}
+
+// Where a closure finds one of its upvalues when it is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    // The cell in this slot of the enclosing frame.
+    Local(u16),
+    // The enclosing closure's upvalue at this index.
+    Upvalue(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    pub span: Span,
+    // Number of positional arguments. The values of the named arguments
+    // follow them on the stack, in the order of `names`.
+    pub args: usize,
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayShape {
+    pub len: usize,
+    pub rest: bool,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParameterInfo {
+    // Set when the parameter can be passed by name.
+    pub name: Option<String>,
+    pub pattern: String,
+    pub has_default: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompiledFunction {
+    pub instructions: Vec<u8>,
+    // Slots 0 to parameters.len() hold the arguments, followed by the rest
+    // parameter if there is one, and then the function's other locals.
+    pub num_locals: usize,
+    pub parameters: Vec<ParameterInfo>,
+    pub rest: Option<String>,
+    pub local_names: Vec<String>,
+    pub upvalue_names: Vec<String>,
+    pub captures: Vec<Capture>,
+    pub call_sites: Vec<CallSite>,
+    pub array_shapes: Vec<ArrayShape>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bytecode {
+    pub main: Rc<CompiledFunction>,
+    pub constants: Vec<Constant>,
+    // Names of the global slots, indexed like the VM's globals.
+    pub globals: Vec<String>,
+}
+
+// Where the compiled statement or expression sits, mirroring the
+// evaluator's notion of position so that the same calls become tail calls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Position {
+    Plain,
+    Function,
+    Tail,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Variable {
+    Global(u16),
+    Local(u16),
+    Cell(u16),
+    Upvalue(u16),
+}
+
+#[derive(Default)]
+struct FunctionScope {
+    function: CompiledFunction,
+    locals: HashMap<Symbol, u16>,
+    cells: HashSet<u16>,
+    // Locals the body binds that have not been bound yet where the compiler
+    // is. Reading one reads the variable of the same name further out.
+    unbound: HashSet<Symbol>,
+}
+
+pub struct Compiler {
+    pub errors: Vec<String>,
+    constants: Vec<Constant>,
+    globals: HashMap<Symbol, u16>,
+    global_names: Vec<String>,
+    // Names bound at the top level of the programs compiled so far.
+    declared_globals: HashSet<Symbol>,
+    // The innermost function being compiled is last, the program itself is
+    // first.
+    scopes: Vec<FunctionScope>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            errors: Vec::new(),
+            constants: Vec::new(),
+            globals: HashMap::new(),
+            global_names: Vec::new(),
+            declared_globals: HashSet::new(),
+            scopes: Vec::new(),
+        }
+    }
+
+    // Globals keep their slots across calls, so a VM that is reused with
+    // the same compiler sees the globals defined by earlier programs.
+    pub fn compile(&mut self, program: &statements::ProgramStatement) -> Bytecode {
+        self.errors.clear();
+        self.constants.clear();
+        self.declared_globals
+            .extend(scope::Walk::program(program).declared);
+        self.scopes = vec![FunctionScope::default()];
+
+        let statements: Vec<Option<&dyn Statement>> =
+            program.body.iter().map(|s| Some(s.as_ref())).collect();
+        self.compile_statements(&statements, Position::Plain);
+        self.emit(Opcode::Return, &[]);
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        Bytecode {
+            main: Rc::new(scope.function),
+            constants: std::mem::take(&mut self.constants),
+            globals: self.global_names.clone(),
+        }
+    }
+
+    fn scope(&mut self) -> &mut FunctionScope {
+        self.scopes.last_mut().expect("no scope to compile into")
+    }
+
+    fn emit(&mut self, op: Opcode, operands: &[u16]) -> usize {
+        let instructions = &mut self.scope().function.instructions;
+        let position = instructions.len();
+        instructions.extend(code::make(op, operands));
+        position
+    }
+
+    // Points the jump at `position` to the end of the code emitted so far.
+    fn patch_jump(&mut self, position: usize, operand: usize) {
+        let end = self.scope().function.instructions.len();
+        let target = self.operand(end);
+        let offset = position + 1 + operand * 2;
+        self.scope().function.instructions[offset..offset + 2]
+            .copy_from_slice(&target.to_be_bytes());
+    }
+
+    fn operand(&mut self, value: usize) -> u16 {
+        match u16::try_from(value) {
+            Ok(value) => value,
+            Err(_) => {
+                self.errors
+                    .push(format!("operand {} does not fit in an instruction", value));
+                0
+            }
+        }
+    }
+
+    fn add_constant(&mut self, constant: Constant) -> u16 {
+        self.constants.push(constant);
+        self.operand(self.constants.len() - 1)
+    }
+
+    fn add_string(&mut self, value: &str) -> u16 {
+        let existing = self
+            .constants
+            .iter()
+            .position(|c| matches!(c, Constant::String(s) if s == value));
+        match existing {
+            Some(index) => self.operand(index),
+            None => self.add_constant(Constant::String(value.to_string())),
+        }
+    }
+
+    fn global(&mut self, name: Symbol) -> u16 {
+        if let Some(index) = self.globals.get(&name) {
+            return *index;
+        }
+        let index = self.operand(self.global_names.len());
+        self.globals.insert(name, index);
+        self.global_names.push(name.to_string());
+        index
+    }
+
+    fn resolve(&mut self, name: Symbol) -> Variable {
+        match self.resolve_in(self.scopes.len() - 1, name) {
+            Some(variable) => variable,
+            None => Variable::Global(self.global(name)),
+        }
+    }
+
+    fn resolve_in(&mut self, level: usize, name: Symbol) -> Option<Variable> {
+        if level == 0 {
+            return None;
+        }
+        let scope = &self.scopes[level];
+        let later = level == self.scopes.len() - 1 && scope.unbound.contains(&name);
+        if let Some(slot) = scope.locals.get(&name).copied().filter(|_| !later) {
+            return Some(match self.scopes[level].cells.contains(&slot) {
+                true => Variable::Cell(slot),
+                false => Variable::Local(slot),
+            });
+        }
+        let capture = match self.resolve_in(level - 1, name)? {
+            Variable::Cell(slot) | Variable::Local(slot) => Capture::Local(slot),
+            Variable::Upvalue(index) => Capture::Upvalue(index),
+            Variable::Global(index) => return Some(Variable::Global(index)),
+        };
+
+        let function = &mut self.scopes[level].function;
+        if let Some(index) = function.captures.iter().position(|c| *c == capture) {
+            return Some(Variable::Upvalue(index as u16));
+        }
+        function.captures.push(capture);
+        function.upvalue_names.push(name.to_string());
+        Some(Variable::Upvalue(function.captures.len() as u16 - 1))
+    }
+
+    // Where a binding made in the current scope is stored. Every name a
+    // function binds was given a slot up front.
+    fn binding(&mut self, name: Symbol) -> Variable {
+        if self.scopes.len() == 1 {
+            return Variable::Global(self.global(name));
+        }
+        let scope = self.scope();
+        scope.unbound.remove(&name);
+        let slot = scope.locals[&name];
+        match scope.cells.contains(&slot) {
+            true => Variable::Cell(slot),
+            false => Variable::Local(slot),
+        }
+    }
+
+    fn emit_get(&mut self, variable: Variable) {
+        match variable {
+            Variable::Global(index) => self.emit(Opcode::GetGlobal, &[index]),
+            Variable::Local(slot) => self.emit(Opcode::GetLocal, &[slot]),
+            Variable::Cell(slot) => self.emit(Opcode::GetCell, &[slot]),
+            Variable::Upvalue(index) => self.emit(Opcode::GetUpvalue, &[index]),
+        };
+    }
+
+    fn emit_set(&mut self, variable: Variable) {
+        match variable {
+            Variable::Global(index) => self.emit(Opcode::SetGlobal, &[index]),
+            Variable::Local(slot) => self.emit(Opcode::SetLocal, &[slot]),
+            Variable::Cell(slot) => self.emit(Opcode::SetCell, &[slot]),
+            Variable::Upvalue(_) => unreachable!("bindings are never made in an upvalue"),
+        };
+    }
+
+    // Leaves the value of the last statement on the stack, or null.
+    fn compile_statements(&mut self, statements: &[Option<&dyn Statement>], position: Position) {
+        let last = statements.iter().rposition(|s| s.is_some());
+        let last = match last {
+            Some(last) => last,
+            None => {
+                self.emit(Opcode::Null, &[]);
+                return;
+            }
+        };
+
+        for (i, statement) in statements.iter().enumerate() {
+            let statement = match statement {
+                Some(statement) => *statement,
+                None => continue,
+            };
+            let statement_position = match position {
+                Position::Tail if i != last => Position::Function,
+                position => position,
+            };
+            let pushed = self.compile_statement(statement, statement_position);
+            if i != last && pushed {
+                self.emit(Opcode::Pop, &[]);
+            } else if i == last && !pushed {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+    }
+
+    fn compile_block(&mut self, block: &statements::BlockStatement, position: Position) {
+        let statements: Vec<Option<&dyn Statement>> =
+            block.statements.iter().map(|s| s.as_deref()).collect();
+        self.compile_statements(&statements, position);
+    }
+
+    // Returns whether the statement left a value on the stack.
+    fn compile_statement(&mut self, statement: &dyn Statement, position: Position) -> bool {
+        if let Some(let_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::LetStatement>()
+        {
+            match &let_stmt.value {
+                Some(expr) => self.compile_expression(expr.as_ref()),
+                None => {
+                    self.emit(Opcode::Null, &[]);
+                }
+            }
+            if let (patterns::Pattern::Identifier(identifier), Some(expr)) =
+                (&let_stmt.pattern, &let_stmt.value)
+            {
+                if may_be_function(expr.as_ref()) {
+                    let name = self.add_string(identifier.value.as_str());
+                    self.emit(Opcode::SetName, &[name]);
+                }
+            }
+            self.compile_binding(&let_stmt.pattern);
+            return false;
+        }
+
+        if let Some(return_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ReturnStatement>()
+        {
+            match &return_stmt.return_value {
+                Some(expr) if position == Position::Plain => self.compile_expression(expr.as_ref()),
+                Some(expr) => self.compile_tail_expression(expr.as_ref()),
+                None => {
+                    self.emit(Opcode::Null, &[]);
+                }
+            }
+            self.emit(Opcode::Return, &[]);
+            return true;
+        }
+
+        if let Some(expr_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ExpressionStatement>()
+        {
+            let expr = match &expr_stmt.expression {
+                Some(expr) => expr,
+                None => {
+                    self.emit(Opcode::Null, &[]);
+                    return true;
+                }
+            };
+            match position {
+                Position::Plain => self.compile_expression(expr.as_ref()),
+                Position::Function => match expr.as_any().downcast_ref::<expressions::If>() {
+                    Some(if_expr) => self.compile_if(if_expr, Position::Function),
+                    None => self.compile_expression(expr.as_ref()),
+                },
+                Position::Tail => self.compile_tail_expression(expr.as_ref()),
+            }
+            return true;
+        }
+
+        if statement.as_any().is::<statements::ThrowStatement>() {
+            self.errors
+                .push("throw is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::ImportStatement>() {
+            self.errors
+                .push("import is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::StructStatement>() {
+            self.errors
+                .push("struct is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::EnumStatement>() {
+            self.errors
+                .push("enum is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::ClassStatement>() {
+            self.errors
+                .push("class is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::AssignStatement>() {
+            self.errors
+                .push("field assignment is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::ForStatement>() {
+            self.errors
+                .push("for is not supported by the vm".to_string());
+        } else if statement.as_any().is::<statements::YieldStatement>() {
+            self.errors
+                .push("yield is not supported by the vm".to_string());
+        } else {
+            self.errors
+                .push(format!("cannot compile {}", statement.token_literal()));
+        }
+        self.emit(Opcode::Null, &[]);
+        true
+    }
+
+    // Binds the value on top of the stack, popping it.
+    fn compile_binding(&mut self, pattern: &patterns::Pattern) {
+        match pattern {
+            patterns::Pattern::Identifier(identifier) => {
+                let variable = self.binding(identifier.value);
+                self.emit_set(variable);
+            }
+            patterns::Pattern::Array(array) => {
+                let description = match array.rest {
+                    Some(_) => format!("array of at least {} elements", array.elements.len()),
+                    None => format!("array of {} elements", array.elements.len()),
+                };
+                let shapes = &mut self.scope().function.array_shapes;
+                shapes.push(ArrayShape {
+                    len: array.elements.len(),
+                    rest: array.rest.is_some(),
+                    description,
+                });
+                let shape = shapes.len() - 1;
+                let shape = self.operand(shape);
+                self.emit(Opcode::DestructureArray, &[shape]);
+                for element in array.elements.iter() {
+                    self.compile_binding(element);
+                }
+                if let Some(rest) = &array.rest {
+                    let variable = self.binding(rest.value);
+                    self.emit_set(variable);
+                }
+            }
+            patterns::Pattern::Hash(hash) => {
+                let keys: Vec<String> = hash
+                    .entries
+                    .iter()
+                    .map(|entry| format!("\"{}\"", entry.key))
+                    .collect();
+                let description = self.add_string(&format!("hash with keys {}", keys.join(", ")));
+                self.emit(Opcode::CheckHash, &[description]);
+                for entry in hash.entries.iter() {
+                    let key = self.add_string(&entry.key);
+                    self.emit(Opcode::HashEntry, &[key, description]);
+                    self.compile_binding(&entry.pattern);
+                }
+                self.emit(Opcode::Pop, &[]);
+            }
+        }
+    }
+
+    fn compile_tail_expression(&mut self, expression: &dyn Expression) {
+        if let Some(call) = expression
+            .as_any()
+            .downcast_ref::<expressions::CallExpression>()
+        {
+            return self.compile_call(call, Opcode::TailCall);
+        }
+        if let Some(if_expr) = expression.as_any().downcast_ref::<expressions::If>() {
+            return self.compile_if(if_expr, Position::Tail);
+        }
+        self.compile_expression(expression)
+    }
+
+    fn compile_optional(&mut self, expression: &Option<Box<dyn Expression>>, what: &str) {
+        match expression {
+            Some(expression) => self.compile_expression(expression.as_ref()),
+            None => {
+                self.errors.push(format!("missing {}", what));
+            }
+        }
+    }
+
+    fn compile_expression(&mut self, expression: &dyn Expression) {
+        let any = expression.as_any();
+
+        if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+            let variable = self.resolve(identifier.value);
+            if let Variable::Global(_) = variable {
+                // Like the evaluator's resolver, a variable that is bound
+                // nowhere is an error even if it is never read.
+                let name = identifier.value;
+                if !self.declared_globals.contains(&name) {
+                    let error = match UNSUPPORTED_BUILTINS.contains(&name.as_str()) {
+                        true => format!("{}() is not supported by the vm", name),
+                        false => format!("identifier not found: {}", name),
+                    };
+                    self.errors.push(error);
+                }
+            }
+            return self.emit_get(variable);
+        }
+
+        if let Some(literal) = any.downcast_ref::<expressions::IntegerLiteral>() {
+            let constant = self.add_constant(Constant::Integer(literal.value));
+            self.emit(Opcode::Constant, &[constant]);
+            return;
+        }
+
+        if let Some(literal) = any.downcast_ref::<expressions::Bool>() {
+            match literal.value {
+                true => self.emit(Opcode::True, &[]),
+                false => self.emit(Opcode::False, &[]),
+            };
+            return;
+        }
+
+        if let Some(literal) = any.downcast_ref::<expressions::StringLiteral>() {
+            let constant = self.add_string(&literal.value);
+            self.emit(Opcode::Constant, &[constant]);
+            return;
+        }
+
+        if let Some(array) = any.downcast_ref::<expressions::ArrayLiteral>() {
+            for element in array.elements.iter() {
+                self.compile_expression(element.as_ref());
+            }
+            let len = self.operand(array.elements.len());
+            self.emit(Opcode::Array, &[len]);
+            return;
+        }
+
+        if let Some(hash) = any.downcast_ref::<expressions::HashLiteral>() {
+            for (key, value) in hash.pairs.iter() {
+                self.compile_expression(key.as_ref());
+                self.emit(Opcode::CheckHashKey, &[]);
+                self.compile_expression(value.as_ref());
+            }
+            let len = self.operand(hash.pairs.len());
+            self.emit(Opcode::Hash, &[len]);
+            return;
+        }
+
+        if let Some(index) = any.downcast_ref::<expressions::IndexExpression>() {
+            self.compile_optional(&index.left, "index target");
+            self.compile_optional(&index.index, "index expression");
+            self.emit(Opcode::Index, &[]);
+            return;
+        }
+
+        if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+            self.compile_optional(&prefix.right, "right expression");
+            match prefix.operator.as_str() {
+                "!" => self.emit(Opcode::Bang, &[]),
+                "-" => self.emit(Opcode::Minus, &[]),
+                operator => {
+                    self.errors.push(format!("unknown operator: {}", operator));
+                    return;
+                }
+            };
+            return;
+        }
+
+        if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+            self.compile_optional(&infix.left, "left expression");
+            self.compile_optional(&infix.right, "right expression");
+            let op = match infix.operator.as_str() {
+                "+" => Opcode::Add,
+                "-" => Opcode::Sub,
+                "*" => Opcode::Mul,
+                "/" => Opcode::Div,
+                "==" => Opcode::Equal,
+                "!=" => Opcode::NotEqual,
+                ">" => Opcode::GreaterThan,
+                "<" => Opcode::LessThan,
+                operator => {
+                    self.errors.push(format!("unknown operator: {}", operator));
+                    return;
+                }
+            };
+            self.emit(op, &[]);
+            return;
+        }
+
+        if let Some(if_expr) = any.downcast_ref::<expressions::If>() {
+            return self.compile_if(if_expr, Position::Plain);
+        }
+
+        if any.is::<expressions::Try>() {
+            self.errors
+                .push("try is not supported by the vm".to_string());
+            return;
+        }
+
+        if any.is::<expressions::Propagate>() {
+            self.errors.push("? is not supported by the vm".to_string());
+            return;
+        }
+
+        if any.is::<expressions::Member>() {
+            self.errors
+                .push("member access is not supported by the vm".to_string());
+            return;
+        }
+
+        if any.is::<expressions::StructLiteral>() {
+            self.errors
+                .push("struct literals are not supported by the vm".to_string());
+            return;
+        }
+
+        if any.is::<expressions::Super>() {
+            self.errors
+                .push("super is not supported by the vm".to_string());
+            return;
+        }
+
+        if any.is::<expressions::Match>() {
+            self.errors
+                .push("match is not supported by the vm".to_string());
+            return;
+        }
+
+        if let Some(func) = any.downcast_ref::<expressions::FunctionLiteral>() {
+            if func.generator {
+                self.errors
+                    .push("generators are not supported by the vm".to_string());
+                return;
+            }
+            return self.compile_function(func);
+        }
+
+        if let Some(call) = any.downcast_ref::<expressions::CallExpression>() {
+            return self.compile_call(call, Opcode::Call);
+        }
+
+        self.errors
+            .push(format!("cannot compile {}", expression.token_literal()));
+    }
+
+    fn compile_if(&mut self, if_expr: &expressions::If, position: Position) {
+        self.compile_optional(&if_expr.condition, "if condition");
+        let jump_if_false = self.emit(Opcode::JumpIfFalse, &[u16::MAX]);
+        match &if_expr.first {
+            Some(block) => self.compile_block(block, position),
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+        let jump = self.emit(Opcode::Jump, &[u16::MAX]);
+        self.patch_jump(jump_if_false, 0);
+        match &if_expr.second {
+            Some(block) => self.compile_block(block, position),
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+        self.patch_jump(jump, 0);
+    }
+
+    fn compile_call(&mut self, call: &expressions::CallExpression, op: Opcode) {
+        self.compile_optional(&call.function, "function expression");
+        let args = match &call.arguments {
+            Some(args) => args.as_slice(),
+            None => &[],
+        };
+        for arg in args.iter() {
+            self.compile_expression(arg.as_ref());
+        }
+        for argument in call.named_arguments.iter() {
+            self.compile_expression(argument.value.as_ref());
+        }
+
+        let call_sites = &mut self.scope().function.call_sites;
+        call_sites.push(CallSite {
+            span: call.token.span,
+            args: args.len(),
+            names: call
+                .named_arguments
+                .iter()
+                .map(|argument| argument.name.value.to_string())
+                .collect(),
+        });
+        let site = call_sites.len() - 1;
+        let site = self.operand(site);
+        self.emit(op, &[site]);
+    }
+
+    fn compile_function(&mut self, func: &expressions::FunctionLiteral) {
+        let walk = scope::Walk::function(func);
+        let captured = scope::captured_names(&walk);
+        let empty = Vec::new();
+        let parameters = match &func.parameters {
+            Some(parameters) => parameters.as_ref(),
+            None => &empty,
+        };
+
+        // Arguments arrive in the first slots. A parameter that is a plain
+        // name, unique among the parameters, is bound in place. The others
+        // keep a slot of their own and are bound by the prologue.
+        let mut scope = FunctionScope::default();
+        let names: Vec<Option<Symbol>> = parameters.iter().map(|p| p.name()).collect();
+        let mut bound = Vec::new();
+        for parameter in parameters.iter() {
+            scope::pattern_names(&parameter.pattern, &mut bound);
+        }
+        let rest_name = func.rest.as_ref().map(|rest| rest.value);
+        let is_unique = |name: Symbol| {
+            bound.iter().filter(|n| **n == name).count() == 1 && rest_name != Some(name)
+        };
+        for (slot, name) in names.iter().enumerate() {
+            match name {
+                Some(name) if is_unique(*name) => {
+                    scope.locals.insert(*name, slot as u16);
+                    scope.function.local_names.push(name.to_string());
+                }
+                _ => scope.function.local_names.push(String::new()),
+            }
+        }
+        if let Some(rest) = &func.rest {
+            if !bound.contains(&rest.value) {
+                scope.locals.insert(rest.value, parameters.len() as u16);
+            }
+            scope.function.local_names.push(rest.value.to_string());
+        }
+        for name in walk.declared.iter() {
+            if !scope.locals.contains_key(name) {
+                let slot = scope.function.local_names.len();
+                scope.locals.insert(*name, slot as u16);
+                scope.function.local_names.push(name.to_string());
+            }
+        }
+        let mut cells: Vec<u16> = captured.iter().map(|name| scope.locals[name]).collect();
+        cells.sort();
+        scope.cells = cells.iter().copied().collect();
+        scope.function.num_locals = scope.function.local_names.len();
+        scope.function.parameters = parameters
+            .iter()
+            .map(|p| ParameterInfo {
+                name: p.name().map(|name| name.to_string()),
+                pattern: p.pattern.to_string(),
+                has_default: p.default.is_some(),
+            })
+            .collect();
+        scope.function.rest = func.rest.as_ref().map(|rest| rest.value.to_string());
+        self.scopes.push(scope);
+
+        for slot in cells {
+            self.emit(Opcode::MakeCell, &[slot]);
+        }
+        for (index, parameter) in parameters.iter().enumerate() {
+            let slot = index as u16;
+            let in_place = matches!(names[index], Some(name) if is_unique(name));
+            let arg = match (in_place, names[index]) {
+                (true, Some(name)) => self.binding(name),
+                _ => Variable::Local(slot),
+            };
+            match &parameter.default {
+                Some(default) => {
+                    let jump = self.emit(Opcode::JumpIfArg, &[slot, u16::MAX]);
+                    self.compile_expression(default.as_ref());
+                    self.emit_set(arg);
+                    self.patch_jump(jump, 1);
+                }
+                None => {
+                    self.emit(Opcode::RequireArg, &[slot, slot]);
+                }
+            }
+            if !in_place {
+                self.emit_get(arg);
+                self.compile_binding(&parameter.pattern);
+            }
+        }
+        if let Some(rest) = &func.rest {
+            if bound.contains(&rest.value) {
+                self.emit(Opcode::GetLocal, &[parameters.len() as u16]);
+                let variable = self.binding(rest.value);
+                self.emit_set(variable);
+            }
+        }
+
+        self.scope().unbound = walk
+            .declared
+            .iter()
+            .filter(|name| !bound.contains(name) && rest_name != Some(**name))
+            .copied()
+            .collect();
+        match &func.body {
+            Some(body) => self.compile_block(body, Position::Tail),
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+        self.emit(Opcode::Return, &[]);
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        let constant = self.add_constant(Constant::Function(Rc::new(scope.function)));
+        self.emit(Opcode::Closure, &[constant]);
+    }
+}
+
+// Whether `let` might bind a function with this value, and so should name
+// it.
+fn may_be_function(expression: &dyn Expression) -> bool {
+    let any = expression.as_any();
+    any.is::<expressions::FunctionLiteral>()
+        || any.is::<expressions::Identifier>()
+        || any.is::<expressions::CallExpression>()
+        || any.is::<expressions::IndexExpression>()
+        || any.is::<expressions::If>()
+}
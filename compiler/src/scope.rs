@@ -0,0 +1,224 @@
+use ast::ast::{Expression, Statement};
+use ast::{expressions, patterns, statements};
+use lexer::Symbol;
+use std::collections::HashSet;
+
+// What a function body refers to, not counting the bodies of functions
+// nested inside it.
+#[derive(Default)]
+pub(crate) struct Walk<'a> {
+    // Names bound by the function's parameters and `let` statements, and by
+    // the other statements that bind a name, in order of first appearance.
+    // As in the evaluator, blocks do not introduce scopes of their own.
+    pub declared: Vec<Symbol>,
+    pub used: HashSet<Symbol>,
+    pub nested: Vec<&'a expressions::FunctionLiteral>,
+}
+
+impl<'a> Walk<'a> {
+    pub fn program(program: &'a statements::ProgramStatement) -> Walk<'a> {
+        let mut walk = Walk::default();
+        for statement in program.body.iter() {
+            walk.statement(statement.as_ref());
+        }
+        walk
+    }
+
+    pub fn function(func: &'a expressions::FunctionLiteral) -> Walk<'a> {
+        let mut walk = Walk::default();
+        if let Some(parameters) = &func.parameters {
+            for parameter in parameters.iter() {
+                walk.pattern(&parameter.pattern);
+            }
+            if let Some(rest) = &func.rest {
+                walk.declare(rest.value);
+            }
+            for parameter in parameters.iter() {
+                if let Some(default) = &parameter.default {
+                    walk.expression(default.as_ref());
+                }
+            }
+        }
+        if let Some(body) = &func.body {
+            walk.block(body);
+        }
+        walk
+    }
+
+    fn declare(&mut self, name: Symbol) {
+        if !self.declared.contains(&name) {
+            self.declared.push(name);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &patterns::Pattern) {
+        match pattern {
+            patterns::Pattern::Identifier(identifier) => self.declare(identifier.value),
+            patterns::Pattern::Array(array) => {
+                for element in array.elements.iter() {
+                    self.pattern(element);
+                }
+                if let Some(rest) = &array.rest {
+                    self.declare(rest.value);
+                }
+            }
+            patterns::Pattern::Hash(hash) => {
+                for entry in hash.entries.iter() {
+                    self.pattern(&entry.pattern);
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, block: &'a statements::BlockStatement) {
+        for statement in block.statements.iter().flatten() {
+            self.statement(statement.as_ref());
+        }
+    }
+
+    fn statement(&mut self, statement: &'a dyn Statement) {
+        if let Some(let_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::LetStatement>()
+        {
+            if let Some(value) = &let_stmt.value {
+                self.expression(value.as_ref());
+            }
+            self.pattern(&let_stmt.pattern);
+        } else if let Some(return_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ReturnStatement>()
+        {
+            if let Some(value) = &return_stmt.return_value {
+                self.expression(value.as_ref());
+            }
+        } else if let Some(expr_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ExpressionStatement>()
+        {
+            if let Some(expr) = &expr_stmt.expression {
+                self.expression(expr.as_ref());
+            }
+        } else if let Some(name) = declared_name(statement) {
+            // The vm does not support these, but the names they bind are
+            // still not undefined.
+            self.declare(name);
+        }
+    }
+
+    fn expressions(&mut self, expressions: &'a [Box<dyn Expression>]) {
+        for expression in expressions.iter() {
+            self.expression(expression.as_ref());
+        }
+    }
+
+    fn optional(&mut self, expression: &'a Option<Box<dyn Expression>>) {
+        if let Some(expression) = expression {
+            self.expression(expression.as_ref());
+        }
+    }
+
+    fn expression(&mut self, expression: &'a dyn Expression) {
+        let any = expression.as_any();
+        if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+            self.used.insert(identifier.value);
+        } else if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+            self.optional(&prefix.right);
+        } else if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+            self.optional(&infix.left);
+            self.optional(&infix.right);
+        } else if let Some(if_expr) = any.downcast_ref::<expressions::If>() {
+            self.optional(&if_expr.condition);
+            if let Some(block) = &if_expr.first {
+                self.block(block);
+            }
+            if let Some(block) = &if_expr.second {
+                self.block(block);
+            }
+        } else if let Some(func) = any.downcast_ref::<expressions::FunctionLiteral>() {
+            self.nested.push(func);
+        } else if let Some(call) = any.downcast_ref::<expressions::CallExpression>() {
+            self.optional(&call.function);
+            if let Some(arguments) = &call.arguments {
+                self.expressions(arguments);
+            }
+            for argument in call.named_arguments.iter() {
+                self.expression(argument.value.as_ref());
+            }
+        } else if let Some(array) = any.downcast_ref::<expressions::ArrayLiteral>() {
+            self.expressions(&array.elements);
+        } else if let Some(hash) = any.downcast_ref::<expressions::HashLiteral>() {
+            for (key, value) in hash.pairs.iter() {
+                self.expression(key.as_ref());
+                self.expression(value.as_ref());
+            }
+        } else if let Some(index) = any.downcast_ref::<expressions::IndexExpression>() {
+            self.optional(&index.left);
+            self.optional(&index.index);
+        }
+    }
+}
+
+// Names bound by a pattern, including repeats.
+pub(crate) fn pattern_names(pattern: &patterns::Pattern, names: &mut Vec<Symbol>) {
+    match pattern {
+        patterns::Pattern::Identifier(identifier) => names.push(identifier.value),
+        patterns::Pattern::Array(array) => {
+            for element in array.elements.iter() {
+                pattern_names(element, names);
+            }
+            if let Some(rest) = &array.rest {
+                names.push(rest.value);
+            }
+        }
+        patterns::Pattern::Hash(hash) => {
+            for entry in hash.entries.iter() {
+                pattern_names(&entry.pattern, names);
+            }
+        }
+    }
+}
+
+// Names a function refers to that are bound outside of it.
+pub(crate) fn free_names(func: &expressions::FunctionLiteral) -> HashSet<Symbol> {
+    let walk = Walk::function(func);
+    let mut used = walk.used;
+    for nested in walk.nested.iter() {
+        used.extend(free_names(nested));
+    }
+    for name in walk.declared.iter() {
+        used.remove(name);
+    }
+    used
+}
+
+// Names declared by a function that functions nested in it refer to. These
+// have to outlive the call, so they are kept in cells rather than directly
+// in the frame.
+pub(crate) fn captured_names(walk: &Walk) -> HashSet<Symbol> {
+    let mut free = HashSet::new();
+    for nested in walk.nested.iter() {
+        free.extend(free_names(nested));
+    }
+    walk.declared
+        .iter()
+        .filter(|name| free.contains(*name))
+        .cloned()
+        .collect()
+}
+
+// The name bound by an `import`, `struct`, `enum` or `class` statement.
+fn declared_name(statement: &dyn Statement) -> Option<Symbol> {
+    let any = statement.as_any();
+    if let Some(import) = any.downcast_ref::<statements::ImportStatement>() {
+        return Some(import.alias.value);
+    }
+    if let Some(struct_stmt) = any.downcast_ref::<statements::StructStatement>() {
+        return Some(struct_stmt.name.value);
+    }
+    if let Some(enum_stmt) = any.downcast_ref::<statements::EnumStatement>() {
+        return Some(enum_stmt.name.value);
+    }
+    any.downcast_ref::<statements::ClassStatement>()
+        .map(|class_stmt| class_stmt.name.value)
+}
@@ -0,0 +1,55 @@
+use crate::new_error;
+use crate::object::{Builtin, ErrorKind, Object};
+use crate::Evaluator;
+
+pub(crate) fn lookup(name: &str) -> Option<Builtin> {
+    match name {
+        "gc" => Some(Builtin {
+            name: "gc",
+            func: gc,
+        }),
+        "ok" => Some(Builtin {
+            name: "ok",
+            func: ok,
+        }),
+        "err" => Some(Builtin {
+            name: "err",
+            func: err,
+        }),
+        _ => None,
+    }
+}
+
+// gc() runs the collector and returns how many environments it freed.
+fn gc(evaluator: &mut Evaluator, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("gc", 0, args.len());
+    }
+    Object::Integer(evaluator.gc() as i64)
+}
+
+// ok(v) and err(e) wrap a value as the result of something that succeeded
+// or failed. `?` unwraps an `ok` and returns an `err` from the function.
+fn ok(_: &mut Evaluator, mut args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments("ok", 1, args.len());
+    }
+    Object::Ok(Box::new(args.remove(0)))
+}
+
+fn err(_: &mut Evaluator, mut args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments("err", 1, args.len());
+    }
+    Object::Err(Box::new(args.remove(0)))
+}
+
+pub(crate) fn wrong_arguments(name: &str, expected: usize, given: usize) -> Object {
+    new_error(
+        ErrorKind::Arguments,
+        format!(
+            "wrong number of arguments in call to {}: expected {}, got {}",
+            name, expected, given
+        ),
+    )
+}
@@ -0,0 +1,122 @@
+use crate::object::Object;
+use ast::expressions::Slot;
+use lexer::Symbol;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+// Globals are kept by name in `store`. Each function call gets a frame
+// whose locals live in `slots`, at the indices the resolver assigned them.
+// A slot is `None` until its variable is bound.
+#[derive(Debug)]
+pub struct Environment {
+    store: HashMap<Symbol, Object>,
+    slots: Vec<Option<Object>>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            slots: Vec::new(),
+            outer: None,
+        }))
+    }
+
+    pub fn new_enclosed(outer: Env) -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            slots: Vec::new(),
+            outer: Some(outer),
+        }))
+    }
+
+    pub(crate) fn new_frame(outer: Env, size: usize) -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            slots: vec![None; size],
+            outer: Some(outer),
+        }))
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<Object> {
+        match self.store.get(&name) {
+            Some(obj) => Some(obj.clone()),
+            None => match &self.outer {
+                Some(outer) => outer.borrow().get(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn set(&mut self, name: Symbol, val: Object) -> Object {
+        self.store.insert(name, val.clone());
+        val
+    }
+
+    // Whether a global with this name has been set here or further out.
+    pub fn contains(&self, name: Symbol) -> bool {
+        self.store.contains_key(&name)
+            || self
+                .outer
+                .as_ref()
+                .is_some_and(|outer| outer.borrow().contains(name))
+    }
+
+    pub(crate) fn get_slot(&self, slot: Slot) -> Option<Object> {
+        if slot.depth > 0 {
+            return self.outer.as_ref()?.borrow().get_slot(Slot {
+                depth: slot.depth - 1,
+                index: slot.index,
+            });
+        }
+        self.slots.get(slot.index)?.clone()
+    }
+
+    pub(crate) fn set_slot(&mut self, slot: Slot, val: Object) {
+        if slot.depth > 0 {
+            if let Some(outer) = &self.outer {
+                let slot = Slot {
+                    depth: slot.depth - 1,
+                    index: slot.index,
+                };
+                outer.borrow_mut().set_slot(slot, val);
+            }
+            return;
+        }
+        self.slots[slot.index] = Some(val);
+    }
+
+    // Frames only hold slots, so a global bound while one is innermost,
+    // such as by a `let` in a `catch` block at the top level, is set where
+    // the globals are.
+    pub(crate) fn set_global(&mut self, name: Symbol, val: Object) {
+        match &self.outer {
+            Some(outer) if !self.slots.is_empty() => outer.borrow_mut().set_global(name, val),
+            _ => {
+                self.store.insert(name, val);
+            }
+        }
+    }
+
+    pub(crate) fn outer(&self) -> Option<&Env> {
+        self.outer.as_ref()
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Object> {
+        self.store.values().chain(self.slots.iter().flatten())
+    }
+
+    // Empties the environment, handing back what it held so the caller
+    // can drop it once the environment is no longer borrowed.
+    pub(crate) fn take(&mut self) -> (HashMap<Symbol, Object>, Vec<Option<Object>>, Option<Env>) {
+        (
+            std::mem::take(&mut self.store),
+            std::mem::take(&mut self.slots),
+            self.outer.take(),
+        )
+    }
+}
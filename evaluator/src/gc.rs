@@ -0,0 +1,384 @@
+use crate::environment::{Env, Environment};
+use crate::generators::{FrameKind, Generator, Iteration, State};
+use crate::object::{
+    Class, HashKey, Instance, Module, Object, RuntimeError, StructObject, VariantObject,
+};
+use crate::{array_size, HASH_ENTRY_SIZE};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::{Rc, Weak};
+
+// Collections run once this many environments and instances are tracked,
+// and after that whenever the number tracked has doubled since the last one.
+const MIN_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    // How many times the collector has run.
+    pub collections: usize,
+    // Environments and instances still tracked after the last collection.
+    pub tracked: usize,
+    // Environments and instances freed by the collector so far.
+    pub collected: usize,
+}
+
+// A closure keeps the environment it was defined in alive through an `Rc`,
+// so a function stored in that same environment forms a cycle that
+// reference counting alone never frees. The same goes for an instance whose
+// fields refer back to it. The heap holds a weak handle to every
+// environment and instance the evaluator creates and collects the ones that
+// are only kept alive by such cycles.
+//
+// The roots are the environments and shared values referenced from outside
+// the heap: the host's environment, the frames of calls in progress and any
+// value the evaluator is holding on to. They are found by subtracting the
+// references these hold on each other from their reference counts, whatever
+// is left comes from outside. Environments and instances not reachable from
+// a root are cleared, which breaks their cycles and lets reference counting
+// free them.
+pub(crate) struct Heap {
+    envs: Vec<Weak<RefCell<Environment>>>,
+    instances: Vec<Weak<Instance>>,
+    threshold: usize,
+    stats: GcStats,
+    // The values charged against the memory limit, which are counted again
+    // to credit back the ones that have since been dropped.
+    charged: Vec<Charged>,
+    charged_threshold: usize,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            envs: Vec::new(),
+            instances: Vec::new(),
+            threshold: MIN_THRESHOLD,
+            stats: GcStats::default(),
+            charged: Vec::new(),
+            charged_threshold: MIN_THRESHOLD,
+        }
+    }
+
+    pub fn track(&mut self, env: &Env) {
+        self.envs.push(Rc::downgrade(env));
+    }
+
+    pub fn track_instance(&mut self, instance: &Rc<Instance>) {
+        self.instances.push(Rc::downgrade(instance));
+    }
+
+    pub fn is_tracked(&self, env: &Env) -> bool {
+        self.envs
+            .iter()
+            .any(|tracked| std::ptr::eq(tracked.as_ptr(), Rc::as_ptr(env)))
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.envs.len() + self.instances.len() >= self.threshold
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    // Returns whether enough values have been charged since they were last
+    // counted that the dropped ones should be forgotten.
+    pub fn charge(&mut self, value: &Object) -> bool {
+        let charged = match value {
+            Object::String(value) => Charged::String(Rc::downgrade(value)),
+            Object::Array(elements) => Charged::Array(Rc::downgrade(elements)),
+            Object::Hash(pairs) => Charged::Hash(Rc::downgrade(pairs)),
+            Object::Struct(value) => Charged::Struct(Rc::downgrade(value)),
+            Object::Variant(value) => Charged::Variant(Rc::downgrade(value)),
+            Object::Generator(generator) => Charged::Generator(Rc::downgrade(generator)),
+            _ => return false,
+        };
+        self.charged.push(charged);
+        self.charged.len() >= self.charged_threshold
+    }
+
+    // The approximate size of the charged values and instances that are
+    // still alive, using the same sizes they were charged with.
+    pub fn live_bytes(&mut self) -> usize {
+        let mut bytes = 0;
+        self.charged.retain(|charged| match charged.size() {
+            Some(size) => {
+                bytes += size;
+                true
+            }
+            None => false,
+        });
+        self.charged_threshold = MIN_THRESHOLD.max(self.charged.len() * 2);
+        for instance in self.instances.iter().filter_map(Weak::upgrade) {
+            let fields = instance.fields.borrow().len();
+            bytes += std::mem::size_of::<Instance>() + fields * std::mem::size_of::<Object>();
+        }
+        bytes
+    }
+
+    // Returns how many environments and instances were freed.
+    pub fn collect(&mut self) -> usize {
+        // Arrays, hashes, structs, caught errors, modules, classes and
+        // generators are shared too, so they take part as well: an
+        // environment can be kept alive by a closure inside an array that
+        // is itself referenced from outside. The environments and instances
+        // come first.
+        let mut nodes: Vec<Node> = self
+            .envs
+            .iter()
+            .filter_map(|env| env.upgrade())
+            .map(Node::Env)
+            .chain(
+                self.instances
+                    .iter()
+                    .filter_map(|instance| instance.upgrade())
+                    .map(Node::Instance),
+            )
+            .collect();
+        let tracked = nodes.len();
+        let mut index: HashMap<*const (), usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.ptr(), i))
+            .collect();
+        let mut next = 0;
+        while next < nodes.len() {
+            let mut found = Vec::new();
+            nodes[next].for_each_child(&mut |child| {
+                let owned = matches!(child, Node::Env(_) | Node::Instance(_));
+                if !owned && !index.contains_key(&child.ptr()) {
+                    index.insert(child.ptr(), nodes.len() + found.len());
+                    found.push(child);
+                }
+            });
+            nodes.extend(found);
+            next += 1;
+        }
+
+        // One of the strong references is the one held by `nodes` above.
+        let mut external: Vec<usize> = nodes.iter().map(|node| node.strong_count() - 1).collect();
+        for node in nodes.iter() {
+            node.for_each_child(&mut |child| {
+                if let Some(&i) = index.get(&child.ptr()) {
+                    external[i] -= 1;
+                }
+            });
+        }
+
+        let mut reachable = vec![false; nodes.len()];
+        let mut stack: Vec<usize> = (0..nodes.len()).filter(|&i| external[i] > 0).collect();
+        while let Some(i) = stack.pop() {
+            if reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+            nodes[i].for_each_child(&mut |child| {
+                if let Some(&j) = index.get(&child.ptr()) {
+                    stack.push(j);
+                }
+            });
+        }
+        nodes.truncate(tracked);
+
+        let mut collected = 0;
+        self.envs.clear();
+        self.instances.clear();
+        for (node, reachable) in nodes.iter().zip(reachable) {
+            match (node, reachable) {
+                (Node::Env(env), true) => self.envs.push(Rc::downgrade(env)),
+                (Node::Instance(instance), true) => self.instances.push(Rc::downgrade(instance)),
+                // Dropped outside the borrow, freeing the values may free
+                // other environments in turn.
+                (Node::Env(env), false) => {
+                    let contents = env.borrow_mut().take();
+                    drop(contents);
+                    collected += 1;
+                }
+                (Node::Instance(instance), false) => {
+                    let fields = std::mem::take(&mut *instance.fields.borrow_mut());
+                    drop(fields);
+                    collected += 1;
+                }
+                _ => {}
+            }
+        }
+
+        let tracked = self.envs.len() + self.instances.len();
+        self.threshold = MIN_THRESHOLD.max(tracked * 2);
+        self.stats.collections += 1;
+        self.stats.tracked = tracked;
+        self.stats.collected += collected;
+        collected
+    }
+}
+
+// A value charged against the memory limit, held weakly so that it can
+// still be dropped.
+enum Charged {
+    String(Weak<str>),
+    Array(Weak<Vec<Object>>),
+    Hash(Weak<BTreeMap<HashKey, Object>>),
+    Struct(Weak<StructObject>),
+    Variant(Weak<VariantObject>),
+    Generator(Weak<Generator>),
+}
+
+impl Charged {
+    // `None` once the value has been dropped.
+    fn size(&self) -> Option<usize> {
+        match self {
+            Charged::String(value) => value.upgrade().map(|value| value.len()),
+            Charged::Array(elements) => elements.upgrade().map(|e| array_size(e.len())),
+            Charged::Hash(pairs) => pairs.upgrade().map(|pairs| HASH_ENTRY_SIZE * pairs.len()),
+            Charged::Struct(value) => value.upgrade().map(|v| array_size(v.values.len())),
+            Charged::Variant(value) => value.upgrade().map(|v| array_size(v.values.len())),
+            Charged::Generator(generator) => {
+                (generator.strong_count() > 0).then_some(std::mem::size_of::<Generator>())
+            }
+        }
+    }
+}
+
+// Something in the heap that can hold references to environments.
+enum Node {
+    Env(Env),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    Struct(Rc<StructObject>),
+    Variant(Rc<VariantObject>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    Exception(Rc<RuntimeError>),
+    Module(Rc<Module>),
+    Generator(Rc<Generator>),
+}
+
+impl Node {
+    fn ptr(&self) -> *const () {
+        match self {
+            Node::Env(env) => Rc::as_ptr(env) as *const (),
+            Node::Array(elements) => Rc::as_ptr(elements) as *const (),
+            Node::Hash(pairs) => Rc::as_ptr(pairs) as *const (),
+            Node::Struct(value) => Rc::as_ptr(value) as *const (),
+            Node::Variant(value) => Rc::as_ptr(value) as *const (),
+            Node::Class(class) => Rc::as_ptr(class) as *const (),
+            Node::Instance(instance) => Rc::as_ptr(instance) as *const (),
+            Node::Exception(error) => Rc::as_ptr(error) as *const (),
+            Node::Module(module) => Rc::as_ptr(module) as *const (),
+            Node::Generator(generator) => Rc::as_ptr(generator) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Env(env) => Rc::strong_count(env),
+            Node::Array(elements) => Rc::strong_count(elements),
+            Node::Hash(pairs) => Rc::strong_count(pairs),
+            Node::Struct(value) => Rc::strong_count(value),
+            Node::Variant(value) => Rc::strong_count(value),
+            Node::Class(class) => Rc::strong_count(class),
+            Node::Instance(instance) => Rc::strong_count(instance),
+            Node::Exception(error) => Rc::strong_count(error),
+            Node::Module(module) => Rc::strong_count(module),
+            Node::Generator(generator) => Rc::strong_count(generator),
+        }
+    }
+
+    fn for_each_child(&self, visit: &mut dyn FnMut(Node)) {
+        match self {
+            Node::Env(env) => {
+                let env = env.borrow();
+                if let Some(outer) = env.outer() {
+                    visit(Node::Env(Rc::clone(outer)));
+                }
+                for value in env.values() {
+                    visit_object(value, visit);
+                }
+            }
+            Node::Array(elements) => {
+                for element in elements.iter() {
+                    visit_object(element, visit);
+                }
+            }
+            Node::Hash(pairs) => {
+                for value in pairs.values() {
+                    visit_object(value, visit);
+                }
+            }
+            Node::Struct(value) => {
+                for field in value.values.iter() {
+                    visit_object(field, visit);
+                }
+            }
+            Node::Variant(value) => {
+                for field in value.values.iter() {
+                    visit_object(field, visit);
+                }
+            }
+            Node::Class(class) => {
+                if let Some(superclass) = &class.superclass {
+                    visit(Node::Class(Rc::clone(superclass)));
+                }
+                for method in class.methods.values() {
+                    visit(Node::Env(Rc::clone(&method.env)));
+                }
+            }
+            Node::Instance(instance) => {
+                visit(Node::Class(Rc::clone(&instance.class)));
+                for value in instance.fields.borrow().values() {
+                    visit_object(value, visit);
+                }
+            }
+            Node::Exception(error) => {
+                if let Some(value) = &error.value {
+                    visit_object(value, visit);
+                }
+            }
+            Node::Module(module) => {
+                for value in module.exports.values() {
+                    visit_object(value, visit);
+                }
+            }
+            // A running generator's state is on the host stack, which
+            // makes it a root.
+            Node::Generator(generator) => {
+                if let State::Suspended(suspended) = &*generator.state.borrow() {
+                    visit(Node::Env(Rc::clone(&suspended.env)));
+                    for frame in suspended.frames.iter() {
+                        match &frame.kind {
+                            FrameKind::Loop(Iteration::Array(elements, _)) => {
+                                visit(Node::Array(Rc::clone(elements)))
+                            }
+                            FrameKind::Loop(Iteration::Generator(generator)) => {
+                                visit(Node::Generator(Rc::clone(generator)))
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn visit_object(obj: &Object, visit: &mut dyn FnMut(Node)) {
+    match obj {
+        Object::Function(function) => visit(Node::Env(Rc::clone(&function.env))),
+        Object::Array(elements) => visit(Node::Array(Rc::clone(elements))),
+        Object::Hash(pairs) => visit(Node::Hash(Rc::clone(pairs))),
+        Object::Struct(value) => visit(Node::Struct(Rc::clone(value))),
+        Object::Variant(value) => visit(Node::Variant(Rc::clone(value))),
+        Object::Class(class) => visit(Node::Class(Rc::clone(class))),
+        Object::Instance(instance) => visit(Node::Instance(Rc::clone(instance))),
+        Object::BoundFunction(bound) => {
+            visit(Node::Instance(Rc::clone(&bound.receiver)));
+            visit(Node::Env(Rc::clone(&bound.function.env)));
+        }
+        Object::Exception(error) => visit(Node::Exception(Rc::clone(error))),
+        Object::Module(module) => visit(Node::Module(Rc::clone(module))),
+        Object::Generator(generator) => visit(Node::Generator(Rc::clone(generator))),
+        Object::Ok(value) | Object::Err(value) => visit_object(value, visit),
+        Object::Method(bound) => visit_object(&bound.receiver, visit),
+        _ => {}
+    }
+}
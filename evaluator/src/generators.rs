@@ -0,0 +1,352 @@
+use crate::environment::Env;
+use crate::object::{ErrorKind, FunctionObject, HashKey, Object};
+use crate::{
+    array_size, bind_pattern, is_truthy, locate, new_error, object_type, unwinds, CallFrame,
+    Evaluator, Position,
+};
+use ast::ast::Statement;
+use ast::{expressions, statements};
+use lexer::{Span, Symbol};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// What calling a function declared with `fn*` returns. Its body runs a
+// little at a time: each `next()` runs it up to the following `yield`.
+//
+// The host stack cannot be kept between two calls to `next()`, so a
+// suspended generator instead records the blocks it is in the middle of,
+// each with the statement to run next. Only a `yield` that is a statement
+// of the body, or of an `if` or `for` statement in it, can be resumed
+// from, which the resolver checks. Everything between two `yield`s is
+// evaluated as usual.
+#[derive(Debug)]
+pub struct Generator {
+    pub name: Option<Symbol>,
+    pub(crate) call_site: Span,
+    pub(crate) state: RefCell<State>,
+}
+
+#[derive(Debug)]
+pub(crate) enum State {
+    Suspended(Box<Suspended>),
+    // In the middle of a `next()`, its state is on the host stack.
+    Running,
+    // It ran to the end of its body or a `return`, or raised an error.
+    Done,
+}
+
+#[derive(Debug)]
+pub(crate) struct Suspended {
+    body: Rc<statements::BlockStatement>,
+    pub(crate) env: Env,
+    // The blocks being run, the body first.
+    pub(crate) frames: Vec<Frame>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Frame {
+    pub(crate) kind: FrameKind,
+    // The statement of the enclosing block that entered this one.
+    entered_by: usize,
+    // The statement to run next.
+    next: usize,
+}
+
+#[derive(Debug)]
+pub(crate) enum FrameKind {
+    Body,
+    // The first or second block of an `if`.
+    Then,
+    Else,
+    // The body of a `for`, with the values it has left to go through.
+    Loop(Iteration),
+}
+
+// The values a `for` loop has left to go through: the elements of an
+// array, the characters of a string, the keys of a hash, in order, or the
+// values a generator yields.
+#[derive(Debug)]
+pub(crate) enum Iteration {
+    Array(Rc<Vec<Object>>, usize),
+    // The byte offset of the next character.
+    String(Rc<str>, usize),
+    Generator(Rc<Generator>),
+}
+
+// Where running a generator stopped.
+#[derive(Debug)]
+pub(crate) enum Resumed {
+    Yielded(Object),
+    // It finished, and this is what it returned, null if it ran to the end
+    // of its body. A generator that has already finished returns null.
+    Returned(Object),
+}
+
+impl Generator {
+    // A generator that has not run any of its body yet. Its frame already
+    // holds the arguments.
+    pub(crate) fn new(function: FunctionObject, env: Env, call_site: Span) -> Generator {
+        let suspended = Suspended {
+            body: function.body,
+            env,
+            frames: vec![Frame {
+                kind: FrameKind::Body,
+                entered_by: 0,
+                next: 0,
+            }],
+        };
+        Generator {
+            name: function.name,
+            call_site,
+            state: RefCell::new(State::Suspended(Box::new(suspended))),
+        }
+    }
+}
+
+impl Evaluator {
+    pub(crate) fn iterate(&mut self, iterable: Object) -> Result<Iteration, Object> {
+        match iterable {
+            Object::Array(elements) => Ok(Iteration::Array(elements, 0)),
+            Object::String(value) => Ok(Iteration::String(value, 0)),
+            Object::Hash(pairs) => {
+                if let Some(interrupt) = self.allocate(array_size(pairs.len())) {
+                    return Err(interrupt);
+                }
+                let keys: Rc<Vec<Object>> = Rc::new(pairs.keys().map(HashKey::to_object).collect());
+                self.charged(Object::Array(Rc::clone(&keys)));
+                Ok(Iteration::Array(keys, 0))
+            }
+            Object::Generator(generator) => Ok(Iteration::Generator(generator)),
+            other => Err(new_error(
+                ErrorKind::TypeMismatch,
+                format!("cannot iterate over {}", object_type(&other)),
+            )),
+        }
+    }
+
+    // The next value of a loop, or `None` once there are no more.
+    pub(crate) fn advance(&mut self, iteration: &mut Iteration) -> Result<Option<Object>, Object> {
+        match iteration {
+            Iteration::Array(elements, index) => {
+                let element = elements.get(*index).cloned();
+                *index += 1;
+                Ok(element)
+            }
+            Iteration::String(value, offset) => {
+                let Some(c) = value[*offset..].chars().next() else {
+                    return Ok(None);
+                };
+                *offset += c.len_utf8();
+                if let Some(interrupt) = self.allocate(c.len_utf8()) {
+                    return Err(interrupt);
+                }
+                Ok(Some(self.charged(Object::String(c.to_string().into()))))
+            }
+            // What a generator returns is not one of the values it goes
+            // through.
+            Iteration::Generator(generator) => match self.resume(generator)? {
+                Resumed::Yielded(value) => Ok(Some(value)),
+                Resumed::Returned(_) => Ok(None),
+            },
+        }
+    }
+
+    // Runs a generator up to its next `yield`, or until it finishes. A
+    // generator that raises an error, or is interrupted, is finished too.
+    pub(crate) fn resume(&mut self, generator: &Rc<Generator>) -> Result<Resumed, Object> {
+        let state = std::mem::replace(&mut *generator.state.borrow_mut(), State::Running);
+        let mut suspended = match state {
+            State::Suspended(suspended) => suspended,
+            State::Running => {
+                return Err(new_error(
+                    ErrorKind::NotCallable,
+                    "generator is already running".to_string(),
+                ))
+            }
+            State::Done => {
+                *generator.state.borrow_mut() = State::Done;
+                return Ok(Resumed::Returned(Object::Null));
+            }
+        };
+
+        let frame = CallFrame {
+            name: generator.name,
+            call_site: generator.call_site,
+        };
+        if self.call_stack.len() >= self.max_depth {
+            *generator.state.borrow_mut() = State::Suspended(suspended);
+            return Err(locate(self.recursion_error(frame), generator.call_site));
+        }
+        self.call_stack.push(frame);
+        let result = self.run_generator(&mut suspended);
+        let frame = self.call_stack.pop().unwrap();
+        *generator.state.borrow_mut() = match result {
+            Ok(Resumed::Yielded(_)) => State::Suspended(suspended),
+            _ => State::Done,
+        };
+        result.map_err(|mut err| {
+            if let Object::Error(error) = &mut err {
+                error.trace.push(frame);
+            }
+            err
+        })
+    }
+
+    fn run_generator(&mut self, suspended: &mut Suspended) -> Result<Resumed, Object> {
+        let body = Rc::clone(&suspended.body);
+        let env = suspended.env.clone();
+        loop {
+            let depth = suspended.frames.len();
+            if depth == 0 {
+                return Ok(Resumed::Returned(Object::Null));
+            }
+            let block = block_of(&body, &suspended.frames);
+            let frame = &mut suspended.frames[depth - 1];
+
+            let Some(statement) = block.statements.get(frame.next) else {
+                // The end of a block: a loop goes round again, as long as
+                // there are values left, anything else is left.
+                if let FrameKind::Loop(iteration) = &mut frame.kind {
+                    let entered_by = frame.entered_by;
+                    let advanced = self.advance(iteration);
+                    let parent = block_of(&body, &suspended.frames[..depth - 1]);
+                    let for_stmt = for_statement(parent, entered_by);
+                    let span = for_stmt.token.span;
+                    if let Some(value) = advanced.map_err(|err| locate(err, span))? {
+                        bind_pattern(&for_stmt.pattern, value, &env)
+                            .map_err(|err| locate(err, span))?;
+                        suspended.frames[depth - 1].next = 0;
+                        continue;
+                    }
+                }
+                suspended.frames.pop();
+                continue;
+            };
+            let entered_by = frame.next;
+            frame.next += 1;
+            let Some(statement) = statement else {
+                continue;
+            };
+
+            if let Some(interrupt) = self.step() {
+                return Err(interrupt);
+            }
+            let any = statement.as_any();
+            if let Some(yield_stmt) = any.downcast_ref::<statements::YieldStatement>() {
+                let value = match &yield_stmt.value {
+                    Some(expr) => self.eval_expression(expr.as_ref(), env.clone()),
+                    None => Object::Null,
+                };
+                if unwinds(&value) {
+                    return finish(value);
+                }
+                return Ok(Resumed::Yielded(value));
+            }
+            if let Some(for_stmt) = any.downcast_ref::<statements::ForStatement>() {
+                let Some(iterable) = &for_stmt.iterable else {
+                    return Err(new_error(
+                        ErrorKind::Malformed,
+                        "missing for iterable".to_string(),
+                    ));
+                };
+                let iterable = self.eval_expression(iterable.as_ref(), env.clone());
+                if unwinds(&iterable) {
+                    return finish(iterable);
+                }
+                let iteration = self
+                    .iterate(iterable)
+                    .map_err(|err| locate(err, for_stmt.token.span))?;
+                // Past the end of the block, so the first value is taken
+                // before the body runs.
+                suspended.frames.push(Frame {
+                    kind: FrameKind::Loop(iteration),
+                    entered_by,
+                    next: usize::MAX,
+                });
+                continue;
+            }
+            if let Some(if_expr) = if_statement(statement.as_ref()) {
+                let Some(condition) = &if_expr.condition else {
+                    return Err(new_error(
+                        ErrorKind::Malformed,
+                        "missing if condition".to_string(),
+                    ));
+                };
+                let condition = self.eval_expression(condition.as_ref(), env.clone());
+                if unwinds(&condition) {
+                    return finish(condition);
+                }
+                let (kind, block) = match is_truthy(&condition) {
+                    true => (FrameKind::Then, &if_expr.first),
+                    false => (FrameKind::Else, &if_expr.second),
+                };
+                if block.is_some() {
+                    suspended.frames.push(Frame {
+                        kind,
+                        entered_by,
+                        next: 0,
+                    });
+                }
+                continue;
+            }
+
+            let result = self.eval_statement(statement.as_ref(), env.clone(), Position::Plain);
+            if unwinds(&result) {
+                return finish(result);
+            }
+        }
+    }
+}
+
+// A `return`, or a `?` returning early, finishes the generator with what
+// it returns. Errors and interrupts are raised from `next()`.
+fn finish(result: Object) -> Result<Resumed, Object> {
+    match result {
+        Object::ReturnValue(value) => Ok(Resumed::Returned(*value)),
+        result => Err(result),
+    }
+}
+
+// The block the innermost frame runs, found by following the statements
+// that entered each frame from the body.
+fn block_of<'a>(
+    body: &'a statements::BlockStatement,
+    frames: &[Frame],
+) -> &'a statements::BlockStatement {
+    let mut block = body;
+    for frame in frames.iter().skip(1) {
+        block = match frame.kind {
+            FrameKind::Then | FrameKind::Else => {
+                let statement = block.statements[frame.entered_by].as_deref().unwrap();
+                let if_expr = if_statement(statement).unwrap();
+                let branch = match frame.kind {
+                    FrameKind::Then => &if_expr.first,
+                    _ => &if_expr.second,
+                };
+                branch.as_ref().unwrap()
+            }
+            FrameKind::Loop(_) => &for_statement(block, frame.entered_by).body,
+            FrameKind::Body => unreachable!("the body is only the first frame"),
+        };
+    }
+    block
+}
+
+fn for_statement(block: &statements::BlockStatement, index: usize) -> &statements::ForStatement {
+    block.statements[index]
+        .as_deref()
+        .and_then(|statement| statement.as_any().downcast_ref())
+        .unwrap()
+}
+
+// An `if` that is a statement of its own, rather than part of a larger
+// expression.
+fn if_statement(statement: &dyn Statement) -> Option<&expressions::If> {
+    statement
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()?
+        .expression
+        .as_deref()?
+        .as_any()
+        .downcast_ref()
+}
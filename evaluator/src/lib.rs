@@ -1,307 +1,1949 @@
+mod builtins;
+mod cancellation;
+mod environment;
+mod gc;
+mod generators;
+mod methods;
+mod modules;
+mod object;
+mod resolver;
+
 use ast::ast::{Expression, Statement};
-use ast::{expressions, statements};
+use ast::expressions::Slot;
+use ast::{expressions, patterns, statements};
+use lexer::{Span, Symbol};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
-pub type Env = Rc<RefCell<Environment>>;
+pub use cancellation::CancellationToken;
+pub use environment::{Env, Environment};
+pub use gc::GcStats;
+pub use generators::Generator;
+pub use modules::{FileLoader, LoadError, MemoryLoader, ModuleId, ModuleLoader};
+pub use object::{
+    BoundFunction, BoundMethod, Builtin, Class, Constructor, EnumLayout, ErrorKind, FunctionObject,
+    HashKey, Instance, Interrupt, Method, Module, Object, RuntimeError, StructLayout, StructObject,
+    TailCall, VariantObject,
+};
 
-#[derive(Debug)]
-pub struct Environment {
-    store: HashMap<String, Object>,
-    outer: Option<Env>,
+// Where a block or statement is being evaluated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Position {
+    // Top level, or inside an arbitrary expression.
+    Plain,
+    // Inside a function body, where `return` leaves the function.
+    Function,
+    // As `Function`, and the value also becomes the function's result.
+    Tail,
 }
 
-impl Environment {
-    pub fn new() -> Env {
-        Rc::new(RefCell::new(Environment {
-            store: HashMap::new(),
-            outer: None,
-        }))
-    }
+// Each nested call takes roughly 5KB of host stack in release builds and
+// 12KB in debug builds, so this fits a 2MB thread in release and the 8MB
+// main thread in debug. Hosts running scripts on smaller stacks should
+// lower `Evaluator::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 400;
 
-    pub fn new_enclosed(outer: Env) -> Env {
-        Rc::new(RefCell::new(Environment {
-            store: HashMap::new(),
-            outer: Some(outer),
-        }))
-    }
+// Approximate heap cost of array slots and hash entries, used for memory
+// accounting.
+pub(crate) const HASH_ENTRY_SIZE: usize =
+    std::mem::size_of::<HashKey>() + std::mem::size_of::<Object>();
 
-    pub fn get(&self, name: &str) -> Option<Object> {
-        match self.store.get(name) {
-            Some(obj) => Some(obj.clone()),
-            None => match &self.outer {
-                Some(outer) => outer.borrow().get(name),
-                None => None,
-            },
+// How many steps run between checks of the cancellation token.
+const CANCELLATION_CHECK_INTERVAL: u64 = 1024;
+
+// A function call that is being evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    pub name: Option<Symbol>,
+    pub call_site: Span,
+}
+
+impl Display for CallFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match &self.name {
+            Some(name) => write!(f, "{} (at {})", name, self.call_site),
+            None => write!(f, "<anonymous> (at {})", self.call_site),
         }
     }
+}
 
-    pub fn set(&mut self, name: String, val: Object) -> Object {
-        self.store.insert(name, val.clone());
-        val
+pub struct Evaluator {
+    // Calls nested deeper than this fail with an error instead of
+    // overflowing the host stack. Tail calls do not count towards it.
+    pub max_depth: usize,
+    call_stack: Vec<CallFrame>,
+    // Steps left before evaluation is interrupted, `None` for no limit.
+    fuel: Option<u64>,
+    cancellation: Option<CancellationToken>,
+    steps: u64,
+    memory_limit: Option<usize>,
+    memory_used: usize,
+    heap: gc::Heap,
+    loader: Box<dyn ModuleLoader>,
+    module_id: Option<ModuleId>,
+    // The modules being evaluated, the innermost import last, each with the
+    // path it was imported by.
+    loading: Vec<(ModuleId, String)>,
+    // Modules already evaluated.
+    modules: HashMap<ModuleId, Rc<Module>>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct FunctionObject {
-    pub parameters: Vec<String>,
-    pub body: Rc<statements::BlockStatement>,
-    pub env: Env,
+pub fn eval(program: &statements::ProgramStatement, env: Env) -> Object {
+    Evaluator::new().eval(program, env)
 }
 
-#[derive(Debug, Clone)]
-pub enum Object {
-    Integer(i64),
-    Boolean(bool),
-    Null,
-    ReturnValue(Box<Object>),
-    Function(FunctionObject),
-    Error(String),
+// Reports the first variable the program reads that is bound nowhere,
+// without running it. `Evaluator::eval` does this itself. Otherwise returns
+// warnings, such as for a `match` that leaves out variants of an enum.
+pub fn resolve(program: &statements::ProgramStatement, env: &Env) -> Result<Vec<String>, String> {
+    resolver::resolve(program, env)
 }
 
-impl Display for Object {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        match self {
-            Object::Integer(value) => write!(f, "{}", value),
-            Object::Boolean(value) => write!(f, "{}", value),
-            Object::Null => write!(f, "null"),
-            Object::ReturnValue(value) => write!(f, "{}", value),
-            Object::Function(function) => {
-                let params = function.parameters.join(", ");
-                write!(f, "fn({}) {{ ... }}", params)
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator {
+            max_depth: DEFAULT_MAX_DEPTH,
+            call_stack: Vec::new(),
+            fuel: None,
+            cancellation: None,
+            steps: 0,
+            memory_limit: None,
+            memory_used: 0,
+            heap: gc::Heap::new(),
+            loader: Box::new(FileLoader),
+            module_id: None,
+            loading: Vec::new(),
+            modules: HashMap::new(),
+        }
+    }
+
+    // Every statement and expression evaluated costs one unit of fuel. When
+    // it runs out, evaluation stops with `Interrupt::OutOfFuel`; the host can
+    // top it up and evaluate again in the same environment.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn add_fuel(&mut self, amount: u64) {
+        if let Some(fuel) = self.fuel.as_mut() {
+            *fuel = fuel.saturating_add(amount);
+        }
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // The token is polled every few steps, so a cancelled or expired token
+    // stops evaluation with `Interrupt::Cancelled` or
+    // `Interrupt::DeadlineExceeded` shortly after.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    // Strings, arrays and hashes created by scripts are charged against the
+    // limit. Evaluation stops with `Interrupt::MemoryLimitExceeded` once the
+    // values still alive exceed it.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    // Approximate bytes used by script values. Values that are dropped, or
+    // freed by the collector, are only subtracted the next time the live
+    // values are counted, which happens when the limit would be exceeded
+    // and every so often as values are created. Hosts reusing an evaluator
+    // can reset it between scripts.
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    pub fn reset_memory_used(&mut self) {
+        self.memory_used = 0;
+    }
+
+    // Checks `bytes` against the limit before the value is built.
+    fn allocate(&mut self, bytes: usize) -> Option<Object> {
+        let mut used = self.memory_used.saturating_add(bytes);
+        if let Some(limit) = self.memory_limit {
+            if used > limit {
+                self.memory_used = self.heap.live_bytes();
+                used = self.memory_used.saturating_add(bytes);
+            }
+            if used > limit {
+                return Some(Object::Interrupt(Interrupt::MemoryLimitExceeded));
             }
-            Object::Error(message) => write!(f, "ERROR: {}", message),
         }
+        self.memory_used = used;
+        None
     }
-}
 
-pub fn eval(program: &statements::ProgramStatement, env: Env) -> Object {
-    eval_program(program, env)
-}
+    // Records a value that was allocated for, so that its bytes can be
+    // credited back once it is dropped. Without a limit nothing is counted.
+    fn charged(&mut self, value: Object) -> Object {
+        if self.memory_limit.is_some() && self.heap.charge(&value) {
+            self.memory_used = self.heap.live_bytes();
+        }
+        value
+    }
 
-fn eval_program(program: &statements::ProgramStatement, env: Env) -> Object {
-    let mut result = Object::Null;
-    for statement in program.body.iter() {
-        result = eval_statement(statement.as_ref(), env.clone());
+    // Total statements and expressions evaluated so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    fn step(&mut self) -> Option<Object> {
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Some(Object::Interrupt(Interrupt::OutOfFuel));
+            }
+            *fuel -= 1;
+        }
 
-        match result {
-            Object::ReturnValue(value) => return *value,
-            Object::Error(_) => return result,
-            _ => {}
+        if self.steps.is_multiple_of(CANCELLATION_CHECK_INTERVAL) {
+            if let Some(token) = &self.cancellation {
+                if token.is_cancelled() {
+                    return Some(Object::Interrupt(Interrupt::Cancelled));
+                }
+                if token.is_past_deadline() {
+                    return Some(Object::Interrupt(Interrupt::DeadlineExceeded));
+                }
+            }
         }
+        self.steps += 1;
+        None
+    }
+
+    // Frees environments that are only kept alive by closures referring to
+    // each other, returning how many were freed. Also run automatically as
+    // function calls create environments.
+    pub fn gc(&mut self) -> usize {
+        self.heap.collect()
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
     }
-    result
-}
 
-fn eval_block_statement(block: &statements::BlockStatement, env: Env) -> Object {
-    let mut result = Object::Null;
+    // Where imported modules are loaded from, files by default. Modules
+    // already loaded stay cached.
+    pub fn set_module_loader(&mut self, loader: Box<dyn ModuleLoader>) {
+        self.loader = loader;
+    }
+
+    // The id of the program given to `eval`, passed to the loader as the
+    // importing module of the imports in it.
+    pub fn set_module_id(&mut self, id: Option<ModuleId>) {
+        self.module_id = id;
+    }
+
+    pub fn eval(&mut self, program: &statements::ProgramStatement, env: Env) -> Object {
+        self.call_stack.clear();
+        self.begin_modules();
+        // The host's environment is tracked too, so that once the host drops
+        // it any cycles it is part of can be freed.
+        if !self.heap.is_tracked(&env) {
+            self.heap.track(&env);
+        }
+        if let Err(message) = resolver::resolve(program, &env) {
+            return new_error(ErrorKind::UndefinedVariable, message);
+        }
+        self.eval_program(program, env)
+    }
 
-    for statement in block.statements.iter() {
-        let statement = match statement {
-            Some(statement) => statement,
-            None => continue,
+    fn eval_program(&mut self, program: &statements::ProgramStatement, env: Env) -> Object {
+        let mut result = Object::Null;
+        for statement in program.body.iter() {
+            result = self.eval_statement(statement.as_ref(), env.clone(), Position::Plain);
+
+            match result {
+                Object::ReturnValue(value) => return *value,
+                Object::Error(_) | Object::Interrupt(_) => return result,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn eval_block_statement(
+        &mut self,
+        block: &statements::BlockStatement,
+        env: Env,
+        position: Position,
+    ) -> Object {
+        let mut result = Object::Null;
+
+        let last = block.statements.iter().rposition(|s| s.is_some());
+        for (i, statement) in block.statements.iter().enumerate() {
+            let statement = match statement {
+                Some(statement) => statement,
+                None => continue,
+            };
+
+            let statement_position = match position {
+                Position::Tail if Some(i) != last => Position::Function,
+                position => position,
+            };
+            result = self.eval_statement(statement.as_ref(), env.clone(), statement_position);
+            match result {
+                Object::ReturnValue(_)
+                | Object::TailCall(_)
+                | Object::Error(_)
+                | Object::Interrupt(_) => return result,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn eval_statement(
+        &mut self,
+        statement: &dyn Statement,
+        env: Env,
+        position: Position,
+    ) -> Object {
+        if let Some(interrupt) = self.step() {
+            return interrupt;
+        }
+
+        if let Some(let_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::LetStatement>()
+        {
+            let value = match &let_stmt.value {
+                Some(expr) => self.eval_expression(expr.as_ref(), env.clone()),
+                None => Object::Null,
+            };
+            if unwinds(&value) {
+                return value;
+            }
+            let value = match (&let_stmt.pattern, value) {
+                (patterns::Pattern::Identifier(identifier), Object::Function(mut function))
+                    if function.name.is_none() =>
+                {
+                    function.name = Some(identifier.value);
+                    Object::Function(function)
+                }
+                (_, value) => value,
+            };
+            if let Err(err) = bind_pattern(&let_stmt.pattern, value, &env) {
+                return locate(err, let_stmt.token.span);
+            }
+            return Object::Null;
+        }
+
+        if let Some(return_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ReturnStatement>()
+        {
+            let value = match &return_stmt.return_value {
+                Some(expr) if position == Position::Plain => {
+                    self.eval_expression(expr.as_ref(), env)
+                }
+                Some(expr) => self.eval_tail_expression(expr.as_ref(), env),
+                None => Object::Null,
+            };
+            if unwinds(&value) || matches!(value, Object::TailCall(_)) {
+                return value;
+            }
+            return Object::ReturnValue(Box::new(value));
+        }
+
+        if let Some(throw_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ThrowStatement>()
+        {
+            let value = match &throw_stmt.value {
+                Some(expr) => self.eval_expression(expr.as_ref(), env),
+                None => Object::Null,
+            };
+            if unwinds(&value) {
+                return value;
+            }
+            return locate(throw(value), throw_stmt.token.span);
+        }
+
+        if let Some(import) = statement
+            .as_any()
+            .downcast_ref::<statements::ImportStatement>()
+        {
+            let module = match self.import(&import.path) {
+                Ok(module) => module,
+                Err(err) => return locate(err, import.token.span),
+            };
+            let alias = &import.alias;
+            set_variable(alias.value, alias.slot.get(), Object::Module(module), &env);
+            return Object::Null;
+        }
+
+        if let Some(struct_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::StructStatement>()
+        {
+            let layout = StructLayout {
+                name: struct_stmt.name.value,
+                fields: struct_stmt.fields.iter().map(|field| field.value).collect(),
+            };
+            let name = &struct_stmt.name;
+            let value = Object::StructType(Rc::new(layout));
+            set_variable(name.value, name.slot.get(), value, &env);
+            return Object::Null;
+        }
+
+        if let Some(enum_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::EnumStatement>()
+        {
+            let layout = EnumLayout {
+                name: enum_stmt.name.value,
+                variants: enum_stmt
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        let fields = variant.fields.iter().map(|field| field.value).collect();
+                        (variant.name.value, fields)
+                    })
+                    .collect(),
+            };
+            let name = &enum_stmt.name;
+            let value = Object::EnumType(Rc::new(layout));
+            set_variable(name.value, name.slot.get(), value, &env);
+            return Object::Null;
+        }
+
+        if let Some(class_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ClassStatement>()
+        {
+            return self.eval_class_statement(class_stmt, env);
+        }
+
+        if let Some(assign) = statement
+            .as_any()
+            .downcast_ref::<statements::AssignStatement>()
+        {
+            return self.eval_assign_statement(assign, env);
+        }
+
+        if let Some(for_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ForStatement>()
+        {
+            return self.eval_for_statement(for_stmt, env, position);
+        }
+
+        if statement.as_any().is::<statements::YieldStatement>() {
+            return new_error(
+                ErrorKind::Malformed,
+                "yield outside a generator".to_string(),
+            );
+        }
+
+        if let Some(expr_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ExpressionStatement>()
+        {
+            let expr = match &expr_stmt.expression {
+                Some(expr) => expr,
+                None => return Object::Null,
+            };
+            return match position {
+                Position::Plain => self.eval_expression(expr.as_ref(), env),
+                Position::Function => match expr.as_any().downcast_ref::<expressions::If>() {
+                    Some(if_expr) => self.eval_if_expression(if_expr, env, Position::Function),
+                    None => self.eval_expression(expr.as_ref(), env),
+                },
+                Position::Tail => self.eval_tail_expression(expr.as_ref(), env),
+            };
+        }
+
+        Object::Null
+    }
+
+    fn eval_expression(&mut self, expression: &dyn Expression, env: Env) -> Object {
+        if let Some(interrupt) = self.step() {
+            return interrupt;
+        }
+
+        if let Some(identifier) = expression
+            .as_any()
+            .downcast_ref::<expressions::Identifier>()
+        {
+            return locate(eval_identifier(identifier, env), identifier.token.span);
+        }
+
+        if let Some(literal) = expression
+            .as_any()
+            .downcast_ref::<expressions::IntegerLiteral>()
+        {
+            return Object::Integer(literal.value);
+        }
+
+        if let Some(literal) = expression.as_any().downcast_ref::<expressions::Bool>() {
+            return Object::Boolean(literal.value);
+        }
+
+        if let Some(literal) = expression
+            .as_any()
+            .downcast_ref::<expressions::StringLiteral>()
+        {
+            if let Some(interrupt) = self.allocate(literal.value.len()) {
+                return interrupt;
+            }
+            return self.charged(Object::String(literal.value.as_str().into()));
+        }
+
+        if let Some(array) = expression
+            .as_any()
+            .downcast_ref::<expressions::ArrayLiteral>()
+        {
+            let elements = match self.eval_expressions(&array.elements, env) {
+                Ok(elements) => elements,
+                Err(err) => return err,
+            };
+            if let Some(interrupt) = self.allocate(array_size(elements.len())) {
+                return interrupt;
+            }
+            return self.charged(Object::Array(Rc::new(elements)));
+        }
+
+        if let Some(hash) = expression
+            .as_any()
+            .downcast_ref::<expressions::HashLiteral>()
+        {
+            return self.eval_hash_literal(hash, env);
+        }
+
+        if let Some(literal) = expression
+            .as_any()
+            .downcast_ref::<expressions::StructLiteral>()
+        {
+            return self.eval_struct_literal(literal, env);
+        }
+
+        if let Some(index) = expression
+            .as_any()
+            .downcast_ref::<expressions::IndexExpression>()
+        {
+            return self.eval_index(index, env);
+        }
+
+        if let Some(prefix) = expression.as_any().downcast_ref::<expressions::Prefix>() {
+            return self.eval_prefix(prefix, env);
+        }
+
+        if let Some(infix) = expression.as_any().downcast_ref::<expressions::Infix>() {
+            return self.eval_infix(infix, env);
+        }
+
+        if let Some(if_expr) = expression.as_any().downcast_ref::<expressions::If>() {
+            return self.eval_if_expression(if_expr, env, Position::Plain);
+        }
+
+        if let Some(try_expr) = expression.as_any().downcast_ref::<expressions::Try>() {
+            return self.eval_try_expression(try_expr, env);
+        }
+
+        if let Some(match_expr) = expression.as_any().downcast_ref::<expressions::Match>() {
+            return self.eval_match(match_expr, env, Position::Plain);
+        }
+
+        if let Some(propagate) = expression.as_any().downcast_ref::<expressions::Propagate>() {
+            return self.eval_propagate(propagate, env);
+        }
+
+        if let Some(member) = expression.as_any().downcast_ref::<expressions::Member>() {
+            return self.eval_member(member, env);
+        }
+
+        if let Some(super_expr) = expression.as_any().downcast_ref::<expressions::Super>() {
+            return self.eval_super(super_expr, env);
+        }
+
+        if let Some(func) = expression
+            .as_any()
+            .downcast_ref::<expressions::FunctionLiteral>()
+        {
+            return eval_function_literal(func, env);
+        }
+
+        if let Some(call) = expression
+            .as_any()
+            .downcast_ref::<expressions::CallExpression>()
+        {
+            return match self.eval_call(call, env) {
+                Ok(call) => {
+                    self.apply_function(call.function, call.args, call.named_args, call.call_site)
+                }
+                Err(err) => err,
+            };
+        }
+
+        Object::Null
+    }
+
+    // Evaluates an expression whose value is the result of the enclosing
+    // function. Calls are not applied here but returned as `Object::TailCall`.
+    fn eval_tail_expression(&mut self, expression: &dyn Expression, env: Env) -> Object {
+        if let Some(call) = expression
+            .as_any()
+            .downcast_ref::<expressions::CallExpression>()
+        {
+            return match self.eval_call(call, env) {
+                Ok(call) => Object::TailCall(Box::new(call)),
+                Err(err) => err,
+            };
+        }
+
+        if let Some(if_expr) = expression.as_any().downcast_ref::<expressions::If>() {
+            return self.eval_if_expression(if_expr, env, Position::Tail);
+        }
+
+        if let Some(match_expr) = expression.as_any().downcast_ref::<expressions::Match>() {
+            return self.eval_match(match_expr, env, Position::Tail);
+        }
+
+        self.eval_expression(expression, env)
+    }
+
+    fn eval_call(
+        &mut self,
+        call: &expressions::CallExpression,
+        env: Env,
+    ) -> Result<TailCall, Object> {
+        let function_expr = match &call.function {
+            Some(expr) => expr,
+            None => {
+                return Err(new_error(
+                    ErrorKind::Malformed,
+                    "missing function expression".to_string(),
+                ))
+            }
         };
+        let function = self.eval_expression(function_expr.as_ref(), env.clone());
+        if unwinds(&function) {
+            return Err(function);
+        }
 
-        result = eval_statement(statement.as_ref(), env.clone());
-        match result {
-            Object::ReturnValue(_) | Object::Error(_) => return result,
-            _ => {}
+        let args = match &call.arguments {
+            Some(args) => self.eval_expressions(args, env.clone())?,
+            None => vec![],
+        };
+
+        let mut named_args = Vec::new();
+        for argument in call.named_arguments.iter() {
+            let value = self.eval_expression(argument.value.as_ref(), env.clone());
+            if unwinds(&value) {
+                return Err(value);
+            }
+            named_args.push((argument.name.value, value));
         }
+
+        Ok(TailCall {
+            function,
+            args,
+            named_args,
+            call_site: call.token.span,
+        })
     }
-    result
-}
 
-fn eval_statement(statement: &dyn Statement, env: Env) -> Object {
-    if let Some(let_stmt) = statement
-        .as_any()
-        .downcast_ref::<statements::LetStatement>()
-    {
-        let value = match &let_stmt.value {
-            Some(expr) => eval_expression(expr.as_ref(), env.clone()),
-            None => Object::Null,
+    fn eval_index(&mut self, index: &expressions::IndexExpression, env: Env) -> Object {
+        let span = index.token.span;
+        let left_expr = match &index.left {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing index target".to_string()),
         };
-        if is_error(&value) {
-            return value;
+        let index_expr = match &index.index {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing index expression".to_string()),
+        };
+
+        let left = self.eval_expression(left_expr.as_ref(), env.clone());
+        if unwinds(&left) {
+            return left;
         }
-        env.borrow_mut()
-            .set(let_stmt.name.value.clone(), value);
-        return Object::Null;
+        let index = self.eval_expression(index_expr.as_ref(), env);
+        if unwinds(&index) {
+            return index;
+        }
+        locate(eval_index_expression(left, index), span)
     }
 
-    if let Some(return_stmt) = statement
-        .as_any()
-        .downcast_ref::<statements::ReturnStatement>()
-    {
-        let value = match &return_stmt.return_value {
-            Some(expr) => eval_expression(expr.as_ref(), env),
-            None => Object::Null,
+    fn eval_prefix(&mut self, prefix: &expressions::Prefix, env: Env) -> Object {
+        let right_expr = match &prefix.right {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing right expression".to_string()),
         };
-        if is_error(&value) {
-            return value;
+        let right = self.eval_expression(right_expr.as_ref(), env);
+        if unwinds(&right) {
+            return right;
         }
-        return Object::ReturnValue(Box::new(value));
+        locate(
+            eval_prefix_expression(&prefix.operator, right),
+            prefix.token.span,
+        )
     }
 
-    if let Some(expr_stmt) = statement
-        .as_any()
-        .downcast_ref::<statements::ExpressionStatement>()
-    {
-        return match &expr_stmt.expression {
-            Some(expr) => eval_expression(expr.as_ref(), env),
-            None => Object::Null,
+    // `x?` is the value inside `ok(value)`. For `err(e)` it returns the
+    // `err` from the enclosing function as `return` would.
+    fn eval_propagate(&mut self, propagate: &expressions::Propagate, env: Env) -> Object {
+        let value_expr = match &propagate.value {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing ? operand".to_string()),
         };
+        let value = self.eval_expression(value_expr.as_ref(), env);
+        match value {
+            Object::Ok(value) => *value,
+            Object::Err(_) => Object::ReturnValue(Box::new(value)),
+            value if unwinds(&value) => value,
+            value => {
+                let error = new_error(
+                    ErrorKind::UnknownOperator,
+                    format!("unknown operator: {}?", object_type(&value)),
+                );
+                locate(error, propagate.token.span)
+            }
+        }
     }
 
-    Object::Null
-}
+    fn eval_member(&mut self, member: &expressions::Member, env: Env) -> Object {
+        let object_expr = match &member.object {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing member target".to_string()),
+        };
+        let object = self.eval_expression(object_expr.as_ref(), env);
+        if unwinds(&object) {
+            return object;
+        }
+        let result = match object {
+            Object::Instance(instance) => {
+                self.eval_instance_member(instance, member.property.value)
+            }
+            object => eval_member_access(object, member.property.value),
+        };
+        locate(result, member.token.span)
+    }
 
-fn eval_expression(expression: &dyn Expression, env: Env) -> Object {
-    if let Some(identifier) = expression
-        .as_any()
-        .downcast_ref::<expressions::Identifier>()
-    {
-        return eval_identifier(identifier, env);
+    // A field of the instance, or else one of its methods bound to it.
+    fn eval_instance_member(&mut self, instance: Rc<Instance>, name: Symbol) -> Object {
+        if let Some(value) = instance.fields.borrow().get(&name) {
+            return value.clone();
+        }
+        let class = Rc::clone(&instance.class);
+        match Class::find_method(&class, name) {
+            Some((declaring, method)) => self.bind_method(instance, declaring, method),
+            None => new_error(
+                ErrorKind::Member,
+                format!("{} has no member {}", class.name, name),
+            ),
+        }
     }
 
-    if let Some(literal) = expression
-        .as_any()
-        .downcast_ref::<expressions::IntegerLiteral>()
-    {
-        return Object::Integer(literal.value);
+    fn bind_method(
+        &mut self,
+        receiver: Rc<Instance>,
+        declaring: &Rc<Class>,
+        method: &FunctionObject,
+    ) -> Object {
+        let frame = Environment::new_frame(Rc::clone(&method.env), 2);
+        self.heap.track(&frame);
+        let receiver_slot = Slot { depth: 0, index: 0 };
+        frame
+            .borrow_mut()
+            .set_slot(receiver_slot, Object::Instance(Rc::clone(&receiver)));
+        if let Some(superclass) = &declaring.superclass {
+            let super_slot = Slot { depth: 0, index: 1 };
+            frame
+                .borrow_mut()
+                .set_slot(super_slot, Object::Class(Rc::clone(superclass)));
+        }
+        Object::BoundFunction(Box::new(BoundFunction {
+            receiver,
+            function: FunctionObject {
+                env: frame,
+                ..method.clone()
+            },
+        }))
     }
 
-    if let Some(literal) = expression.as_any().downcast_ref::<expressions::Bool>() {
-        return Object::Boolean(literal.value);
+    // `super.name` starts looking for the method in the superclass of the
+    // class that declares the method it is written in.
+    fn eval_super(&mut self, super_expr: &expressions::Super, env: Env) -> Object {
+        let span = super_expr.token.span;
+        let superclass = eval_identifier(&super_expr.class, env.clone());
+        let receiver = eval_identifier(&super_expr.receiver, env);
+        let (Object::Class(superclass), Object::Instance(receiver)) = (superclass, receiver) else {
+            let error = new_error(
+                ErrorKind::Malformed,
+                "super used outside of a method".to_string(),
+            );
+            return locate(error, span);
+        };
+        let name = super_expr.method.value;
+        match Class::find_method(&superclass, name) {
+            Some((declaring, method)) => self.bind_method(receiver, declaring, method),
+            None => {
+                let error = new_error(
+                    ErrorKind::Member,
+                    format!("{} has no method {}", superclass.name, name),
+                );
+                locate(error, super_expr.method.token.span)
+            }
+        }
     }
 
-    if let Some(prefix) = expression.as_any().downcast_ref::<expressions::Prefix>() {
-        let right_expr = match &prefix.right {
-            Some(expr) => expr,
-            None => return new_error("missing right expression".to_string()),
+    fn eval_class_statement(
+        &mut self,
+        class_stmt: &statements::ClassStatement,
+        env: Env,
+    ) -> Object {
+        let superclass = match &class_stmt.superclass {
+            Some(identifier) => match eval_identifier(identifier, env.clone()) {
+                Object::Class(class) => Some(class),
+                Object::Error(error) => return locate(Object::Error(error), identifier.token.span),
+                other => {
+                    let error = new_error(
+                        ErrorKind::TypeMismatch,
+                        format!("cannot extend {}", describe_type(&other)),
+                    );
+                    return locate(error, identifier.token.span);
+                }
+            },
+            None => None,
         };
-        let right = eval_expression(right_expr.as_ref(), env);
-        if is_error(&right) {
-            return right;
+        let mut methods = HashMap::new();
+        for method in class_stmt.methods.iter() {
+            let function = match eval_function_literal(&method.function, env.clone()) {
+                Object::Function(function) => function,
+                other => return other,
+            };
+            let function = FunctionObject {
+                name: Some(method.name.value),
+                ..function
+            };
+            methods.insert(method.name.value, function);
+        }
+        let name = &class_stmt.name;
+        let class = Object::Class(Rc::new(Class {
+            name: name.value,
+            superclass,
+            methods,
+        }));
+        set_variable(name.value, name.slot.get(), class, &env);
+        Object::Null
+    }
+
+    // The body runs once for each value, bound to the pattern in the
+    // enclosing scope. A `return` in it leaves the function, so it is not
+    // in tail position.
+    fn eval_for_statement(
+        &mut self,
+        for_stmt: &statements::ForStatement,
+        env: Env,
+        position: Position,
+    ) -> Object {
+        let span = for_stmt.token.span;
+        let iterable = match &for_stmt.iterable {
+            Some(expr) => self.eval_expression(expr.as_ref(), env.clone()),
+            None => return new_error(ErrorKind::Malformed, "missing for iterable".to_string()),
+        };
+        if unwinds(&iterable) {
+            return iterable;
+        }
+        let mut iteration = match self.iterate(iterable) {
+            Ok(iteration) => iteration,
+            Err(err) => return locate(err, span),
+        };
+        let position = match position {
+            Position::Plain => Position::Plain,
+            _ => Position::Function,
+        };
+        loop {
+            let value = match self.advance(&mut iteration) {
+                Ok(Some(value)) => value,
+                Ok(None) => return Object::Null,
+                Err(err) => return locate(err, span),
+            };
+            if let Err(err) = bind_pattern(&for_stmt.pattern, value, &env) {
+                return locate(err, span);
+            }
+            let result = self.eval_block_statement(&for_stmt.body, env.clone(), position);
+            match result {
+                Object::ReturnValue(_)
+                | Object::TailCall(_)
+                | Object::Error(_)
+                | Object::Interrupt(_) => return result,
+                _ => {}
+            }
         }
-        return eval_prefix_expression(&prefix.operator, right);
     }
 
-    if let Some(infix) = expression.as_any().downcast_ref::<expressions::Infix>() {
+    // An instance is changed in place. A struct is a value, so updating one
+    // of its fields makes a new struct, which replaces the old one in the
+    // variable, or the field of an enclosing struct or instance, holding it.
+    fn eval_assign_statement(&mut self, assign: &statements::AssignStatement, env: Env) -> Object {
+        let (Some(object_expr), Some(value_expr)) = (&assign.object, &assign.value) else {
+            return new_error(ErrorKind::Malformed, "missing assignment".to_string());
+        };
+        let object = self.eval_expression(object_expr.as_ref(), env.clone());
+        if unwinds(&object) {
+            return object;
+        }
+        let field = assign.property.value;
+        if !matches!(object, Object::Instance(_) | Object::Struct(_)) {
+            let error = new_error(
+                ErrorKind::TypeMismatch,
+                format!(
+                    "cannot assign to field {} of {}",
+                    field,
+                    describe_type(&object)
+                ),
+            );
+            return locate(error, assign.token.span);
+        }
+        if matches!(object, Object::Struct(_)) && !is_place(object_expr.as_ref()) {
+            let error = new_error(
+                ErrorKind::TypeMismatch,
+                format!(
+                    "cannot assign to field {} of {} that is not held in a variable",
+                    field,
+                    describe_type(&object)
+                ),
+            );
+            return locate(error, assign.token.span);
+        }
+        let value = self.eval_expression(value_expr.as_ref(), env.clone());
+        if unwinds(&value) {
+            return value;
+        }
+        let result = self.assign_field(object_expr.as_ref(), object, field, value, env);
+        locate(result, assign.token.span)
+    }
+
+    // Sets `field` of `object`, which `place` evaluated to.
+    fn assign_field(
+        &mut self,
+        place: &dyn Expression,
+        object: Object,
+        field: Symbol,
+        value: Object,
+        env: Env,
+    ) -> Object {
+        let target = match object {
+            Object::Instance(instance) => {
+                if let Some(interrupt) = self.allocate(std::mem::size_of::<Object>()) {
+                    return interrupt;
+                }
+                instance.fields.borrow_mut().insert(field, value);
+                return Object::Null;
+            }
+            Object::Struct(target) => target,
+            object => {
+                return new_error(
+                    ErrorKind::TypeMismatch,
+                    format!(
+                        "cannot assign to field {} of {}",
+                        field,
+                        describe_type(&object)
+                    ),
+                )
+            }
+        };
+        let Some(index) = target.layout.index(field) else {
+            return new_error(
+                ErrorKind::Member,
+                format!("{} has no field {}", target.layout.name, field),
+            );
+        };
+        if let Some(interrupt) = self.allocate(array_size(target.values.len())) {
+            return interrupt;
+        }
+        let mut values = target.values.clone();
+        values[index] = value;
+        let updated = self.charged(Object::Struct(Rc::new(StructObject {
+            layout: Rc::clone(&target.layout),
+            values,
+        })));
+
+        let any = place.as_any();
+        if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+            set_variable(identifier.value, identifier.slot.get(), updated, &env);
+            return Object::Null;
+        }
+        let Some(member) = any.downcast_ref::<expressions::Member>() else {
+            unreachable!("a place is an identifier or a member")
+        };
+        let Some(outer_expr) = &member.object else {
+            return new_error(ErrorKind::Malformed, "missing member object".to_string());
+        };
+        // Reading a place has no side effects, so it can be read again.
+        let outer = self.eval_expression(outer_expr.as_ref(), env.clone());
+        if unwinds(&outer) {
+            return outer;
+        }
+        self.assign_field(
+            outer_expr.as_ref(),
+            outer,
+            member.property.value,
+            updated,
+            env,
+        )
+    }
+
+    fn eval_infix(&mut self, infix: &expressions::Infix, env: Env) -> Object {
         let left_expr = match &infix.left {
             Some(expr) => expr,
-            None => return new_error("missing left expression".to_string()),
+            None => return new_error(ErrorKind::Malformed, "missing left expression".to_string()),
         };
         let right_expr = match &infix.right {
             Some(expr) => expr,
-            None => return new_error("missing right expression".to_string()),
+            None => return new_error(ErrorKind::Malformed, "missing right expression".to_string()),
         };
 
-        let left = eval_expression(left_expr.as_ref(), env.clone());
-        if is_error(&left) {
+        let left = self.eval_expression(left_expr.as_ref(), env.clone());
+        if unwinds(&left) {
             return left;
         }
-        let right = eval_expression(right_expr.as_ref(), env);
-        if is_error(&right) {
+        let right = self.eval_expression(right_expr.as_ref(), env);
+        if unwinds(&right) {
             return right;
         }
-        return eval_infix_expression(&infix.operator, left, right);
+        if let (Object::String(l), Object::String(r)) = (&left, &right) {
+            if infix.operator == "+" {
+                if let Some(interrupt) = self.allocate(l.len() + r.len()) {
+                    return interrupt;
+                }
+                let concatenated = eval_infix_expression(&infix.operator, left, right);
+                return self.charged(concatenated);
+            }
+        }
+        locate(
+            eval_infix_expression(&infix.operator, left, right),
+            infix.token.span,
+        )
+    }
+
+    fn eval_expressions(
+        &mut self,
+        expressions: &[Box<dyn Expression>],
+        env: Env,
+    ) -> Result<Vec<Object>, Object> {
+        let mut result = Vec::new();
+        for expression in expressions.iter() {
+            let evaluated = self.eval_expression(expression.as_ref(), env.clone());
+            if unwinds(&evaluated) {
+                return Err(evaluated);
+            }
+            result.push(evaluated);
+        }
+        Ok(result)
     }
 
-    if let Some(if_expr) = expression.as_any().downcast_ref::<expressions::If>() {
-        return eval_if_expression(if_expr, env);
+    fn eval_hash_literal(&mut self, hash: &expressions::HashLiteral, env: Env) -> Object {
+        let mut pairs = BTreeMap::new();
+        for (key_expr, value_expr) in hash.pairs.iter() {
+            let key = self.eval_expression(key_expr.as_ref(), env.clone());
+            if unwinds(&key) {
+                return key;
+            }
+            let hash_key = match HashKey::from_object(&key) {
+                Some(hash_key) => hash_key,
+                None => {
+                    let error = new_error(
+                        ErrorKind::Index,
+                        format!("unusable as hash key: {}", object_type(&key)),
+                    );
+                    return locate(error, hash.token.span);
+                }
+            };
+
+            let value = self.eval_expression(value_expr.as_ref(), env.clone());
+            if unwinds(&value) {
+                return value;
+            }
+            if let Some(interrupt) = self.allocate(HASH_ENTRY_SIZE) {
+                return interrupt;
+            }
+            pairs.insert(hash_key, value);
+        }
+        self.charged(Object::Hash(Rc::new(pairs)))
     }
 
-    if let Some(func) = expression
-        .as_any()
-        .downcast_ref::<expressions::FunctionLiteral>()
-    {
-        let parameters = func
-            .parameters
-            .as_ref()
-            .map(|params| params.iter().map(|param| param.value.clone()).collect())
-            .unwrap_or_default();
-        let body = match &func.body {
-            Some(body) => Rc::clone(body),
-            None => return new_error("missing function body".to_string()),
+    // Every field of the struct has to be given, either in the literal or by
+    // the struct it spreads, which has to be of the same type.
+    fn eval_struct_literal(&mut self, literal: &expressions::StructLiteral, env: Env) -> Object {
+        let span = literal.token.span;
+        let type_expr = match &literal.struct_type {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing struct type".to_string()),
         };
-        return Object::Function(FunctionObject {
-            parameters,
-            body,
-            env,
-        });
+        let struct_type = self.eval_expression(type_expr.as_ref(), env.clone());
+        if unwinds(&struct_type) {
+            return struct_type;
+        }
+        let layout = match struct_type {
+            Object::StructType(layout) => layout,
+            other => {
+                let error = new_error(
+                    ErrorKind::TypeMismatch,
+                    format!("not a struct type: {}", object_type(&other)),
+                );
+                return locate(error, span);
+            }
+        };
+
+        let mut values: Vec<Option<Object>> = vec![None; layout.fields.len()];
+        if let Some(base_expr) = &literal.base {
+            let base = self.eval_expression(base_expr.as_ref(), env.clone());
+            if unwinds(&base) {
+                return base;
+            }
+            match base {
+                Object::Struct(base) if Rc::ptr_eq(&base.layout, &layout) => {
+                    values = base.values.iter().cloned().map(Some).collect();
+                }
+                other => {
+                    let error = new_error(
+                        ErrorKind::TypeMismatch,
+                        format!(
+                            "cannot spread {} into {}",
+                            describe_type(&other),
+                            layout.name
+                        ),
+                    );
+                    return locate(error, span);
+                }
+            }
+        }
+
+        for (name, value_expr) in literal.fields.iter() {
+            let index = match layout.index(name.value) {
+                Some(index) => index,
+                None => {
+                    let error = new_error(
+                        ErrorKind::Member,
+                        format!("{} has no field {}", layout.name, name.value),
+                    );
+                    return locate(error, name.token.span);
+                }
+            };
+            let value = self.eval_expression(value_expr.as_ref(), env.clone());
+            if unwinds(&value) {
+                return value;
+            }
+            values[index] = Some(value);
+        }
+
+        let mut fields = Vec::with_capacity(values.len());
+        for (name, value) in layout.fields.iter().zip(values) {
+            match value {
+                Some(value) => fields.push(value),
+                None => {
+                    let error = new_error(
+                        ErrorKind::Arguments,
+                        format!("missing field {} in {}", name, layout.name),
+                    );
+                    return locate(error, span);
+                }
+            }
+        }
+        if let Some(interrupt) = self.allocate(array_size(fields.len())) {
+            return interrupt;
+        }
+        self.charged(Object::Struct(Rc::new(StructObject {
+            layout,
+            values: fields,
+        })))
     }
 
-    if let Some(call) = expression
-        .as_any()
-        .downcast_ref::<expressions::CallExpression>()
-    {
-        let function_expr = match &call.function {
+    fn eval_if_expression(
+        &mut self,
+        if_expr: &expressions::If,
+        env: Env,
+        position: Position,
+    ) -> Object {
+        let condition_expr = match &if_expr.condition {
             Some(expr) => expr,
-            None => return new_error("missing function expression".to_string()),
+            None => return new_error(ErrorKind::Malformed, "missing if condition".to_string()),
         };
-        let function = eval_expression(function_expr.as_ref(), env.clone());
-        if is_error(&function) {
-            return function;
+        let condition = self.eval_expression(condition_expr.as_ref(), env.clone());
+        if unwinds(&condition) {
+            return condition;
         }
 
-        let args = match &call.arguments {
-            Some(args) => match eval_expressions(args, env) {
-                Ok(args) => args,
+        if is_truthy(&condition) {
+            match &if_expr.first {
+                Some(block) => self.eval_block_statement(block, env, position),
+                None => Object::Null,
+            }
+        } else {
+            match &if_expr.second {
+                Some(block) => self.eval_block_statement(block, env, position),
+                None => Object::Null,
+            }
+        }
+    }
+
+    // The arms are tried in order, and the body of the first one whose
+    // pattern matches is the value of the `match`. The names the pattern
+    // binds are set in the enclosing scope.
+    fn eval_match(
+        &mut self,
+        match_expr: &expressions::Match,
+        env: Env,
+        position: Position,
+    ) -> Object {
+        let subject_expr = match &match_expr.subject {
+            Some(expr) => expr,
+            None => return new_error(ErrorKind::Malformed, "missing match subject".to_string()),
+        };
+        let subject = self.eval_expression(subject_expr.as_ref(), env.clone());
+        if unwinds(&subject) {
+            return subject;
+        }
+
+        for arm in match_expr.arms.iter() {
+            let mut bindings = Vec::new();
+            match match_pattern(&arm.pattern, &subject, &env, &mut bindings) {
+                Ok(true) => {}
+                Ok(false) => continue,
                 Err(err) => return err,
-            },
-            None => vec![],
+            }
+            // The names the pattern binds are in a frame of their own,
+            // which only the arm sees.
+            let env = match bindings.is_empty() {
+                true => env,
+                false => {
+                    let frame = Environment::new_frame(env, bindings.len());
+                    self.heap.track(&frame);
+                    frame
+                }
+            };
+            for (identifier, value) in bindings {
+                set_variable(identifier.value, identifier.slot.get(), value, &env);
+            }
+            return match position {
+                Position::Tail => self.eval_tail_expression(arm.body.as_ref(), env),
+                _ => self.eval_expression(arm.body.as_ref(), env),
+            };
+        }
+        let error = new_error(ErrorKind::Match, format!("no match arm for {}", subject));
+        locate(error, match_expr.token.span)
+    }
+
+    // The blocks of a `try` are evaluated in plain position, so that calls
+    // in them are not tail calls and a `return` still runs the `finally`
+    // block on its way out. Interrupts are not caught, and skip `finally`.
+    fn eval_try_expression(&mut self, try_expr: &expressions::Try, env: Env) -> Object {
+        let mut result = match &try_expr.body {
+            Some(block) => self.eval_block_statement(block, env.clone(), Position::Plain),
+            None => Object::Null,
+        };
+
+        if let (Some(parameter), Some(handler)) = (&try_expr.parameter, &try_expr.handler) {
+            if let Object::Error(error) = result {
+                // The parameter is bound in a frame of its own, which only
+                // the handler sees.
+                let frame = Environment::new_frame(env.clone(), 1);
+                self.heap.track(&frame);
+                let caught = Object::Exception(Rc::new(*error));
+                set_variable(parameter.value, parameter.slot.get(), caught, &frame);
+                result = self.eval_block_statement(handler, frame, Position::Plain);
+            }
+        }
+
+        if matches!(result, Object::Interrupt(_)) {
+            return result;
+        }
+        // An error or `return` in the `finally` block replaces the result.
+        if let Some(finalizer) = &try_expr.finalizer {
+            let finished = self.eval_block_statement(finalizer, env, Position::Plain);
+            if unwinds(&finished) {
+                return finished;
+            }
+        }
+        result
+    }
+
+    fn apply_function(
+        &mut self,
+        function: Object,
+        args: Vec<Object>,
+        named_args: Vec<(Symbol, Object)>,
+        call_site: Span,
+    ) -> Object {
+        let frame = CallFrame {
+            name: function_name(&function),
+            call_site,
         };
+        if self.call_stack.len() >= self.max_depth {
+            return locate(self.recursion_error(frame), call_site);
+        }
+
+        self.call_stack.push(frame);
+        let mut result = self.trampoline(TailCall {
+            function,
+            args,
+            named_args,
+            call_site,
+        });
+        // The frame now describes the last of any tail calls made, which
+        // is the one the error came out of.
+        let frame = self.call_stack.pop().unwrap();
+        if let Object::Error(error) = &mut result {
+            error.trace.push(frame);
+        }
+        result
+    }
+
+    // Applies a call, and then every tail call it returns, reusing the
+    // caller's frame on the call stack.
+    fn trampoline(&mut self, mut call: TailCall) -> Object {
+        loop {
+            if let Some(frame) = self.call_stack.last_mut() {
+                frame.name = function_name(&call.function);
+                frame.call_site = call.call_site;
+            }
+            let function = match call.function {
+                Object::Function(function) => function,
+                Object::Builtin(builtin) => {
+                    if let Some((name, _)) = call.named_args.first() {
+                        let error = new_error(
+                            ErrorKind::Arguments,
+                            format!(
+                                "unexpected keyword argument in call to {}: {}",
+                                builtin.name, name
+                            ),
+                        );
+                        return locate(error, call.call_site);
+                    }
+                    return locate((builtin.func)(self, call.args), call.call_site);
+                }
+                Object::Method(bound) => {
+                    if let Some((name, _)) = call.named_args.first() {
+                        let error = new_error(
+                            ErrorKind::Arguments,
+                            format!(
+                                "unexpected keyword argument in call to {}: {}",
+                                bound.method.name, name
+                            ),
+                        );
+                        return locate(error, call.call_site);
+                    }
+                    let result = (bound.method.func)(self, bound.receiver, call.args);
+                    return locate(result, call.call_site);
+                }
+                Object::BoundFunction(bound) => bound.function,
+                Object::Class(class) => {
+                    return self.instantiate(class, call.args, call.named_args, call.call_site)
+                }
+                Object::Constructor(constructor) => {
+                    return self.construct(constructor, call.args, call.named_args, call.call_site)
+                }
+                _ => {
+                    let error = new_error(
+                        ErrorKind::NotCallable,
+                        format!("not a function: {}", object_type(&call.function)),
+                    );
+                    return locate(error, call.call_site);
+                }
+            };
+            let extended_env = match self.extend_function_env(
+                &function,
+                call.args,
+                call.named_args,
+                call.call_site,
+            ) {
+                Ok(env) => env,
+                // A `?` in a default value returns from this call.
+                Err(err) => return unwrap_return_value(locate(err, call.call_site)),
+            };
+            if function.generator {
+                return self.start_generator(function, extended_env, call.call_site);
+            }
+            match self.eval_block_statement(function.body.as_ref(), extended_env, Position::Tail) {
+                Object::TailCall(tail_call) => call = *tail_call,
+                evaluated => return unwrap_return_value(evaluated),
+            }
+        }
+    }
+
+    // A value of one of the variants of an enum.
+    fn construct(
+        &mut self,
+        constructor: Constructor,
+        args: Vec<Object>,
+        named_args: Vec<(Symbol, Object)>,
+        call_site: Span,
+    ) -> Object {
+        let layout = constructor.layout;
+        let name = format!("{}.{}", layout.name, layout.variant_name(constructor.tag));
+        if let Some((keyword, _)) = named_args.first() {
+            let error = new_error(
+                ErrorKind::Arguments,
+                format!(
+                    "unexpected keyword argument in call to {}: {}",
+                    name, keyword
+                ),
+            );
+            return locate(error, call_site);
+        }
+        let fields = layout.variants[constructor.tag].1.len();
+        if args.len() != fields {
+            let error = builtins::wrong_arguments(&name, fields, args.len());
+            return locate(error, call_site);
+        }
+        if let Some(interrupt) = self.allocate(array_size(fields)) {
+            return interrupt;
+        }
+        self.charged(Object::Variant(Rc::new(VariantObject {
+            layout,
+            tag: constructor.tag,
+            values: args,
+        })))
+    }
 
-        return apply_function(function, args);
+    // Calling a function declared with `fn*` only sets up its frame.
+    fn start_generator(&mut self, function: FunctionObject, env: Env, call_site: Span) -> Object {
+        if let Some(interrupt) = self.allocate(std::mem::size_of::<Generator>()) {
+            return interrupt;
+        }
+        let generator = Generator::new(function, env, call_site);
+        self.charged(Object::Generator(Rc::new(generator)))
     }
 
-    Object::Null
+    // A new instance, set up by the class's `init` method when it has one.
+    fn instantiate(
+        &mut self,
+        class: Rc<Class>,
+        args: Vec<Object>,
+        named_args: Vec<(Symbol, Object)>,
+        call_site: Span,
+    ) -> Object {
+        if let Some(interrupt) = self.allocate(std::mem::size_of::<Instance>()) {
+            return interrupt;
+        }
+        let instance = Rc::new(Instance {
+            class: Rc::clone(&class),
+            fields: RefCell::new(BTreeMap::new()),
+        });
+        self.heap.track_instance(&instance);
+
+        let init = Symbol::intern("init");
+        let Some((declaring, method)) = Class::find_method(&class, init) else {
+            if let Some((name, _)) = named_args.first() {
+                let error = new_error(
+                    ErrorKind::Arguments,
+                    format!(
+                        "unexpected keyword argument in call to {}: {}",
+                        class.name, name
+                    ),
+                );
+                return locate(error, call_site);
+            }
+            if !args.is_empty() {
+                let error = builtins::wrong_arguments(class.name.as_str(), 0, args.len());
+                return locate(error, call_site);
+            }
+            return Object::Instance(instance);
+        };
+        let init = self.bind_method(Rc::clone(&instance), declaring, method);
+        let result = self.apply_function(init, args, named_args, call_site);
+        if unwinds(&result) {
+            return result;
+        }
+        Object::Instance(instance)
+    }
+
+    fn recursion_error(&self, frame: CallFrame) -> Object {
+        let mut chain: Vec<(&CallFrame, usize)> = Vec::new();
+        for frame in self.call_stack.iter().chain(std::iter::once(&frame)) {
+            match chain.last_mut() {
+                Some((last, count)) if *last == frame => *count += 1,
+                _ => chain.push((frame, 1)),
+            }
+        }
+        let chain: Vec<String> = chain
+            .iter()
+            .map(|(frame, count)| match count {
+                1 => frame.to_string(),
+                _ => format!("{} x{}", frame, count),
+            })
+            .collect();
+        new_error(
+            ErrorKind::RecursionLimit,
+            format!(
+                "maximum recursion depth exceeded (limit {}): {}",
+                self.max_depth,
+                chain.join(" -> ")
+            ),
+        )
+    }
+
+    fn extend_function_env(
+        &mut self,
+        function: &FunctionObject,
+        args: Vec<Object>,
+        named_args: Vec<(Symbol, Object)>,
+        call_site: Span,
+    ) -> Result<Env, Object> {
+        let parameters = function.parameters.as_ref();
+        let required = parameters.iter().filter(|p| p.default.is_none()).count();
+        let given = args.len() + named_args.len();
+        let too_many = function.rest.is_none() && args.len() > parameters.len();
+        if too_many || given < required {
+            return Err(arity_error(function, given, call_site));
+        }
+
+        let mut args = args.into_iter();
+        let mut values: Vec<Option<Object>> = parameters.iter().map(|_| args.next()).collect();
+        let rest: Vec<Object> = args.collect();
+
+        for (name, value) in named_args {
+            let index = match parameters.iter().position(|p| p.name() == Some(name)) {
+                Some(index) => index,
+                None => {
+                    return Err(call_error(
+                        function,
+                        call_site,
+                        "unexpected keyword argument",
+                        name.to_string(),
+                    ))
+                }
+            };
+            if values[index].is_some() {
+                return Err(call_error(
+                    function,
+                    call_site,
+                    "multiple values for argument",
+                    name.to_string(),
+                ));
+            }
+            values[index] = Some(value);
+        }
+
+        if self.heap.should_collect() {
+            self.heap.collect();
+        }
+        let env = Environment::new_frame(Rc::clone(&function.env), function.locals);
+        self.heap.track(&env);
+        for (value, parameter) in values.into_iter().zip(parameters.iter()) {
+            let value = match (value, &parameter.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => {
+                    let value = self.eval_expression(default.as_ref(), env.clone());
+                    if unwinds(&value) {
+                        return Err(value);
+                    }
+                    value
+                }
+                (None, None) => {
+                    return Err(call_error(
+                        function,
+                        call_site,
+                        "missing argument",
+                        parameter.pattern.to_string(),
+                    ))
+                }
+            };
+            bind_pattern(&parameter.pattern, value, &env)?;
+        }
+        if let Some(name) = &function.rest {
+            if let Some(interrupt) = self.allocate(array_size(rest.len())) {
+                return Err(interrupt);
+            }
+            let rest = self.charged(Object::Array(Rc::new(rest)));
+            set_variable(*name, function.rest_slot, rest, &env);
+        }
+        Ok(env)
+    }
 }
 
-fn eval_expressions(
-    expressions: &[Box<dyn Expression>],
-    env: Env,
-) -> Result<Vec<Object>, Object> {
-    let mut result = Vec::new();
-    for expression in expressions.iter() {
-        let evaluated = eval_expression(expression.as_ref(), env.clone());
-        if is_error(&evaluated) {
-            return Err(evaluated);
+fn eval_function_literal(func: &expressions::FunctionLiteral, env: Env) -> Object {
+    let parameters = match &func.parameters {
+        Some(parameters) => Rc::clone(parameters),
+        None => Rc::new(vec![]),
+    };
+    let body = match &func.body {
+        Some(body) => Rc::clone(body),
+        None => return new_error(ErrorKind::Malformed, "missing function body".to_string()),
+    };
+    Object::Function(FunctionObject {
+        name: None,
+        parameters,
+        rest: func.rest.as_ref().map(|rest| rest.value),
+        rest_slot: func.rest.as_ref().and_then(|rest| rest.slot.get()),
+        body,
+        env,
+        locals: func.locals.get(),
+        generator: func.generator,
+    })
+}
+
+fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (&left, &index) {
+        (Object::Exception(error), Object::String(key)) => {
+            exception_field(error, key).unwrap_or(Object::Null)
+        }
+        (Object::Array(elements), Object::Integer(i)) => {
+            if *i < 0 {
+                return Object::Null;
+            }
+            elements.get(*i as usize).cloned().unwrap_or(Object::Null)
         }
-        result.push(evaluated);
+        (Object::Hash(pairs), _) => match HashKey::from_object(&index) {
+            Some(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            None => new_error(
+                ErrorKind::Index,
+                format!("unusable as hash key: {}", object_type(&index)),
+            ),
+        },
+        _ => new_error(
+            ErrorKind::Index,
+            format!(
+                "index operator not supported: {}[{}]",
+                object_type(&left),
+                object_type(&index)
+            ),
+        ),
     }
-    Ok(result)
 }
 
-fn eval_identifier(identifier: &expressions::Identifier, env: Env) -> Object {
-    match env.borrow().get(&identifier.value) {
-        Some(value) => value,
-        None => new_error(format!("identifier not found: {}", identifier.value)),
+// `x.name` is an export of a module, a field of a struct or of a caught
+// error, a variant of an enum type or the entry under the key "name" of a
+// hash. Otherwise it is a method built into the value, bound to it so that
+// `x.name(...)` calls it on `x`.
+fn eval_member_access(object: Object, name: Symbol) -> Object {
+    match &object {
+        Object::Module(module) => {
+            return match module.exports.get(&name) {
+                Some(value) => value.clone(),
+                None => new_error(
+                    ErrorKind::Member,
+                    format!("module {} does not export {}", module.name, name),
+                ),
+            }
+        }
+        Object::Hash(pairs) => {
+            if let Some(value) = pairs.get(&HashKey::String(name.as_str().into())) {
+                return value.clone();
+            }
+        }
+        Object::Exception(error) => {
+            if let Some(value) = exception_field(error, name.as_str()) {
+                return value;
+            }
+        }
+        Object::Struct(value) => {
+            return match value.layout.index(name) {
+                Some(index) => value.values[index].clone(),
+                None => new_error(
+                    ErrorKind::Member,
+                    format!("{} has no field {}", value.layout.name, name),
+                ),
+            }
+        }
+        // A variant without fields is a value, one with fields the
+        // constructor that builds it.
+        Object::EnumType(layout) => {
+            return match layout.tag(name) {
+                Some(tag) if layout.variants[tag].1.is_empty() => {
+                    Object::Variant(Rc::new(VariantObject {
+                        layout: Rc::clone(layout),
+                        tag,
+                        values: vec![],
+                    }))
+                }
+                Some(tag) => Object::Constructor(Constructor {
+                    layout: Rc::clone(layout),
+                    tag,
+                }),
+                None => new_error(
+                    ErrorKind::Member,
+                    format!("{} has no variant {}", layout.name, name),
+                ),
+            }
+        }
+        _ => {}
+    }
+    match methods::lookup(&object, name.as_str()) {
+        Some(method) => Object::Method(Box::new(BoundMethod {
+            receiver: object,
+            method,
+        })),
+        None => new_error(
+            ErrorKind::Member,
+            format!("no member {} on {}", name, object_type(&object)),
+        ),
     }
 }
 
-fn eval_if_expression(if_expr: &expressions::If, env: Env) -> Object {
-    let condition_expr = match &if_expr.condition {
-        Some(expr) => expr,
-        None => return new_error("missing if condition".to_string()),
+// What a script can read from an error it caught.
+fn exception_field(error: &RuntimeError, key: &str) -> Option<Object> {
+    let value = match key {
+        "message" => Object::String(error.message.as_str().into()),
+        "kind" => Object::String(error.kind.to_string().into()),
+        "trace" => Object::Array(Rc::new(
+            error
+                .trace
+                .iter()
+                .map(|frame| Object::String(frame.to_string().into()))
+                .collect(),
+        )),
+        "value" => error.value.clone().unwrap_or(Object::Null),
+        _ => return None,
+    };
+    Some(value)
+}
+
+// Throwing an error that was caught raises it again as it was, trace
+// included. Anything else becomes the value of a new error.
+fn throw(value: Object) -> Object {
+    let error = match value {
+        Object::Exception(error) => Rc::unwrap_or_clone(error),
+        value => {
+            let mut error = RuntimeError::new(ErrorKind::Thrown, value.to_string());
+            error.value = Some(value);
+            error
+        }
     };
-    let condition = eval_expression(condition_expr.as_ref(), env.clone());
-    if is_error(&condition) {
-        return condition;
+    Object::Error(Box::new(error))
+}
+
+fn bind_pattern(pattern: &patterns::Pattern, value: Object, env: &Env) -> Result<(), Object> {
+    match pattern {
+        patterns::Pattern::Identifier(identifier) => {
+            set_variable(identifier.value, identifier.slot.get(), value, env);
+            Ok(())
+        }
+        patterns::Pattern::Array(array) => {
+            let elements = match value {
+                Object::Array(elements) => elements,
+                _ => {
+                    return Err(destructure_error(
+                        describe_array_pattern(array),
+                        describe_shape(&value),
+                    ))
+                }
+            };
+            let arity_ok = match array.rest {
+                Some(_) => elements.len() >= array.elements.len(),
+                None => elements.len() == array.elements.len(),
+            };
+            if !arity_ok {
+                return Err(destructure_error(
+                    describe_array_pattern(array),
+                    format!("array of {} elements", elements.len()),
+                ));
+            }
+
+            let mut elements = Rc::unwrap_or_clone(elements).into_iter();
+            for element in array.elements.iter() {
+                bind_pattern(element, elements.next().unwrap_or(Object::Null), env)?;
+            }
+            if let Some(rest) = &array.rest {
+                let elements = Object::Array(Rc::new(elements.collect()));
+                set_variable(rest.value, rest.slot.get(), elements, env);
+            }
+            Ok(())
+        }
+        patterns::Pattern::Hash(hash) => {
+            let mut pairs = match value {
+                Object::Hash(pairs) => Rc::unwrap_or_clone(pairs),
+                _ => {
+                    return Err(destructure_error(
+                        describe_hash_pattern(hash),
+                        describe_shape(&value),
+                    ))
+                }
+            };
+            for entry in hash.entries.iter() {
+                let value = match pairs.remove(&HashKey::String(entry.key.as_str().into())) {
+                    Some(value) => value,
+                    None => {
+                        return Err(destructure_error(
+                            describe_hash_pattern(hash),
+                            format!("hash without key \"{}\"", entry.key),
+                        ))
+                    }
+                };
+                bind_pattern(&entry.pattern, value, env)?;
+            }
+            Ok(())
+        }
     }
+}
 
-    if is_truthy(&condition) {
-        match &if_expr.first {
-            Some(block) => eval_block_statement(block, env),
-            None => Object::Null,
+// Whether `value` matches `pattern`, collecting the names it binds. A
+// pattern naming a variant that does not exist is an error rather than a
+// pattern that never matches.
+fn match_pattern<'a>(
+    pattern: &'a patterns::MatchPattern,
+    value: &Object,
+    env: &Env,
+    bindings: &mut Vec<(&'a expressions::Identifier, Object)>,
+) -> Result<bool, Object> {
+    match (pattern, value) {
+        (patterns::MatchPattern::Wildcard(_), _) => Ok(true),
+        (patterns::MatchPattern::Binding(identifier), _) => {
+            bindings.push((identifier, value.clone()));
+            Ok(true)
         }
-    } else {
-        match &if_expr.second {
-            Some(block) => eval_block_statement(block, env),
-            None => Object::Null,
+        (patterns::MatchPattern::Integer(expected), Object::Integer(value)) => {
+            Ok(expected == value)
+        }
+        (patterns::MatchPattern::String(expected), Object::String(value)) => {
+            Ok(expected.as_str() == value.as_ref())
+        }
+        (patterns::MatchPattern::Boolean(expected), Object::Boolean(value)) => {
+            Ok(expected == value)
+        }
+        (patterns::MatchPattern::Variant(variant), _) => {
+            match_variant(variant, value, env, bindings)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn match_variant<'a>(
+    pattern: &'a patterns::VariantPattern,
+    value: &Object,
+    env: &Env,
+    bindings: &mut Vec<(&'a expressions::Identifier, Object)>,
+) -> Result<bool, Object> {
+    let span = pattern.token.span;
+    let layout = match eval_identifier(&pattern.enum_name, env.clone()) {
+        Object::EnumType(layout) => layout,
+        Object::Error(error) => return Err(locate(Object::Error(error), span)),
+        other => {
+            let error = new_error(
+                ErrorKind::TypeMismatch,
+                format!("not an enum type: {}", object_type(&other)),
+            );
+            return Err(locate(error, span));
         }
+    };
+    let variant = pattern.variant.value;
+    let tag = match layout.tag(variant) {
+        Some(tag) => tag,
+        None => {
+            let error = new_error(
+                ErrorKind::Member,
+                format!("{} has no variant {}", layout.name, variant),
+            );
+            return Err(locate(error, pattern.variant.token.span));
+        }
+    };
+    let fields = layout.variants[tag].1.len();
+    if fields != pattern.fields.len() {
+        let error = destructure_error(
+            format!("{} fields for {}.{}", fields, layout.name, variant),
+            format!("{} patterns", pattern.fields.len()),
+        );
+        return Err(locate(error, span));
+    }
+
+    let Object::Variant(value) = value else {
+        return Ok(false);
+    };
+    if !Rc::ptr_eq(&value.layout, &layout) || value.tag != tag {
+        return Ok(false);
+    }
+    for (field, value) in pattern.fields.iter().zip(value.values.iter()) {
+        if !match_pattern(field, value, env, bindings)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn describe_array_pattern(array: &patterns::ArrayPattern) -> String {
+    match array.rest {
+        Some(_) => format!("array of at least {} elements", array.elements.len()),
+        None => format!("array of {} elements", array.elements.len()),
+    }
+}
+
+fn describe_hash_pattern(hash: &patterns::HashPattern) -> String {
+    let keys: Vec<String> = hash
+        .entries
+        .iter()
+        .map(|entry| format!("\"{}\"", entry.key))
+        .collect();
+    format!("hash with keys {}", keys.join(", "))
+}
+
+fn describe_shape(obj: &Object) -> String {
+    match obj {
+        Object::Array(elements) => format!("array of {} elements", elements.len()),
+        _ => object_type(obj).to_string(),
+    }
+}
+
+// The type of a value, naming structs by their type.
+// Whether an expression names somewhere a value is held: a variable, or a
+// field of a value held somewhere.
+fn is_place(expression: &dyn Expression) -> bool {
+    let any = expression.as_any();
+    if any.is::<expressions::Identifier>() {
+        return true;
+    }
+    any.downcast_ref::<expressions::Member>()
+        .and_then(|member| member.object.as_deref())
+        .is_some_and(is_place)
+}
+
+fn describe_type(obj: &Object) -> String {
+    match obj {
+        Object::Struct(value) => value.layout.name.to_string(),
+        Object::Variant(value) => value.layout.name.to_string(),
+        Object::Instance(instance) => instance.class.name.to_string(),
+        _ => object_type(obj).to_string(),
+    }
+}
+
+fn destructure_error(expected: String, got: String) -> Object {
+    new_error(
+        ErrorKind::Destructure,
+        format!("cannot destructure: expected {}, got {}", expected, got),
+    )
+}
+
+// Locals are always bound in the innermost frame, so only globals are set
+// by name.
+fn set_variable(name: Symbol, slot: Option<Slot>, value: Object, env: &Env) {
+    match slot {
+        Some(slot) => env.borrow_mut().set_slot(slot, value),
+        None => env.borrow_mut().set_global(name, value),
+    }
+}
+
+fn eval_identifier(identifier: &expressions::Identifier, env: Env) -> Object {
+    if let Some(slot) = identifier.slot.get() {
+        return match env.borrow().get_slot(slot) {
+            Some(value) => value,
+            None => new_error(
+                ErrorKind::UndefinedVariable,
+                format!("identifier not found: {}", identifier.value),
+            ),
+        };
+    }
+    match env.borrow().get(identifier.value) {
+        Some(value) => value,
+        None => match builtins::lookup(identifier.value.as_str()) {
+            Some(builtin) => Object::Builtin(builtin),
+            None => new_error(
+                ErrorKind::UndefinedVariable,
+                format!("identifier not found: {}", identifier.value),
+            ),
+        },
     }
 }
 
@@ -309,7 +1951,10 @@ fn eval_prefix_expression(operator: &str, right: Object) -> Object {
     match operator {
         "!" => eval_bang_operator_expression(right),
         "-" => eval_minus_prefix_operator_expression(right),
-        _ => new_error(format!("unknown operator: {}{}", operator, object_type(&right))),
+        _ => new_error(
+            ErrorKind::UnknownOperator,
+            format!("unknown operator: {}{}", operator, object_type(&right)),
+        ),
     }
 }
 
@@ -321,40 +1966,136 @@ fn eval_infix_expression(operator: &str, left: Object, right: Object) -> Object
         (Object::Boolean(left), Object::Boolean(right)) => {
             eval_boolean_infix_expression(operator, *left, *right)
         }
+        (Object::String(left), Object::String(right)) => {
+            eval_string_infix_expression(operator, left, right)
+        }
+        // Variants are equal when they are the same variant of the same
+        // enum, and their fields are equal.
+        (Object::Variant(left_variant), Object::Variant(right_variant)) => match operator {
+            "==" => Object::Boolean(variants_equal(left_variant, right_variant)),
+            "!=" => Object::Boolean(!variants_equal(left_variant, right_variant)),
+            _ => new_error(
+                ErrorKind::UnknownOperator,
+                format!(
+                    "unknown operator: {} {} {}",
+                    object_type(&left),
+                    operator,
+                    object_type(&right)
+                ),
+            ),
+        },
+        // Structs are equal when they are of the same struct, and their
+        // fields are equal.
+        (Object::Struct(left_struct), Object::Struct(right_struct)) => match operator {
+            "==" => Object::Boolean(structs_equal(left_struct, right_struct)),
+            "!=" => Object::Boolean(!structs_equal(left_struct, right_struct)),
+            _ => new_error(
+                ErrorKind::UnknownOperator,
+                format!(
+                    "unknown operator: {} {} {}",
+                    object_type(&left),
+                    operator,
+                    object_type(&right)
+                ),
+            ),
+        },
+        // An instance is only equal to itself.
+        (Object::Instance(left_instance), Object::Instance(right_instance)) => match operator {
+            "==" => Object::Boolean(Rc::ptr_eq(left_instance, right_instance)),
+            "!=" => Object::Boolean(!Rc::ptr_eq(left_instance, right_instance)),
+            _ => new_error(
+                ErrorKind::UnknownOperator,
+                format!(
+                    "unknown operator: {} {} {}",
+                    object_type(&left),
+                    operator,
+                    object_type(&right)
+                ),
+            ),
+        },
         (Object::Null, Object::Null) => match operator {
             "==" => Object::Boolean(true),
             "!=" => Object::Boolean(false),
-            _ => new_error(format!(
-                "unknown operator: {} {} {}",
-                object_type(&left),
-                operator,
-                object_type(&right)
-            )),
+            _ => new_error(
+                ErrorKind::UnknownOperator,
+                format!(
+                    "unknown operator: {} {} {}",
+                    object_type(&left),
+                    operator,
+                    object_type(&right)
+                ),
+            ),
         },
         _ => match operator {
             "==" => Object::Boolean(false),
             "!=" => Object::Boolean(true),
-            _ => new_error(format!(
-                "type mismatch: {} {} {}",
-                object_type(&left),
-                operator,
-                object_type(&right)
-            )),
+            _ => new_error(
+                ErrorKind::TypeMismatch,
+                format!(
+                    "type mismatch: {} {} {}",
+                    object_type(&left),
+                    operator,
+                    object_type(&right)
+                ),
+            ),
         },
     }
 }
 
+fn variants_equal(left: &VariantObject, right: &VariantObject) -> bool {
+    Rc::ptr_eq(&left.layout, &right.layout)
+        && left.tag == right.tag
+        && left
+            .values
+            .iter()
+            .zip(right.values.iter())
+            .all(|(left, right)| {
+                matches!(
+                    eval_infix_expression("==", left.clone(), right.clone()),
+                    Object::Boolean(true)
+                )
+            })
+}
+
+fn structs_equal(left: &StructObject, right: &StructObject) -> bool {
+    Rc::ptr_eq(&left.layout, &right.layout)
+        && left
+            .values
+            .iter()
+            .zip(right.values.iter())
+            .all(|(left, right)| {
+                matches!(
+                    eval_infix_expression("==", left.clone(), right.clone()),
+                    Object::Boolean(true)
+                )
+            })
+}
+
 fn eval_integer_infix_expression(operator: &str, left: i64, right: i64) -> Object {
     match operator {
-        "+" => Object::Integer(left + right),
-        "-" => Object::Integer(left - right),
-        "*" => Object::Integer(left * right),
-        "/" => Object::Integer(left / right),
+        "+" => checked(left.checked_add(right), operator, left, right),
+        "-" => checked(left.checked_sub(right), operator, left, right),
+        "*" => checked(left.checked_mul(right), operator, left, right),
+        "/" if right == 0 => new_error(ErrorKind::Arithmetic, "division by zero".to_string()),
+        "/" => checked(left.checked_div(right), operator, left, right),
         "<" => Object::Boolean(left < right),
         ">" => Object::Boolean(left > right),
         "==" => Object::Boolean(left == right),
         "!=" => Object::Boolean(left != right),
-        _ => new_error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+        _ => new_error(
+            ErrorKind::UnknownOperator,
+            format!("unknown operator: INTEGER {} INTEGER", operator),
+        ),
+    }
+}
+
+fn checked(result: Option<i64>, operator: &str, left: i64, right: i64) -> Object {
+    match result {
+        Some(value) => Object::Integer(value),
+        None => new_error(
+            ErrorKind::Arithmetic,
+            format!("integer overflow: {} {} {}", left, operator, right),
+        ),
     }
 }
 
@@ -362,7 +2103,22 @@ fn eval_boolean_infix_expression(operator: &str, left: bool, right: bool) -> Obj
     match operator {
         "==" => Object::Boolean(left == right),
         "!=" => Object::Boolean(left != right),
-        _ => new_error(format!("unknown operator: BOOLEAN {} BOOLEAN", operator)),
+        _ => new_error(
+            ErrorKind::UnknownOperator,
+            format!("unknown operator: BOOLEAN {} BOOLEAN", operator),
+        ),
+    }
+}
+
+fn eval_string_infix_expression(operator: &str, left: &str, right: &str) -> Object {
+    match operator {
+        "+" => Object::String(format!("{}{}", left, right).into()),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => new_error(
+            ErrorKind::UnknownOperator,
+            format!("unknown operator: STRING {} STRING", operator),
+        ),
     }
 }
 
@@ -377,28 +2133,70 @@ fn eval_bang_operator_expression(right: Object) -> Object {
 
 fn eval_minus_prefix_operator_expression(right: Object) -> Object {
     match right {
-        Object::Integer(value) => Object::Integer(-value),
-        _ => new_error(format!("unknown operator: -{}", object_type(&right))),
+        Object::Integer(value) => match value.checked_neg() {
+            Some(value) => Object::Integer(value),
+            None => new_error(
+                ErrorKind::Arithmetic,
+                format!("integer overflow: -{}", value),
+            ),
+        },
+        _ => new_error(
+            ErrorKind::UnknownOperator,
+            format!("unknown operator: -{}", object_type(&right)),
+        ),
     }
 }
 
-fn apply_function(function: Object, args: Vec<Object>) -> Object {
-    match function {
-        Object::Function(function) => {
-            let extended_env = extend_function_env(&function, args);
-            let evaluated = eval_block_statement(function.body.as_ref(), extended_env);
-            unwrap_return_value(evaluated)
-        }
-        _ => new_error(format!("not a function: {}", object_type(&function))),
-    }
+fn arity_error(function: &FunctionObject, given: usize, call_site: Span) -> Object {
+    let total = function.parameters.len();
+    let required = function
+        .parameters
+        .iter()
+        .filter(|p| p.default.is_none())
+        .count();
+    let expected = if function.rest.is_some() {
+        format!("at least {}", required)
+    } else if required == total {
+        format!("{}", total)
+    } else {
+        format!("{} to {}", required, total)
+    };
+    call_error(
+        function,
+        call_site,
+        "wrong number of arguments",
+        format!("expected {}, got {}", expected, given),
+    )
+}
+
+// Errors raised while binding arguments name the function being called,
+// when it has a name, and point at the call site.
+fn call_error(function: &FunctionObject, call_site: Span, message: &str, detail: String) -> Object {
+    let target = match &function.name {
+        Some(name) => format!(" in call to {}", name),
+        None => String::new(),
+    };
+    let error = new_error(
+        ErrorKind::Arguments,
+        format!("{}{}: {}", message, target, detail),
+    );
+    locate(error, call_site)
+}
+
+fn array_size(len: usize) -> usize {
+    len * std::mem::size_of::<Object>()
 }
 
-fn extend_function_env(function: &FunctionObject, args: Vec<Object>) -> Env {
-    let env = Environment::new_enclosed(Rc::clone(&function.env));
-    for (param, arg) in function.parameters.iter().zip(args.into_iter()) {
-        env.borrow_mut().set(param.clone(), arg);
+fn function_name(obj: &Object) -> Option<Symbol> {
+    match obj {
+        Object::Function(function) => function.name,
+        Object::Builtin(builtin) => Some(Symbol::intern(builtin.name)),
+        Object::Method(bound) => Some(Symbol::intern(bound.method.name)),
+        Object::Constructor(constructor) => Some(constructor.layout.variant_name(constructor.tag)),
+        Object::BoundFunction(bound) => bound.function.name,
+        Object::Class(class) => Some(class.name),
+        _ => None,
     }
-    env
 }
 
 fn unwrap_return_value(obj: Object) -> Object {
@@ -416,21 +2214,58 @@ fn is_truthy(obj: &Object) -> bool {
     }
 }
 
-fn is_error(obj: &Object) -> bool {
-    matches!(obj, Object::Error(_))
+// Whether evaluating an expression stops with this value rather than
+// going on to use it: errors and interrupts, and the `ReturnValue` of a `?`
+// leaving the function from the middle of an expression.
+fn unwinds(obj: &Object) -> bool {
+    matches!(
+        obj,
+        Object::Error(_) | Object::Interrupt(_) | Object::ReturnValue(_)
+    )
 }
 
 fn object_type(obj: &Object) -> &'static str {
     match obj {
         Object::Integer(_) => "INTEGER",
         Object::Boolean(_) => "BOOLEAN",
+        Object::String(_) => "STRING",
+        Object::Array(_) => "ARRAY",
+        Object::Hash(_) => "HASH",
+        Object::StructType(_) => "STRUCT_TYPE",
+        Object::Struct(_) => "STRUCT",
+        Object::EnumType(_) => "ENUM_TYPE",
+        Object::Variant(_) => "ENUM",
+        Object::Constructor(_) => "CONSTRUCTOR",
+        Object::Class(_) => "CLASS",
+        Object::Instance(_) => "INSTANCE",
+        Object::BoundFunction(_) => "METHOD",
         Object::Null => "NULL",
+        Object::Ok(_) | Object::Err(_) => "RESULT",
         Object::ReturnValue(_) => "RETURN_VALUE",
+        Object::TailCall(_) => "TAIL_CALL",
         Object::Function(_) => "FUNCTION",
+        Object::Builtin(_) => "BUILTIN",
+        Object::Method(_) => "METHOD",
+        Object::Module(_) => "MODULE",
+        Object::Generator(_) => "GENERATOR",
         Object::Error(_) => "ERROR",
+        Object::Exception(_) => "EXCEPTION",
+        Object::Interrupt(_) => "INTERRUPT",
     }
 }
 
-fn new_error(message: String) -> Object {
-    Object::Error(message)
+pub(crate) fn new_error(kind: ErrorKind, message: String) -> Object {
+    Object::Error(Box::new(RuntimeError::new(kind, message)))
+}
+
+// Records where an error was raised, unless an inner expression already
+// did.
+fn locate(obj: Object, span: Span) -> Object {
+    match obj {
+        Object::Error(mut error) => {
+            error.span.get_or_insert(span);
+            Object::Error(error)
+        }
+        obj => obj,
+    }
 }
@@ -0,0 +1,365 @@
+use crate::builtins::wrong_arguments;
+use crate::generators::Resumed;
+use crate::object::{ErrorKind, HashKey, Method, Object};
+use crate::{array_size, new_error, object_type, Evaluator, HASH_ENTRY_SIZE};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::rc::Rc;
+
+// The methods built into strings, arrays, hashes and generators, called as
+// `s.len()`. They take the value they were called on and never change it,
+// methods such as `push` return a new value.
+const STRING_METHODS: &[Method] = &[
+    Method {
+        name: "len",
+        func: string_len,
+    },
+    Method {
+        name: "upper",
+        func: string_upper,
+    },
+    Method {
+        name: "lower",
+        func: string_lower,
+    },
+    Method {
+        name: "trim",
+        func: string_trim,
+    },
+    Method {
+        name: "split",
+        func: string_split,
+    },
+    Method {
+        name: "contains",
+        func: string_contains,
+    },
+];
+
+const ARRAY_METHODS: &[Method] = &[
+    Method {
+        name: "len",
+        func: array_len,
+    },
+    Method {
+        name: "first",
+        func: array_first,
+    },
+    Method {
+        name: "last",
+        func: array_last,
+    },
+    Method {
+        name: "push",
+        func: array_push,
+    },
+    Method {
+        name: "reverse",
+        func: array_reverse,
+    },
+    Method {
+        name: "join",
+        func: array_join,
+    },
+];
+
+const HASH_METHODS: &[Method] = &[
+    Method {
+        name: "len",
+        func: hash_len,
+    },
+    Method {
+        name: "keys",
+        func: hash_keys,
+    },
+    Method {
+        name: "values",
+        func: hash_values,
+    },
+    Method {
+        name: "has",
+        func: hash_has,
+    },
+];
+
+const GENERATOR_METHODS: &[Method] = &[Method {
+    name: "next",
+    func: generator_next,
+}];
+
+pub(crate) fn lookup(receiver: &Object, name: &str) -> Option<Method> {
+    let methods = match receiver {
+        Object::String(_) => STRING_METHODS,
+        Object::Array(_) => ARRAY_METHODS,
+        Object::Hash(_) => HASH_METHODS,
+        Object::Generator(_) => GENERATOR_METHODS,
+        _ => return None,
+    };
+    methods.iter().find(|method| method.name == name).copied()
+}
+
+fn string_len(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("len", 0, args.len());
+    }
+    let Object::String(value) = receiver else {
+        unreachable!("string method called on {}", object_type(&receiver))
+    };
+    Object::Integer(value.chars().count() as i64)
+}
+
+fn string_upper(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    map_string(evaluator, "upper", receiver, args, str::to_uppercase)
+}
+
+fn string_lower(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    map_string(evaluator, "lower", receiver, args, str::to_lowercase)
+}
+
+fn string_trim(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    map_string(evaluator, "trim", receiver, args, |value| {
+        value.trim().to_string()
+    })
+}
+
+fn map_string(
+    evaluator: &mut Evaluator,
+    name: &str,
+    receiver: Object,
+    args: Vec<Object>,
+    map: fn(&str) -> String,
+) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments(name, 0, args.len());
+    }
+    let Object::String(value) = receiver else {
+        unreachable!("string method called on {}", object_type(&receiver))
+    };
+    // Changing the case of a character can make it longer, which is only
+    // known once it has been mapped.
+    if let Some(interrupt) = evaluator.allocate(value.len()) {
+        return interrupt;
+    }
+    let mapped = map(&value);
+    if let Some(interrupt) = evaluator.allocate(mapped.len().saturating_sub(value.len())) {
+        return interrupt;
+    }
+    evaluator.charged(Object::String(mapped.into()))
+}
+
+fn string_split(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    let separator = match string_argument("split", &args) {
+        Ok(separator) => separator,
+        Err(err) => return err,
+    };
+    let Object::String(value) = receiver else {
+        unreachable!("string method called on {}", object_type(&receiver))
+    };
+    let count = value.split(separator.as_ref()).count();
+    if let Some(interrupt) = evaluator.allocate(array_size(count) + value.len()) {
+        return interrupt;
+    }
+    let parts: Vec<Object> = value
+        .split(separator.as_ref())
+        .map(|part| evaluator.charged(Object::String(part.into())))
+        .collect();
+    evaluator.charged(Object::Array(Rc::new(parts)))
+}
+
+fn string_contains(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    let needle = match string_argument("contains", &args) {
+        Ok(needle) => needle,
+        Err(err) => return err,
+    };
+    let Object::String(value) = receiver else {
+        unreachable!("string method called on {}", object_type(&receiver))
+    };
+    Object::Boolean(value.contains(needle.as_ref()))
+}
+
+fn array_len(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("len", 0, args.len());
+    }
+    let Object::Array(elements) = receiver else {
+        unreachable!("array method called on {}", object_type(&receiver))
+    };
+    Object::Integer(elements.len() as i64)
+}
+
+// first() and last() are null for an empty array.
+fn array_first(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("first", 0, args.len());
+    }
+    let Object::Array(elements) = receiver else {
+        unreachable!("array method called on {}", object_type(&receiver))
+    };
+    elements.first().cloned().unwrap_or(Object::Null)
+}
+
+fn array_last(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("last", 0, args.len());
+    }
+    let Object::Array(elements) = receiver else {
+        unreachable!("array method called on {}", object_type(&receiver))
+    };
+    elements.last().cloned().unwrap_or(Object::Null)
+}
+
+fn array_push(evaluator: &mut Evaluator, receiver: Object, mut args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments("push", 1, args.len());
+    }
+    let Object::Array(elements) = receiver else {
+        unreachable!("array method called on {}", object_type(&receiver))
+    };
+    if let Some(interrupt) = evaluator.allocate(array_size(elements.len() + 1)) {
+        return interrupt;
+    }
+    let mut elements = elements.as_ref().clone();
+    elements.push(args.remove(0));
+    evaluator.charged(Object::Array(Rc::new(elements)))
+}
+
+fn array_reverse(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("reverse", 0, args.len());
+    }
+    let Object::Array(elements) = receiver else {
+        unreachable!("array method called on {}", object_type(&receiver))
+    };
+    if let Some(interrupt) = evaluator.allocate(array_size(elements.len())) {
+        return interrupt;
+    }
+    evaluator.charged(Object::Array(Rc::new(
+        elements.iter().rev().cloned().collect(),
+    )))
+}
+
+fn array_join(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    let separator = match string_argument("join", &args) {
+        Ok(separator) => separator,
+        Err(err) => return err,
+    };
+    let Object::Array(elements) = receiver else {
+        unreachable!("array method called on {}", object_type(&receiver))
+    };
+    let separators = separator.len() * elements.len().saturating_sub(1);
+    let len = elements.iter().map(display_len).sum::<usize>() + separators;
+    if let Some(interrupt) = evaluator.allocate(len) {
+        return interrupt;
+    }
+    let parts: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+    evaluator.charged(Object::String(parts.join(&separator).into()))
+}
+
+fn hash_len(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("len", 0, args.len());
+    }
+    let Object::Hash(pairs) = receiver else {
+        unreachable!("hash method called on {}", object_type(&receiver))
+    };
+    Object::Integer(pairs.len() as i64)
+}
+
+fn hash_keys(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("keys", 0, args.len());
+    }
+    let Object::Hash(pairs) = receiver else {
+        unreachable!("hash method called on {}", object_type(&receiver))
+    };
+    if let Some(interrupt) = evaluator.allocate(array_size(pairs.len())) {
+        return interrupt;
+    }
+    evaluator.charged(Object::Array(Rc::new(
+        pairs.keys().map(HashKey::to_object).collect(),
+    )))
+}
+
+fn hash_values(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("values", 0, args.len());
+    }
+    let Object::Hash(pairs) = receiver else {
+        unreachable!("hash method called on {}", object_type(&receiver))
+    };
+    if let Some(interrupt) = evaluator.allocate(array_size(pairs.len())) {
+        return interrupt;
+    }
+    evaluator.charged(Object::Array(Rc::new(pairs.values().cloned().collect())))
+}
+
+fn hash_has(_: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments("has", 1, args.len());
+    }
+    let Object::Hash(pairs) = receiver else {
+        unreachable!("hash method called on {}", object_type(&receiver))
+    };
+    match HashKey::from_object(&args[0]) {
+        Some(key) => Object::Boolean(pairs.contains_key(&key)),
+        None => new_error(
+            ErrorKind::Index,
+            format!("unusable as hash key: {}", object_type(&args[0])),
+        ),
+    }
+}
+
+// The single string argument of methods such as `split`.
+fn string_argument(name: &str, args: &[Object]) -> Result<Rc<str>, Object> {
+    match args {
+        [Object::String(value)] => Ok(Rc::clone(value)),
+        [other] => Err(new_error(
+            ErrorKind::TypeMismatch,
+            format!(
+                "argument to {} must be STRING, got {}",
+                name,
+                object_type(other)
+            ),
+        )),
+        _ => Err(wrong_arguments(name, 1, args.len())),
+    }
+}
+
+// Runs the generator on, and returns `{"done": false, "value": v}` for a
+// value it yields, or `{"done": true, "value": v}` once it has returned v.
+// A `yield null` and the end of the generator can be told apart this way.
+fn generator_next(evaluator: &mut Evaluator, receiver: Object, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return wrong_arguments("next", 0, args.len());
+    }
+    let Object::Generator(generator) = receiver else {
+        unreachable!("generator method called on {}", object_type(&receiver))
+    };
+    let (done, value) = match evaluator.resume(&generator) {
+        Ok(Resumed::Yielded(value)) => (false, value),
+        Ok(Resumed::Returned(value)) => (true, value),
+        Err(err) => return err,
+    };
+    if let Some(interrupt) = evaluator.allocate(2 * HASH_ENTRY_SIZE) {
+        return interrupt;
+    }
+    let pairs = BTreeMap::from([
+        (HashKey::String("done".into()), Object::Boolean(done)),
+        (HashKey::String("value".into()), value),
+    ]);
+    evaluator.charged(Object::Hash(Rc::new(pairs)))
+}
+
+// The length of a value once displayed, found without building the string.
+fn display_len(value: &Object) -> usize {
+    struct Counter(usize);
+    impl fmt::Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    let _ = write!(counter, "{}", value);
+    counter.0
+}
@@ -0,0 +1,201 @@
+use crate::environment::Environment;
+use crate::object::{ErrorKind, Module, Object};
+use crate::{new_error, resolver, Evaluator};
+use lexer::Lexer;
+use parser::Parser;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+// Names a module, such as its canonical path or the key it is stored under.
+// Imports that resolve to the same id share one module.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModuleId(String);
+
+impl ModuleId {
+    pub fn new(id: impl Into<String>) -> ModuleId {
+        ModuleId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for ModuleId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    NotFound,
+    // Anything else, such as an I/O error.
+    Other(String),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            LoadError::NotFound => write!(f, "module not found"),
+            LoadError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        match err.kind() {
+            io::ErrorKind::NotFound => LoadError::NotFound,
+            _ => LoadError::Other(err.to_string()),
+        }
+    }
+}
+
+// Where the source of imported modules comes from. `spec` is the string
+// written in the `import` and `from` the module doing the import, `None`
+// for a program the host evaluates without giving it an id.
+pub trait ModuleLoader {
+    // The id of the module `spec` refers to. The default takes `spec` as
+    // it is, for loaders with a flat namespace.
+    fn resolve(&self, spec: &str, from: Option<&ModuleId>) -> Result<ModuleId, LoadError> {
+        let _ = from;
+        Ok(ModuleId::new(spec))
+    }
+
+    fn load(&self, spec: &str, from: Option<&ModuleId>) -> Result<String, LoadError>;
+}
+
+// Loads modules from files, resolving paths relative to the importing file
+// or to the working directory. Ids are canonical paths.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileLoader;
+
+impl ModuleLoader for FileLoader {
+    fn resolve(&self, spec: &str, from: Option<&ModuleId>) -> Result<ModuleId, LoadError> {
+        let base = match from {
+            Some(from) => Path::new(from.as_str()).parent().unwrap_or(Path::new("")),
+            None => Path::new(""),
+        };
+        let path = fs::canonicalize(base.join(spec))?;
+        Ok(ModuleId::new(path.to_string_lossy()))
+    }
+
+    fn load(&self, spec: &str, from: Option<&ModuleId>) -> Result<String, LoadError> {
+        let id = self.resolve(spec, from)?;
+        Ok(fs::read_to_string(id.as_str())?)
+    }
+}
+
+// Serves modules from memory, keyed by the exact string they are imported
+// by. Meant for tests and hosts that keep their scripts elsewhere.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> MemoryLoader {
+        MemoryLoader::default()
+    }
+
+    pub fn insert(&mut self, spec: impl Into<String>, source: impl Into<String>) {
+        self.modules.insert(spec.into(), source.into());
+    }
+}
+
+impl From<HashMap<String, String>> for MemoryLoader {
+    fn from(modules: HashMap<String, String>) -> MemoryLoader {
+        MemoryLoader { modules }
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn resolve(&self, spec: &str, _from: Option<&ModuleId>) -> Result<ModuleId, LoadError> {
+        match self.modules.contains_key(spec) {
+            true => Ok(ModuleId::new(spec)),
+            false => Err(LoadError::NotFound),
+        }
+    }
+
+    fn load(&self, spec: &str, _from: Option<&ModuleId>) -> Result<String, LoadError> {
+        self.modules.get(spec).cloned().ok_or(LoadError::NotFound)
+    }
+}
+
+impl Evaluator {
+    // Starts the stack of modules being evaluated with the host's program.
+    pub(crate) fn begin_modules(&mut self) {
+        self.loading.clear();
+        if let Some(id) = &self.module_id {
+            self.loading.push((id.clone(), id.to_string()));
+        }
+    }
+
+    // A module is evaluated the first time it is imported, in an
+    // environment of its own. Later imports of the same module share it.
+    pub(crate) fn import(&mut self, spec: &str) -> Result<Rc<Module>, Object> {
+        let from = self.loading.last().map(|(id, _)| id.clone());
+        let id = self
+            .loader
+            .resolve(spec, from.as_ref())
+            .map_err(|err| import_error(format!("cannot import {}: {}", spec, err)))?;
+        if let Some(module) = self.modules.get(&id) {
+            return Ok(Rc::clone(module));
+        }
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == id) {
+            let mut cycle: Vec<&str> = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            cycle.push(spec);
+            return Err(import_error(format!(
+                "import cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        let source = self
+            .loader
+            .load(spec, from.as_ref())
+            .map_err(|err| import_error(format!("cannot import {}: {}", spec, err)))?;
+        let mut parser = Parser::new(Lexer::new(source.into_bytes()));
+        let program = parser.parse();
+        if let Some(error) = parser.errors.first() {
+            return Err(import_error(format!("cannot import {}: {}", spec, error)));
+        }
+        let env = Environment::new();
+        self.heap.track(&env);
+        if let Err(message) = resolver::resolve(&program, &env) {
+            return Err(new_error(ErrorKind::UndefinedVariable, message));
+        }
+
+        self.loading.push((id.clone(), spec.to_string()));
+        let result = self.eval_program(&program, env.clone());
+        self.loading.pop();
+        if matches!(result, Object::Error(_) | Object::Interrupt(_)) {
+            return Err(result);
+        }
+
+        let mut exports = BTreeMap::new();
+        for name in resolver::exports(&program) {
+            if let Some(value) = env.borrow().get(name) {
+                exports.insert(name, value);
+            }
+        }
+        let module = Rc::new(Module {
+            name: spec.to_string(),
+            exports,
+        });
+        self.modules.insert(id, Rc::clone(&module));
+        Ok(module)
+    }
+}
+
+fn import_error(message: String) -> Object {
+    new_error(ErrorKind::Import, message)
+}
@@ -0,0 +1,432 @@
+use crate::environment::Env;
+use crate::generators::Generator;
+use crate::{object_type, CallFrame, Evaluator};
+use ast::expressions::Slot;
+use ast::{patterns, statements};
+use lexer::{Span, Symbol};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct FunctionObject {
+    pub name: Option<Symbol>,
+    pub parameters: Rc<Vec<patterns::Parameter>>,
+    pub rest: Option<Symbol>,
+    pub rest_slot: Option<Slot>,
+    pub body: Rc<statements::BlockStatement>,
+    pub env: Env,
+    // The size of the frame each call gets, as counted by the resolver.
+    pub locals: usize,
+    // Declared with `fn*`, a call returns a generator instead of running
+    // the body.
+    pub generator: bool,
+}
+
+// A module loaded by `import`. Its exports are the values its top-level
+// `export let` statements bound, read once evaluation finished.
+#[derive(Debug)]
+pub struct Module {
+    // The path it was first imported by.
+    pub name: String,
+    pub exports: BTreeMap<Symbol, Object>,
+}
+
+// The fields a struct type declares, in order. Every value of the type
+// stores its fields in this order.
+#[derive(Debug)]
+pub struct StructLayout {
+    pub name: Symbol,
+    pub fields: Vec<Symbol>,
+}
+
+impl StructLayout {
+    pub fn index(&self, field: Symbol) -> Option<usize> {
+        self.fields.iter().position(|name| *name == field)
+    }
+}
+
+#[derive(Debug)]
+pub struct StructObject {
+    pub layout: Rc<StructLayout>,
+    pub values: Vec<Object>,
+}
+
+// A class declared with `class`. Its methods are closures over the
+// environment the class was declared in.
+#[derive(Debug)]
+pub struct Class {
+    pub name: Symbol,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<Symbol, FunctionObject>,
+}
+
+impl Class {
+    // The method with this name and the class that declares it, looking
+    // through the superclasses.
+    pub fn find_method(class: &Rc<Class>, name: Symbol) -> Option<(&Rc<Class>, &FunctionObject)> {
+        let mut class = class;
+        loop {
+            if let Some(method) = class.methods.get(&name) {
+                return Some((class, method));
+            }
+            class = class.superclass.as_ref()?;
+        }
+    }
+}
+
+// Unlike every other value, the fields of an instance can be changed in
+// place, with `self.x = 1`, and everything holding the instance sees it.
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<BTreeMap<Symbol, Object>>,
+}
+
+// A method looked up on an instance, as `p.area` is. The function is a
+// closure over a frame binding `self` to the instance, and `super` to the
+// superclass of the class declaring the method.
+#[derive(Debug, Clone)]
+pub struct BoundFunction {
+    pub receiver: Rc<Instance>,
+    pub function: FunctionObject,
+}
+
+// The variants an enum type declares, each with the names of its fields.
+// A variant is identified by its index, its tag.
+#[derive(Debug)]
+pub struct EnumLayout {
+    pub name: Symbol,
+    pub variants: Vec<(Symbol, Vec<Symbol>)>,
+}
+
+impl EnumLayout {
+    pub fn tag(&self, variant: Symbol) -> Option<usize> {
+        self.variants.iter().position(|(name, _)| *name == variant)
+    }
+
+    pub fn variant_name(&self, tag: usize) -> Symbol {
+        self.variants[tag].0
+    }
+}
+
+#[derive(Debug)]
+pub struct VariantObject {
+    pub layout: Rc<EnumLayout>,
+    pub tag: usize,
+    pub values: Vec<Object>,
+}
+
+// A variant with fields, as `Shape.Circle` is before it is called.
+#[derive(Debug, Clone)]
+pub struct Constructor {
+    pub layout: Rc<EnumLayout>,
+    pub tag: usize,
+}
+
+// A function implemented by the host, such as `gc`.
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: fn(&mut Evaluator, Vec<Object>) -> Object,
+}
+
+// A method built into strings, arrays or hashes, such as `len`.
+#[derive(Debug, Clone, Copy)]
+pub struct Method {
+    pub name: &'static str,
+    pub func: fn(&mut Evaluator, Object, Vec<Object>) -> Object,
+}
+
+// A method together with the value it was looked up on, as `s.len` is.
+#[derive(Debug, Clone)]
+pub struct BoundMethod {
+    pub receiver: Object,
+    pub method: Method,
+}
+
+// A call in tail position that has been evaluated up to, but not including,
+// applying the function. It is handed back to `apply_function` so that tail
+// recursion runs in a loop instead of growing the host stack.
+#[derive(Debug, Clone)]
+pub struct TailCall {
+    pub function: Object,
+    pub args: Vec<Object>,
+    pub named_args: Vec<(Symbol, Object)>,
+    pub call_site: Span,
+}
+
+// Why the host stopped an evaluation. Unlike `Object::Error` these are not
+// raised by the script itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Interrupt {
+    OutOfFuel,
+    Cancelled,
+    DeadlineExceeded,
+    MemoryLimitExceeded,
+}
+
+impl Display for Interrupt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Interrupt::OutOfFuel => write!(f, "out of fuel"),
+            Interrupt::Cancelled => write!(f, "cancelled"),
+            Interrupt::DeadlineExceeded => write!(f, "deadline exceeded"),
+            Interrupt::MemoryLimitExceeded => write!(f, "memory limit exceeded"),
+        }
+    }
+}
+
+// The category of a runtime error, so hosts can tell errors apart without
+// parsing their messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    UndefinedVariable,
+    TypeMismatch,
+    UnknownOperator,
+    NotCallable,
+    // Arguments that do not fit the parameters of the function called.
+    Arguments,
+    Index,
+    Destructure,
+    RecursionLimit,
+    // The program is incomplete, as left by a parser that reported errors.
+    Malformed,
+    // Raised by the script with `throw`.
+    Thrown,
+    // A module that could not be read, parsed or evaluated, or an import
+    // cycle.
+    Import,
+    // A member that does not exist, such as a name a module does not export.
+    Member,
+    // A value that no arm of a `match` matches.
+    Match,
+    // Division by zero, or a result that does not fit in an integer.
+    Arithmetic,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ErrorKind::UndefinedVariable => write!(f, "undefined variable"),
+            ErrorKind::TypeMismatch => write!(f, "type mismatch"),
+            ErrorKind::UnknownOperator => write!(f, "unknown operator"),
+            ErrorKind::NotCallable => write!(f, "not callable"),
+            ErrorKind::Arguments => write!(f, "bad arguments"),
+            ErrorKind::Index => write!(f, "bad index"),
+            ErrorKind::Destructure => write!(f, "cannot destructure"),
+            ErrorKind::RecursionLimit => write!(f, "recursion limit"),
+            ErrorKind::Malformed => write!(f, "malformed program"),
+            ErrorKind::Thrown => write!(f, "thrown"),
+            ErrorKind::Import => write!(f, "import failed"),
+            ErrorKind::Member => write!(f, "no such member"),
+            ErrorKind::Match => write!(f, "no match"),
+            ErrorKind::Arithmetic => write!(f, "arithmetic error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    // Where the error was raised, when known.
+    pub span: Option<Span>,
+    // The calls the error propagated out of, innermost first.
+    pub trace: Vec<CallFrame>,
+    // What the script threw, for errors raised with `throw`.
+    pub value: Option<Object>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, message: String) -> RuntimeError {
+        RuntimeError {
+            kind,
+            message,
+            span: None,
+            trace: Vec::new(),
+            value: None,
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+impl HashKey {
+    pub(crate) fn from_object(obj: &Object) -> Option<HashKey> {
+        match obj {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(Rc::clone(value))),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_object(&self) -> Object {
+        match self {
+            HashKey::Integer(value) => Object::Integer(*value),
+            HashKey::Boolean(value) => Object::Boolean(*value),
+            HashKey::String(value) => Object::String(Rc::clone(value)),
+        }
+    }
+}
+
+impl Display for HashKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            HashKey::Integer(value) => write!(f, "{}", value),
+            HashKey::Boolean(value) => write!(f, "{}", value),
+            HashKey::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+// Strings, arrays and hashes are immutable and live behind an `Rc`, so
+// cloning an object is cheap whatever its size. Assigning an array to a
+// variable or passing it to a function shares it rather than copying it,
+// but as nothing can change it in place this cannot be told apart from a
+// copy: they have value semantics. Building a new value, such as with
+// `s + t` or `[xs, 1]`, allocates.
+#[derive(Debug, Clone)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    // A struct type declared with `struct`, and a value of one.
+    StructType(Rc<StructLayout>),
+    Struct(Rc<StructObject>),
+    // An enum type declared with `enum`, one of its variants, and the
+    // function that builds a variant with fields.
+    EnumType(Rc<EnumLayout>),
+    Variant(Rc<VariantObject>),
+    Constructor(Constructor),
+    // A class declared with `class`, an instance of one, and one of its
+    // methods bound to an instance.
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    BoundFunction(Box<BoundFunction>),
+    Null,
+    // Results made by `ok(v)` and `err(e)`, unwrapped by `?`.
+    Ok(Box<Object>),
+    Err(Box<Object>),
+    ReturnValue(Box<Object>),
+    TailCall(Box<TailCall>),
+    Function(FunctionObject),
+    Builtin(Builtin),
+    Method(Box<BoundMethod>),
+    Module(Rc<Module>),
+    Generator(Rc<Generator>),
+    Error(Box<RuntimeError>),
+    // An error caught by `catch`, as a value the script can inspect or
+    // throw again.
+    Exception(Rc<RuntimeError>),
+    Interrupt(Interrupt),
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::StructType(layout) => write!(f, "struct {}", layout.name),
+            Object::Struct(value) => {
+                let fields: Vec<String> = value
+                    .layout
+                    .fields
+                    .iter()
+                    .zip(value.values.iter())
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                match fields.is_empty() {
+                    true => write!(f, "{} {{}}", value.layout.name),
+                    false => write!(f, "{} {{ {} }}", value.layout.name, fields.join(", ")),
+                }
+            }
+            Object::EnumType(layout) => write!(f, "enum {}", layout.name),
+            Object::Variant(value) => {
+                let name = value.layout.variant_name(value.tag);
+                match value.values.is_empty() {
+                    true => write!(f, "{}.{}", value.layout.name, name),
+                    false => {
+                        let values: Vec<String> =
+                            value.values.iter().map(|v| v.to_string()).collect();
+                        write!(f, "{}.{}({})", value.layout.name, name, values.join(", "))
+                    }
+                }
+            }
+            Object::Constructor(constructor) => write!(
+                f,
+                "constructor {}.{}",
+                constructor.layout.name,
+                constructor.layout.variant_name(constructor.tag)
+            ),
+            Object::Class(class) => write!(f, "class {}", class.name),
+            Object::Instance(instance) => write!(f, "instance of {}", instance.class.name),
+            Object::BoundFunction(bound) => write!(
+                f,
+                "method {} of {}",
+                bound
+                    .function
+                    .name
+                    .map(|name| name.to_string())
+                    .unwrap_or_default(),
+                bound.receiver.class.name
+            ),
+            Object::Null => write!(f, "null"),
+            Object::Ok(value) => write!(f, "ok({})", value),
+            Object::Err(value) => write!(f, "err({})", value),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::TailCall(call) => write!(f, "tail call to {}", call.function),
+            Object::Function(function) => {
+                let mut params: Vec<String> =
+                    function.parameters.iter().map(|p| p.to_string()).collect();
+                if let Some(rest) = &function.rest {
+                    params.push(format!("...{}", rest));
+                }
+                let keyword = if function.generator { "fn*" } else { "fn" };
+                write!(f, "{}({}) {{ ... }}", keyword, params.join(", "))
+            }
+            Object::Builtin(builtin) => write!(f, "builtin {}", builtin.name),
+            Object::Method(bound) => write!(
+                f,
+                "method {} of {}",
+                bound.method.name,
+                object_type(&bound.receiver)
+            ),
+            Object::Module(module) => write!(f, "module {}", module.name),
+            Object::Generator(generator) => match generator.name {
+                Some(name) => write!(f, "generator {}", name),
+                None => write!(f, "generator"),
+            },
+            Object::Error(error) => write!(f, "ERROR: {}", error),
+            Object::Exception(error) => write!(f, "error: {}", error),
+            Object::Interrupt(interrupt) => write!(f, "INTERRUPTED: {}", interrupt),
+        }
+    }
+}
@@ -0,0 +1,710 @@
+use crate::builtins;
+use crate::environment::Env;
+use ast::ast::{Expression, Statement};
+use ast::expressions::Slot;
+use ast::{expressions, patterns, statements};
+use lexer::Symbol;
+use std::collections::{HashMap, HashSet};
+
+// Assigns every variable inside a function a slot in that function's
+// frame, and checks that every variable used is bound somewhere.
+//
+// As blocks do not introduce scopes, a function's locals are its
+// parameters and every name it binds with `let`, wherever that is in its
+// body. They are given slots up front, so a closure can refer to a local
+// that is only bound after the closure is created. The function itself
+// only sees a local from where it is bound, so the `x` read in
+// `let x = x + 1` is an outer `x`. Names bound at the top level of the
+// program are globals and stay in the host's environment.
+//
+// The exception a `catch` binds, and the names the pattern of a `match` arm
+// binds, are the exceptions: they are only in scope in the handler block or
+// the arm, which runs in a frame of its own that holds them.
+//
+// The methods of a class are closures over a frame of their own, where
+// `self` and, in a subclass, `super` are bound. It sits between the method
+// and the scope the class is declared in.
+//
+// A generator can only be resumed from a `yield` that is a statement of
+// its body, or of the `if` and `for` statements in it, so one anywhere
+// else is an error.
+//
+// It also warns about a `match` that leaves out variants of an enum
+// declared in the program.
+pub(crate) struct Resolver<'a> {
+    env: &'a Env,
+    globals: HashSet<Symbol>,
+    // The locals of each enclosing function, innermost last.
+    scopes: Vec<HashMap<Symbol, usize>>,
+    // For each scope that is a function's, the locals that have not been
+    // bound yet where the resolver is. `None` for the other frames.
+    unbound: Vec<Option<HashSet<Symbol>>>,
+    // The variants of the enums declared so far, and all those declared
+    // at the top level.
+    enums: HashMap<Symbol, Vec<Symbol>>,
+    error: Option<String>,
+    warnings: Vec<String>,
+    // Whether the innermost function is a generator, and whether a `yield`
+    // where the resolver is could be resumed from.
+    generator: bool,
+    resumable: bool,
+}
+
+pub(crate) fn resolve(
+    program: &statements::ProgramStatement,
+    env: &Env,
+) -> Result<Vec<String>, String> {
+    let mut globals = Vec::new();
+    let mut enums = HashMap::new();
+    for statement in program.body.iter() {
+        declare_statement(statement.as_ref(), &mut globals);
+        if let Some(enum_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::EnumStatement>()
+        {
+            enums.insert(enum_stmt.name.value, variant_names(enum_stmt));
+        }
+    }
+    let mut resolver = Resolver {
+        env,
+        globals: globals.into_iter().collect(),
+        scopes: Vec::new(),
+        unbound: Vec::new(),
+        enums,
+        error: None,
+        warnings: Vec::new(),
+        generator: false,
+        resumable: false,
+    };
+    for statement in program.body.iter() {
+        resolver.statement(statement.as_ref());
+    }
+    match resolver.error {
+        Some(error) => Err(error),
+        None => Ok(resolver.warnings),
+    }
+}
+
+fn variant_names(enum_stmt: &statements::EnumStatement) -> Vec<Symbol> {
+    enum_stmt
+        .variants
+        .iter()
+        .map(|variant| variant.name.value)
+        .collect()
+}
+
+// The names bound by the exported `let`, `struct`, `enum` and `class`
+// statements of a module. They can only appear at its top level.
+pub(crate) fn exports(program: &statements::ProgramStatement) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    for statement in program.body.iter() {
+        let any = statement.as_any();
+        if let Some(let_stmt) = any.downcast_ref::<statements::LetStatement>() {
+            if let_stmt.exported {
+                declare_pattern(&let_stmt.pattern, &mut names);
+            }
+        } else if let Some(struct_stmt) = any.downcast_ref::<statements::StructStatement>() {
+            if struct_stmt.exported {
+                declare(struct_stmt.name.value, &mut names);
+            }
+        } else if let Some(enum_stmt) = any.downcast_ref::<statements::EnumStatement>() {
+            if enum_stmt.exported {
+                declare(enum_stmt.name.value, &mut names);
+            }
+        } else if let Some(class_stmt) = any.downcast_ref::<statements::ClassStatement>() {
+            if class_stmt.exported {
+                declare(class_stmt.name.value, &mut names);
+            }
+        }
+    }
+    names
+}
+
+fn is_exported(statement: &dyn Statement) -> bool {
+    let any = statement.as_any();
+    if let Some(let_stmt) = any.downcast_ref::<statements::LetStatement>() {
+        return let_stmt.exported;
+    }
+    if let Some(struct_stmt) = any.downcast_ref::<statements::StructStatement>() {
+        return struct_stmt.exported;
+    }
+    if let Some(enum_stmt) = any.downcast_ref::<statements::EnumStatement>() {
+        return enum_stmt.exported;
+    }
+    if let Some(class_stmt) = any.downcast_ref::<statements::ClassStatement>() {
+        return class_stmt.exported;
+    }
+    false
+}
+
+impl Resolver<'_> {
+    fn lookup(&mut self, identifier: &expressions::Identifier) {
+        let mut nested = false;
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            let unbound = &self.unbound[self.scopes.len() - 1 - depth];
+            if let Some(index) = scope.get(&identifier.value) {
+                let later = unbound
+                    .as_ref()
+                    .is_some_and(|names| names.contains(&identifier.value));
+                if nested || !later {
+                    identifier.slot.set(Some(Slot {
+                        depth,
+                        index: *index,
+                    }));
+                    return;
+                }
+            }
+            nested |= unbound.is_some();
+        }
+
+        identifier.slot.set(None);
+        let name = identifier.value;
+        let defined = self.globals.contains(&name)
+            || self.env.borrow().contains(name)
+            || builtins::lookup(name.as_str()).is_some();
+        if !defined && self.error.is_none() {
+            self.error = Some(format!("identifier not found: {}", name));
+        }
+    }
+
+    // Every name is bound in the innermost function's frame, or a global,
+    // except inside a frame that holds the name itself.
+    fn bind(&mut self, identifier: &expressions::Identifier) {
+        let slot = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                let index = *scope.get(&identifier.value)?;
+                Some(Slot { depth, index })
+            });
+        if let Some(slot) = slot {
+            let scope = self.scopes.len() - 1 - slot.depth;
+            if let Some(names) = &mut self.unbound[scope] {
+                names.remove(&identifier.value);
+            }
+        }
+        identifier.slot.set(slot);
+    }
+
+    fn push_scope(&mut self, scope: HashMap<Symbol, usize>, function: bool) {
+        let unbound = function.then(|| scope.keys().copied().collect());
+        self.scopes.push(scope);
+        self.unbound.push(unbound);
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.unbound.pop();
+    }
+
+    fn pattern(&mut self, pattern: &patterns::Pattern) {
+        match pattern {
+            patterns::Pattern::Identifier(identifier) => self.bind(identifier),
+            patterns::Pattern::Array(array) => {
+                for element in array.elements.iter() {
+                    self.pattern(element);
+                }
+                if let Some(rest) = &array.rest {
+                    self.bind(rest);
+                }
+            }
+            patterns::Pattern::Hash(hash) => {
+                for entry in hash.entries.iter() {
+                    self.pattern(&entry.pattern);
+                }
+            }
+        }
+    }
+
+    fn match_pattern(&mut self, pattern: &patterns::MatchPattern) {
+        match pattern {
+            patterns::MatchPattern::Binding(identifier) => self.bind(identifier),
+            patterns::MatchPattern::Variant(variant) => {
+                self.lookup(&variant.enum_name);
+                for field in variant.fields.iter() {
+                    self.match_pattern(field);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Only a `match` whose arms all name variants of the same enum is
+    // checked, and a variant only counts as covered by an arm whose field
+    // patterns match anything.
+    fn exhaustive(&mut self, match_expr: &expressions::Match) {
+        let mut enum_name = None;
+        let mut covered = HashSet::new();
+        for arm in match_expr.arms.iter() {
+            let patterns::MatchPattern::Variant(variant) = &arm.pattern else {
+                return;
+            };
+            if *enum_name.get_or_insert(variant.enum_name.value) != variant.enum_name.value {
+                return;
+            }
+            if variant
+                .fields
+                .iter()
+                .all(patterns::MatchPattern::is_irrefutable)
+            {
+                covered.insert(variant.variant.value);
+            }
+        }
+        let Some(name) = enum_name else {
+            return;
+        };
+        let Some(variants) = self.enums.get(&name) else {
+            return;
+        };
+        let missing: Vec<String> = variants
+            .iter()
+            .filter(|variant| !covered.contains(*variant))
+            .map(|variant| format!("{}.{}", name, variant))
+            .collect();
+        if !missing.is_empty() {
+            self.warnings.push(format!(
+                "match at {} does not cover {}",
+                match_expr.token.span,
+                missing.join(", ")
+            ));
+        }
+    }
+
+    fn block(&mut self, block: &statements::BlockStatement) {
+        for statement in block.statements.iter().flatten() {
+            if is_exported(statement.as_ref()) && self.error.is_none() {
+                self.error = Some("export is only allowed at the top level".to_string());
+            }
+            self.statement(statement.as_ref());
+        }
+    }
+
+    fn statement(&mut self, statement: &dyn Statement) {
+        if let Some(let_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::LetStatement>()
+        {
+            self.optional(&let_stmt.value);
+            self.pattern(&let_stmt.pattern);
+        } else if let Some(return_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ReturnStatement>()
+        {
+            self.optional(&return_stmt.return_value);
+        } else if let Some(throw_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ThrowStatement>()
+        {
+            self.optional(&throw_stmt.value);
+        } else if let Some(import) = statement
+            .as_any()
+            .downcast_ref::<statements::ImportStatement>()
+        {
+            self.bind(&import.alias);
+        } else if let Some(struct_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::StructStatement>()
+        {
+            self.bind(&struct_stmt.name);
+        } else if let Some(enum_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::EnumStatement>()
+        {
+            self.bind(&enum_stmt.name);
+            self.enums
+                .insert(enum_stmt.name.value, variant_names(enum_stmt));
+        } else if let Some(class_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ClassStatement>()
+        {
+            if let Some(superclass) = &class_stmt.superclass {
+                self.lookup(superclass);
+            }
+            self.bind(&class_stmt.name);
+            for method in class_stmt.methods.iter() {
+                let mut scope = HashMap::from([(Symbol::intern("self"), 0)]);
+                if class_stmt.superclass.is_some() {
+                    scope.insert(Symbol::intern("super"), 1);
+                }
+                self.push_scope(scope, false);
+                self.function(&method.function);
+                self.pop_scope();
+            }
+        } else if let Some(assign) = statement
+            .as_any()
+            .downcast_ref::<statements::AssignStatement>()
+        {
+            self.optional(&assign.object);
+            self.optional(&assign.value);
+        } else if let Some(for_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ForStatement>()
+        {
+            self.optional(&for_stmt.iterable);
+            self.pattern(&for_stmt.pattern);
+            self.block(&for_stmt.body);
+        } else if let Some(yield_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::YieldStatement>()
+        {
+            let error = if !self.generator {
+                Some("yield is only allowed in a generator function")
+            } else if !self.resumable {
+                Some("yield cannot be used inside an expression or a try block")
+            } else {
+                None
+            };
+            if let (Some(error), None) = (error, &self.error) {
+                self.error = Some(error.to_string());
+            }
+            self.optional(&yield_stmt.value);
+        } else if let Some(expr_stmt) = statement
+            .as_any()
+            .downcast_ref::<statements::ExpressionStatement>()
+        {
+            // The blocks of an `if` statement keep a generator resumable,
+            // unlike those of an `if` inside an expression.
+            let if_expr = expr_stmt
+                .expression
+                .as_ref()
+                .and_then(|expr| expr.as_any().downcast_ref::<expressions::If>());
+            match if_expr {
+                Some(if_expr) => self.if_blocks(if_expr),
+                None => self.optional(&expr_stmt.expression),
+            }
+        }
+    }
+
+    fn if_blocks(&mut self, if_expr: &expressions::If) {
+        self.optional(&if_expr.condition);
+        if let Some(block) = &if_expr.first {
+            self.block(block);
+        }
+        if let Some(block) = &if_expr.second {
+            self.block(block);
+        }
+    }
+
+    fn optional(&mut self, expression: &Option<Box<dyn Expression>>) {
+        if let Some(expression) = expression {
+            self.expression(expression.as_ref());
+        }
+    }
+
+    fn expression(&mut self, expression: &dyn Expression) {
+        let any = expression.as_any();
+        if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+            self.lookup(identifier);
+        } else if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+            self.optional(&prefix.right);
+        } else if let Some(propagate) = any.downcast_ref::<expressions::Propagate>() {
+            self.optional(&propagate.value);
+        } else if let Some(member) = any.downcast_ref::<expressions::Member>() {
+            self.optional(&member.object);
+        } else if let Some(super_expr) = any.downcast_ref::<expressions::Super>() {
+            let name = super_expr.class.value;
+            if !self.scopes.iter().any(|scope| scope.contains_key(&name)) {
+                if self.error.is_none() {
+                    self.error = Some(
+                        "super can only be used in a method of a class that extends another"
+                            .to_string(),
+                    );
+                }
+                return;
+            }
+            self.lookup(&super_expr.class);
+            self.lookup(&super_expr.receiver);
+        } else if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+            self.optional(&infix.left);
+            self.optional(&infix.right);
+        } else if let Some(if_expr) = any.downcast_ref::<expressions::If>() {
+            let resumable = std::mem::replace(&mut self.resumable, false);
+            self.if_blocks(if_expr);
+            self.resumable = resumable;
+        } else if let Some(try_expr) = any.downcast_ref::<expressions::Try>() {
+            let resumable = std::mem::replace(&mut self.resumable, false);
+            if let Some(block) = &try_expr.body {
+                self.block(block);
+            }
+            if let (Some(parameter), Some(block)) = (&try_expr.parameter, &try_expr.handler) {
+                self.push_scope(HashMap::from([(parameter.value, 0)]), false);
+                self.bind(parameter);
+                self.block(block);
+                self.pop_scope();
+            }
+            if let Some(block) = &try_expr.finalizer {
+                self.block(block);
+            }
+            self.resumable = resumable;
+        } else if let Some(match_expr) = any.downcast_ref::<expressions::Match>() {
+            self.optional(&match_expr.subject);
+            for arm in match_expr.arms.iter() {
+                let mut names = Vec::new();
+                declare_match_pattern(&arm.pattern, &mut names);
+                let scoped = !names.is_empty();
+                if scoped {
+                    let scope = names
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, name)| (name, index))
+                        .collect();
+                    self.push_scope(scope, false);
+                }
+                self.match_pattern(&arm.pattern);
+                self.expression(arm.body.as_ref());
+                if scoped {
+                    self.pop_scope();
+                }
+            }
+            self.exhaustive(match_expr);
+        } else if let Some(func) = any.downcast_ref::<expressions::FunctionLiteral>() {
+            self.function(func);
+        } else if let Some(call) = any.downcast_ref::<expressions::CallExpression>() {
+            self.optional(&call.function);
+            for argument in call.arguments.iter().flatten() {
+                self.expression(argument.as_ref());
+            }
+            for argument in call.named_arguments.iter() {
+                self.expression(argument.value.as_ref());
+            }
+        } else if let Some(array) = any.downcast_ref::<expressions::ArrayLiteral>() {
+            for element in array.elements.iter() {
+                self.expression(element.as_ref());
+            }
+        } else if let Some(hash) = any.downcast_ref::<expressions::HashLiteral>() {
+            for (key, value) in hash.pairs.iter() {
+                self.expression(key.as_ref());
+                self.expression(value.as_ref());
+            }
+        } else if let Some(index) = any.downcast_ref::<expressions::IndexExpression>() {
+            self.optional(&index.left);
+            self.optional(&index.index);
+        } else if let Some(literal) = any.downcast_ref::<expressions::StructLiteral>() {
+            self.optional(&literal.struct_type);
+            for (_, value) in literal.fields.iter() {
+                self.expression(value.as_ref());
+            }
+            self.optional(&literal.base);
+        }
+    }
+
+    fn function(&mut self, func: &expressions::FunctionLiteral) {
+        let mut names = Vec::new();
+        for parameter in func.parameters.iter().flat_map(|p| p.iter()) {
+            declare_pattern(&parameter.pattern, &mut names);
+        }
+        if let Some(rest) = &func.rest {
+            declare(rest.value, &mut names);
+        }
+        if let Some(body) = &func.body {
+            declare_block(body, &mut names);
+        }
+        func.locals.set(names.len());
+        let scope = names
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| (name, index))
+            .collect();
+        self.push_scope(scope, true);
+        let generator = std::mem::replace(&mut self.generator, func.generator);
+        let resumable = std::mem::replace(&mut self.resumable, true);
+
+        for parameter in func.parameters.iter().flat_map(|p| p.iter()) {
+            self.pattern(&parameter.pattern);
+            if let Some(default) = &parameter.default {
+                self.expression(default.as_ref());
+            }
+        }
+        if let Some(rest) = &func.rest {
+            self.bind(rest);
+        }
+        if let Some(body) = &func.body {
+            self.block(body);
+        }
+        self.generator = generator;
+        self.resumable = resumable;
+        self.pop_scope();
+    }
+}
+
+// The functions below collect the names a scope binds, without looking
+// inside nested functions.
+
+fn declare(name: Symbol, names: &mut Vec<Symbol>) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+}
+
+fn declare_pattern(pattern: &patterns::Pattern, names: &mut Vec<Symbol>) {
+    match pattern {
+        patterns::Pattern::Identifier(identifier) => declare(identifier.value, names),
+        patterns::Pattern::Array(array) => {
+            for element in array.elements.iter() {
+                declare_pattern(element, names);
+            }
+            if let Some(rest) = &array.rest {
+                declare(rest.value, names);
+            }
+        }
+        patterns::Pattern::Hash(hash) => {
+            for entry in hash.entries.iter() {
+                declare_pattern(&entry.pattern, names);
+            }
+        }
+    }
+}
+
+fn declare_match_pattern(pattern: &patterns::MatchPattern, names: &mut Vec<Symbol>) {
+    match pattern {
+        patterns::MatchPattern::Binding(identifier) => declare(identifier.value, names),
+        patterns::MatchPattern::Variant(variant) => {
+            for field in variant.fields.iter() {
+                declare_match_pattern(field, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn declare_block(block: &statements::BlockStatement, names: &mut Vec<Symbol>) {
+    for statement in block.statements.iter().flatten() {
+        declare_statement(statement.as_ref(), names);
+    }
+}
+
+fn declare_statement(statement: &dyn Statement, names: &mut Vec<Symbol>) {
+    if let Some(let_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::LetStatement>()
+    {
+        declare_optional(&let_stmt.value, names);
+        declare_pattern(&let_stmt.pattern, names);
+    } else if let Some(return_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::ReturnStatement>()
+    {
+        declare_optional(&return_stmt.return_value, names);
+    } else if let Some(throw_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::ThrowStatement>()
+    {
+        declare_optional(&throw_stmt.value, names);
+    } else if let Some(import) = statement
+        .as_any()
+        .downcast_ref::<statements::ImportStatement>()
+    {
+        declare(import.alias.value, names);
+    } else if let Some(struct_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::StructStatement>()
+    {
+        declare(struct_stmt.name.value, names);
+    } else if let Some(enum_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::EnumStatement>()
+    {
+        declare(enum_stmt.name.value, names);
+    } else if let Some(class_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::ClassStatement>()
+    {
+        declare(class_stmt.name.value, names);
+    } else if let Some(assign) = statement
+        .as_any()
+        .downcast_ref::<statements::AssignStatement>()
+    {
+        declare_optional(&assign.object, names);
+        declare_optional(&assign.value, names);
+    } else if let Some(for_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::ForStatement>()
+    {
+        declare_optional(&for_stmt.iterable, names);
+        declare_pattern(&for_stmt.pattern, names);
+        declare_block(&for_stmt.body, names);
+    } else if let Some(yield_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::YieldStatement>()
+    {
+        declare_optional(&yield_stmt.value, names);
+    } else if let Some(expr_stmt) = statement
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+    {
+        declare_optional(&expr_stmt.expression, names);
+    }
+}
+
+fn declare_optional(expression: &Option<Box<dyn Expression>>, names: &mut Vec<Symbol>) {
+    if let Some(expression) = expression {
+        declare_expression(expression.as_ref(), names);
+    }
+}
+
+// `let` can only appear in blocks, which can only appear in `if` and `try`
+// expressions, but those can be nested in any other expression.
+fn declare_expression(expression: &dyn Expression, names: &mut Vec<Symbol>) {
+    let any = expression.as_any();
+    if let Some(try_expr) = any.downcast_ref::<expressions::Try>() {
+        if let Some(block) = &try_expr.body {
+            declare_block(block, names);
+        }
+        if let Some(block) = &try_expr.handler {
+            declare_block(block, names);
+        }
+        if let Some(block) = &try_expr.finalizer {
+            declare_block(block, names);
+        }
+    } else if let Some(if_expr) = any.downcast_ref::<expressions::If>() {
+        declare_optional(&if_expr.condition, names);
+        if let Some(block) = &if_expr.first {
+            declare_block(block, names);
+        }
+        if let Some(block) = &if_expr.second {
+            declare_block(block, names);
+        }
+    } else if let Some(match_expr) = any.downcast_ref::<expressions::Match>() {
+        declare_optional(&match_expr.subject, names);
+        for arm in match_expr.arms.iter() {
+            declare_expression(arm.body.as_ref(), names);
+        }
+    } else if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+        declare_optional(&prefix.right, names);
+    } else if let Some(propagate) = any.downcast_ref::<expressions::Propagate>() {
+        declare_optional(&propagate.value, names);
+    } else if let Some(member) = any.downcast_ref::<expressions::Member>() {
+        declare_optional(&member.object, names);
+    } else if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+        declare_optional(&infix.left, names);
+        declare_optional(&infix.right, names);
+    } else if let Some(call) = any.downcast_ref::<expressions::CallExpression>() {
+        declare_optional(&call.function, names);
+        for argument in call.arguments.iter().flatten() {
+            declare_expression(argument.as_ref(), names);
+        }
+        for argument in call.named_arguments.iter() {
+            declare_expression(argument.value.as_ref(), names);
+        }
+    } else if let Some(array) = any.downcast_ref::<expressions::ArrayLiteral>() {
+        for element in array.elements.iter() {
+            declare_expression(element.as_ref(), names);
+        }
+    } else if let Some(hash) = any.downcast_ref::<expressions::HashLiteral>() {
+        for (key, value) in hash.pairs.iter() {
+            declare_expression(key.as_ref(), names);
+            declare_expression(value.as_ref(), names);
+        }
+    } else if let Some(index) = any.downcast_ref::<expressions::IndexExpression>() {
+        declare_optional(&index.left, names);
+        declare_optional(&index.index, names);
+    } else if let Some(literal) = any.downcast_ref::<expressions::StructLiteral>() {
+        declare_optional(&literal.struct_type, names);
+        for (_, value) in literal.fields.iter() {
+            declare_expression(value.as_ref(), names);
+        }
+        declare_optional(&literal.base, names);
+    }
+}
@@ -1,4 +1,7 @@
+mod symbol;
+
 use std::fmt::{Display, Formatter};
+pub use symbol::Symbol;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TokenType {
@@ -8,6 +11,7 @@ pub enum TokenType {
     // Ident + Literals
     Ident,
     Int,
+    String,
 
     // Operators
     Assign,
@@ -21,12 +25,19 @@ pub enum TokenType {
     // Delimiters
     Comma,
     Semicolon,
+    Colon,
+    Ellipsis,
+    Dot,
+    FatArrow,
     Slash,
+    Question,
 
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     LT,
     RT,
 
@@ -38,6 +49,22 @@ pub enum TokenType {
     If,
     Else,
     Return,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+    Import,
+    Export,
+    As,
+    Struct,
+    Enum,
+    Match,
+    Class,
+    Extends,
+    Super,
+    For,
+    In,
+    Yield,
 }
 
 impl Display for TokenType {
@@ -47,6 +74,7 @@ impl Display for TokenType {
             TokenType::Eof => write!(f, "Token::Eof"),
             TokenType::Ident => write!(f, "Token::Ident"),
             TokenType::Int => write!(f, "Token::Int"),
+            TokenType::String => write!(f, "Token::String"),
             TokenType::Assign => write!(f, "Token::Assign"),
             TokenType::Plus => write!(f, "Token::Plus"),
             TokenType::Minus => write!(f, "Token::Minus"),
@@ -56,11 +84,18 @@ impl Display for TokenType {
             TokenType::NotEq => write!(f, "Token::NotEq"),
             TokenType::Comma => write!(f, "Token::Comma"),
             TokenType::Semicolon => write!(f, "Token::Semicolon"),
+            TokenType::Colon => write!(f, "Token::Colon"),
+            TokenType::Ellipsis => write!(f, "Token::Ellipsis"),
+            TokenType::Dot => write!(f, "Token::Dot"),
+            TokenType::FatArrow => write!(f, "Token::FatArrow"),
             TokenType::Slash => write!(f, "Token::Slash"),
+            TokenType::Question => write!(f, "Token::Question"),
             TokenType::LParen => write!(f, "Token::LParen"),
             TokenType::RParen => write!(f, "Token::RParen"),
             TokenType::LBrace => write!(f, "Token::LBrace"),
             TokenType::RBrace => write!(f, "Token::RBrace"),
+            TokenType::LBracket => write!(f, "Token::LBracket"),
+            TokenType::RBracket => write!(f, "Token::RBracket"),
             TokenType::LT => write!(f, "Token::LT"),
             TokenType::RT => write!(f, "Token::RT"),
             TokenType::Function => write!(f, "Token::Function"),
@@ -70,18 +105,75 @@ impl Display for TokenType {
             TokenType::If => write!(f, "Token::If"),
             TokenType::Else => write!(f, "Token::Else"),
             TokenType::Return => write!(f, "Token::Return"),
+            TokenType::Try => write!(f, "Token::Try"),
+            TokenType::Catch => write!(f, "Token::Catch"),
+            TokenType::Finally => write!(f, "Token::Finally"),
+            TokenType::Throw => write!(f, "Token::Throw"),
+            TokenType::Import => write!(f, "Token::Import"),
+            TokenType::Export => write!(f, "Token::Export"),
+            TokenType::As => write!(f, "Token::As"),
+            TokenType::Struct => write!(f, "Token::Struct"),
+            TokenType::Enum => write!(f, "Token::Enum"),
+            TokenType::Match => write!(f, "Token::Match"),
+            TokenType::Class => write!(f, "Token::Class"),
+            TokenType::Extends => write!(f, "Token::Extends"),
+            TokenType::Super => write!(f, "Token::Super"),
+            TokenType::For => write!(f, "Token::For"),
+            TokenType::In => write!(f, "Token::In"),
+            TokenType::Yield => write!(f, "Token::Yield"),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// The span is where the token was found in the source. It is not part of
+// the token's identity, so it is left out of equality and hashing.
+//
+// Identifiers carry their interned name in `symbol` and leave `literal`
+// empty.
+#[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
+    pub symbol: Option<Symbol>,
+    pub span: Span,
+}
+
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type
+            && self.literal == other.literal
+            && self.symbol == other.symbol
+    }
+}
+
+impl Eq for Token {}
+
+impl std::hash::Hash for Token {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.token_type.hash(state);
+        self.literal.hash(state);
+        self.symbol.hash(state);
+    }
 }
 
 impl Token {
     pub fn new(token_type: TokenType, literal: Option<String>) -> Self {
+        if token_type == TokenType::Ident {
+            let name = literal.unwrap_or_default();
+            return Token::identifier(Symbol::intern(&name));
+        }
         let literal: String = match token_type {
             TokenType::Bang => "!".to_string(),
             TokenType::Minus => "-".to_string(),
@@ -92,13 +184,31 @@ impl Token {
             TokenType::NotEq => "!=".to_string(),
             TokenType::LT => "<".to_string(),
             TokenType::RT => ">".to_string(),
+            TokenType::Colon => ":".to_string(),
+            TokenType::Ellipsis => "...".to_string(),
             _ => literal.unwrap_or(String::new()),
         };
         return Token {
             token_type,
             literal,
+            symbol: None,
+            span: Span::default(),
         };
     }
+
+    pub fn identifier(symbol: Symbol) -> Self {
+        Token {
+            token_type: TokenType::Ident,
+            literal: String::new(),
+            symbol: Some(symbol),
+            span: Span::default(),
+        }
+    }
+
+    // The identifier's name. Panics if the token is not an identifier.
+    pub fn symbol(&self) -> Symbol {
+        self.symbol.expect("not an identifier token")
+    }
 }
 
 impl Display for Token {
@@ -106,8 +216,9 @@ impl Display for Token {
         match self.token_type {
             TokenType::Illegal => write!(f, "Token::Illegal"),
             TokenType::Eof => write!(f, "Token::Eof"),
-            TokenType::Ident => write!(f, "Token::Ident({})", self.literal),
+            TokenType::Ident => write!(f, "Token::Ident({})", self.symbol()),
             TokenType::Int => write!(f, "Token::Int({})", self.literal),
+            TokenType::String => write!(f, "Token::String({})", self.literal),
             TokenType::Assign => write!(f, "Token::Assign"),
             TokenType::Plus => write!(f, "Token::Plus"),
             TokenType::Minus => write!(f, "Token::Minus"),
@@ -117,11 +228,18 @@ impl Display for Token {
             TokenType::NotEq => write!(f, "Token::NotEq"),
             TokenType::Comma => write!(f, "Token::Comma"),
             TokenType::Semicolon => write!(f, "Token::Semicolon"),
+            TokenType::Colon => write!(f, "Token::Colon"),
+            TokenType::Ellipsis => write!(f, "Token::Ellipsis"),
+            TokenType::Dot => write!(f, "Token::Dot"),
+            TokenType::FatArrow => write!(f, "Token::FatArrow"),
             TokenType::Slash => write!(f, "Token::Slash"),
+            TokenType::Question => write!(f, "Token::Question"),
             TokenType::LParen => write!(f, "Token::LParen"),
             TokenType::RParen => write!(f, "Token::RParen"),
             TokenType::LBrace => write!(f, "Token::LBrace"),
             TokenType::RBrace => write!(f, "Token::RBrace"),
+            TokenType::LBracket => write!(f, "Token::LBracket"),
+            TokenType::RBracket => write!(f, "Token::RBracket"),
             TokenType::LT => write!(f, "Token::LT"),
             TokenType::RT => write!(f, "Token::RT"),
             TokenType::Function => write!(f, "Token::Function"),
@@ -131,6 +249,22 @@ impl Display for Token {
             TokenType::If => write!(f, "Token::If"),
             TokenType::Else => write!(f, "Token::Else"),
             TokenType::Return => write!(f, "Token::Return"),
+            TokenType::Try => write!(f, "Token::Try"),
+            TokenType::Catch => write!(f, "Token::Catch"),
+            TokenType::Finally => write!(f, "Token::Finally"),
+            TokenType::Throw => write!(f, "Token::Throw"),
+            TokenType::Import => write!(f, "Token::Import"),
+            TokenType::Export => write!(f, "Token::Export"),
+            TokenType::As => write!(f, "Token::As"),
+            TokenType::Struct => write!(f, "Token::Struct"),
+            TokenType::Enum => write!(f, "Token::Enum"),
+            TokenType::Match => write!(f, "Token::Match"),
+            TokenType::Class => write!(f, "Token::Class"),
+            TokenType::Extends => write!(f, "Token::Extends"),
+            TokenType::Super => write!(f, "Token::Super"),
+            TokenType::For => write!(f, "Token::For"),
+            TokenType::In => write!(f, "Token::In"),
+            TokenType::Yield => write!(f, "Token::Yield"),
         }
     }
 }
@@ -140,6 +274,11 @@ pub struct Lexer {
     pub pos: usize,
     pub read_pos: usize,
     pub ch: Option<u8>,
+    pub line: usize,
+    pub line_start: usize,
+    // Characters and literals that could not be read, each lexed as an
+    // `Illegal` token.
+    pub errors: Vec<String>,
 }
 
 impl Lexer {
@@ -149,6 +288,9 @@ impl Lexer {
             pos: 0,
             read_pos: 0,
             ch: None,
+            line: 1,
+            line_start: 0,
+            errors: Vec::new(),
         };
         lexer.read_char();
         lexer
@@ -156,14 +298,22 @@ impl Lexer {
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
         let ch = self.ch.expect("Error: ch is None");
-        let token = match ch {
-            b'a'..=b'z' | b'A'..=b'Z' => self.read_identifier(ch),
+        let span = Span {
+            line: self.line,
+            column: self.pos - self.line_start + 1,
+        };
+        let mut token = match ch {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.read_identifier(ch),
             b'0'..=b'9' => self.read_number(ch),
             b'=' => match self.read_head_value() {
                 b'=' => {
                     self.read_char();
                     Token::new(TokenType::Eq, None)
                 }
+                b'>' => {
+                    self.read_char();
+                    Token::new(TokenType::FatArrow, None)
+                }
                 _ => Token::new(TokenType::Assign, None),
             },
             b'+' => Token::new(TokenType::Plus, None),
@@ -176,27 +326,48 @@ impl Lexer {
                 _ => Token::new(TokenType::Bang, None),
             },
             b'*' => Token::new(TokenType::Asterisk, None),
+            b'"' => self.read_string(),
             b',' => Token::new(TokenType::Comma, None),
             b';' => Token::new(TokenType::Semicolon, None),
+            b':' => Token::new(TokenType::Colon, None),
+            b'.' => match (
+                self.read_head_value(),
+                self.read_value_at(self.read_pos + 1),
+            ) {
+                (b'.', b'.') => {
+                    self.read_char();
+                    self.read_char();
+                    Token::new(TokenType::Ellipsis, None)
+                }
+                _ => Token::new(TokenType::Dot, None),
+            },
             b'/' => Token::new(TokenType::Slash, None),
+            b'?' => Token::new(TokenType::Question, None),
             b'(' => Token::new(TokenType::LParen, None),
             b')' => Token::new(TokenType::RParen, None),
             b'{' => Token::new(TokenType::LBrace, None),
             b'}' => Token::new(TokenType::RBrace, None),
+            b'[' => Token::new(TokenType::LBracket, None),
+            b']' => Token::new(TokenType::RBracket, None),
             b'<' => Token::new(TokenType::LT, None),
             b'>' => Token::new(TokenType::RT, None),
             0 => Token::new(TokenType::Eof, None),
             _ => {
-                println!("Unknown character: {}", char::from(ch));
+                self.errors
+                    .push(format!("Unknown character {} at {}", char::from(ch), span));
                 Token::new(TokenType::Illegal, None)
             }
         };
         self.read_char();
+        token.span = span;
         token
     }
 
     pub fn read_head_value(&self) -> u8 {
-        let look_ahead_pos = self.read_pos;
+        self.read_value_at(self.read_pos)
+    }
+
+    pub fn read_value_at(&self, look_ahead_pos: usize) -> u8 {
         if look_ahead_pos >= self.input.len() {
             0
         } else {
@@ -207,7 +378,12 @@ impl Lexer {
     pub fn skip_whitespace(&mut self) {
         loop {
             match self.ch.expect("No char found") {
-                b' ' | b'\n' | b'\r' | b'\t' => self.read_char(),
+                b'\n' => {
+                    self.line += 1;
+                    self.line_start = self.pos + 1;
+                    self.read_char();
+                }
+                b' ' | b'\r' | b'\t' => self.read_char(),
                 _ => break,
             }
         }
@@ -222,6 +398,22 @@ impl Lexer {
             "if" => Token::new(TokenType::If, None),
             "else" => Token::new(TokenType::Else, None),
             "return" => Token::new(TokenType::Return, None),
+            "try" => Token::new(TokenType::Try, None),
+            "catch" => Token::new(TokenType::Catch, None),
+            "finally" => Token::new(TokenType::Finally, None),
+            "throw" => Token::new(TokenType::Throw, None),
+            "import" => Token::new(TokenType::Import, None),
+            "export" => Token::new(TokenType::Export, None),
+            "as" => Token::new(TokenType::As, None),
+            "struct" => Token::new(TokenType::Struct, None),
+            "enum" => Token::new(TokenType::Enum, None),
+            "match" => Token::new(TokenType::Match, None),
+            "class" => Token::new(TokenType::Class, None),
+            "extends" => Token::new(TokenType::Extends, None),
+            "super" => Token::new(TokenType::Super, None),
+            "for" => Token::new(TokenType::For, None),
+            "in" => Token::new(TokenType::In, None),
+            "yield" => Token::new(TokenType::Yield, None),
             _ => return None,
         };
         Some(token)
@@ -252,24 +444,19 @@ impl Lexer {
     }
 
     pub fn read_identifier(&mut self, ch: u8) -> Token {
+        let start = self.pos;
         let mut ch = ch;
-        let mut us: Vec<u8> = vec![];
-        loop {
-            match ch {
-                b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
-                    us.push(ch);
-                    self.read_char();
-                    ch = self.ch.expect("Missing ch (ident)");
-                }
-                _ => break,
-            }
+        while let b'a'..=b'z' | b'A'..=b'Z' | b'_' = ch {
+            self.read_char();
+            ch = self.ch.expect("Missing ch (ident)");
         }
-        let s: String =
-            std::string::String::from_utf8(us).expect("Couldn't coerce identifier to utf8 String");
+        // Identifiers are ASCII, so the bytes are valid UTF-8.
+        let s = std::str::from_utf8(&self.input[start..self.pos])
+            .expect("Couldn't coerce identifier to utf8 str");
 
-        let token = match Lexer::get_keyword_token(&s) {
+        let token = match Lexer::get_keyword_token(s) {
             Some(x) => x,
-            _ => Token::new(TokenType::Ident, Some(s)),
+            _ => Token::identifier(Symbol::intern(s)),
         };
 
         self.pos -= 1;
@@ -277,6 +464,34 @@ impl Lexer {
         token
     }
 
+    pub fn read_string(&mut self) -> Token {
+        let start = Span {
+            line: self.line,
+            column: self.pos - self.line_start + 1,
+        };
+        let mut us: Vec<u8> = vec![];
+        loop {
+            self.read_char();
+            match self.ch.expect("Missing ch (string)") {
+                b'"' => break,
+                b'\n' => {
+                    self.line += 1;
+                    self.line_start = self.pos + 1;
+                    us.push(b'\n');
+                }
+                0 => {
+                    self.errors
+                        .push(format!("Unterminated string literal at {}", start));
+                    return Token::new(TokenType::Illegal, None);
+                }
+                ch => us.push(ch),
+            }
+        }
+        let s: String = std::string::String::from_utf8(us)
+            .expect("Couldn't coerce string literal to utf8 String");
+        Token::new(TokenType::String, Some(s))
+    }
+
     pub fn read_char(&mut self) {
         let read_pos = self.read_pos;
         if read_pos >= self.input.len() {
@@ -0,0 +1,642 @@
+use ast::ast::{Expression, Statement};
+use ast::{expressions, patterns, statements};
+use lexer::{Span, Symbol, Token, TokenType};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Rewrites a program into a cheaper one that evaluates to the same thing.
+//
+// Arithmetic and comparisons on integer and boolean literals are folded,
+// `if`s with a constant condition lose the branch that can never run, and
+// a variable bound exactly once in the program with `let x = <constant>`
+// is replaced by the constant where it is read after that `let`.
+//
+// Anything that would fail at runtime, such as `1 / 0` or an overflow, is
+// left alone so that it still fails in the same way.
+pub fn optimize(program: statements::ProgramStatement) -> statements::ProgramStatement {
+    let mut bindings = HashMap::new();
+    for statement in program.body.iter() {
+        count_statement(statement.as_ref(), &mut bindings);
+    }
+    let mut optimizer = Optimizer {
+        bindings,
+        constants: Vec::new(),
+    };
+    statements::ProgramStatement {
+        body: optimizer.statements(program.body),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Constant {
+    Integer(i64),
+    Boolean(bool),
+}
+
+struct Optimizer {
+    // How many times each name is bound anywhere in the program.
+    bindings: HashMap<Symbol, usize>,
+    // The constants in effect at the current point, innermost last.
+    constants: Vec<(Symbol, Constant)>,
+}
+
+impl Optimizer {
+    fn statements(&mut self, body: Vec<Box<dyn Statement>>) -> Vec<Box<dyn Statement>> {
+        let scope = self.constants.len();
+        let body = body
+            .into_iter()
+            .map(|statement| self.statement(statement))
+            .collect();
+        self.constants.truncate(scope);
+        body
+    }
+
+    fn block(&mut self, block: statements::BlockStatement) -> statements::BlockStatement {
+        let scope = self.constants.len();
+        let statements = block
+            .statements
+            .into_iter()
+            .map(|statement| statement.map(|statement| self.statement(statement)))
+            .collect();
+        self.constants.truncate(scope);
+        statements::BlockStatement {
+            token: block.token,
+            statements,
+        }
+    }
+
+    fn statement(&mut self, statement: Box<dyn Statement>) -> Box<dyn Statement> {
+        let any = statement.as_any();
+        if any.is::<statements::LetStatement>() {
+            let let_stmt: statements::LetStatement = downcast(statement.into_any());
+            let value = self.optional(let_stmt.value);
+            if let (patterns::Pattern::Identifier(identifier), Some(value)) =
+                (&let_stmt.pattern, &value)
+            {
+                if let Some(constant) = constant(value.as_ref()) {
+                    if self.bindings.get(&identifier.value) == Some(&1) {
+                        self.constants.push((identifier.value, constant));
+                    }
+                }
+            }
+            return Box::new(statements::LetStatement {
+                token: let_stmt.token,
+                pattern: let_stmt.pattern,
+                value,
+                exported: let_stmt.exported,
+            });
+        }
+        if any.is::<statements::ReturnStatement>() {
+            let return_stmt: statements::ReturnStatement = downcast(statement.into_any());
+            return Box::new(statements::ReturnStatement {
+                token: return_stmt.token,
+                return_value: self.optional(return_stmt.return_value),
+            });
+        }
+        if any.is::<statements::ThrowStatement>() {
+            let throw_stmt: statements::ThrowStatement = downcast(statement.into_any());
+            return Box::new(statements::ThrowStatement {
+                token: throw_stmt.token,
+                value: self.optional(throw_stmt.value),
+            });
+        }
+        if any.is::<statements::ClassStatement>() {
+            let class_stmt: statements::ClassStatement = downcast(statement.into_any());
+            return Box::new(statements::ClassStatement {
+                token: class_stmt.token,
+                name: class_stmt.name,
+                superclass: class_stmt.superclass,
+                methods: class_stmt
+                    .methods
+                    .into_iter()
+                    .map(|method| statements::ClassMethod {
+                        name: method.name,
+                        function: self.function(method.function),
+                    })
+                    .collect(),
+                exported: class_stmt.exported,
+            });
+        }
+        if any.is::<statements::AssignStatement>() {
+            let assign: statements::AssignStatement = downcast(statement.into_any());
+            return Box::new(statements::AssignStatement {
+                token: assign.token,
+                object: self.optional(assign.object),
+                property: assign.property,
+                value: self.optional(assign.value),
+            });
+        }
+        if any.is::<statements::ForStatement>() {
+            let for_stmt: statements::ForStatement = downcast(statement.into_any());
+            return Box::new(statements::ForStatement {
+                token: for_stmt.token,
+                pattern: for_stmt.pattern,
+                iterable: self.optional(for_stmt.iterable),
+                body: self.block(for_stmt.body),
+            });
+        }
+        if any.is::<statements::YieldStatement>() {
+            let yield_stmt: statements::YieldStatement = downcast(statement.into_any());
+            return Box::new(statements::YieldStatement {
+                token: yield_stmt.token,
+                value: self.optional(yield_stmt.value),
+            });
+        }
+        if any.is::<statements::ExpressionStatement>() {
+            let expr_stmt: statements::ExpressionStatement = downcast(statement.into_any());
+            return Box::new(statements::ExpressionStatement {
+                token: expr_stmt.token,
+                expression: self.optional(expr_stmt.expression),
+            });
+        }
+        statement
+    }
+
+    fn optional(&mut self, expression: Option<Box<dyn Expression>>) -> Option<Box<dyn Expression>> {
+        expression.map(|expression| self.expression(expression))
+    }
+
+    fn expression(&mut self, expression: Box<dyn Expression>) -> Box<dyn Expression> {
+        let any = expression.as_any();
+        if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+            let found = self
+                .constants
+                .iter()
+                .rev()
+                .find(|(name, _)| *name == identifier.value);
+            return match found {
+                Some((_, value)) => literal(*value, identifier.token.span),
+                None => expression,
+            };
+        }
+        if any.is::<expressions::Prefix>() {
+            let prefix: expressions::Prefix = downcast(expression.into_any());
+            let right = self.optional(prefix.right);
+            let folded = right
+                .as_deref()
+                .and_then(constant)
+                .and_then(|right| fold_prefix(&prefix.operator, right));
+            return match folded {
+                Some(value) => literal(value, prefix.token.span),
+                None => Box::new(expressions::Prefix {
+                    token: prefix.token,
+                    operator: prefix.operator,
+                    right,
+                }),
+            };
+        }
+        if any.is::<expressions::Infix>() {
+            let infix: expressions::Infix = downcast(expression.into_any());
+            let left = self.optional(infix.left);
+            let right = self.optional(infix.right);
+            let folded = match (left.as_deref(), right.as_deref()) {
+                (Some(left), Some(right)) => match (constant(left), constant(right)) {
+                    (Some(left), Some(right)) => fold_infix(&infix.operator, left, right),
+                    _ => None,
+                },
+                _ => None,
+            };
+            return match folded {
+                Some(value) => literal(value, infix.token.span),
+                None => Box::new(expressions::Infix {
+                    token: infix.token,
+                    operator: infix.operator,
+                    left,
+                    right,
+                }),
+            };
+        }
+        if any.is::<expressions::If>() {
+            let if_expr: expressions::If = downcast(expression.into_any());
+            return Box::new(self.if_expression(if_expr));
+        }
+        if any.is::<expressions::Propagate>() {
+            let propagate: expressions::Propagate = downcast(expression.into_any());
+            return Box::new(expressions::Propagate {
+                token: propagate.token,
+                value: self.optional(propagate.value),
+            });
+        }
+        if any.is::<expressions::Member>() {
+            let member: expressions::Member = downcast(expression.into_any());
+            return Box::new(expressions::Member {
+                token: member.token,
+                object: self.optional(member.object),
+                property: member.property,
+            });
+        }
+        if any.is::<expressions::Match>() {
+            let match_expr: expressions::Match = downcast(expression.into_any());
+            return Box::new(expressions::Match {
+                token: match_expr.token,
+                subject: self.optional(match_expr.subject),
+                arms: match_expr
+                    .arms
+                    .into_iter()
+                    .map(|arm| expressions::MatchArm {
+                        pattern: arm.pattern,
+                        body: self.expression(arm.body),
+                    })
+                    .collect(),
+            });
+        }
+        if any.is::<expressions::Try>() {
+            let try_expr: expressions::Try = downcast(expression.into_any());
+            return Box::new(expressions::Try {
+                token: try_expr.token,
+                body: try_expr.body.map(|block| self.block(block)),
+                parameter: try_expr.parameter,
+                handler: try_expr.handler.map(|block| self.block(block)),
+                finalizer: try_expr.finalizer.map(|block| self.block(block)),
+            });
+        }
+        if any.is::<expressions::FunctionLiteral>() {
+            let func: expressions::FunctionLiteral = downcast(expression.into_any());
+            return Box::new(self.function(func));
+        }
+        if any.is::<expressions::CallExpression>() {
+            let call: expressions::CallExpression = downcast(expression.into_any());
+            return Box::new(expressions::CallExpression {
+                token: call.token,
+                function: self.optional(call.function),
+                arguments: call.arguments.map(|arguments| {
+                    arguments
+                        .into_iter()
+                        .map(|argument| self.expression(argument))
+                        .collect()
+                }),
+                named_arguments: call
+                    .named_arguments
+                    .into_iter()
+                    .map(|argument| expressions::NamedArgument {
+                        name: argument.name,
+                        value: self.expression(argument.value),
+                    })
+                    .collect(),
+            });
+        }
+        if any.is::<expressions::ArrayLiteral>() {
+            let array: expressions::ArrayLiteral = downcast(expression.into_any());
+            return Box::new(expressions::ArrayLiteral {
+                token: array.token,
+                elements: array
+                    .elements
+                    .into_iter()
+                    .map(|element| self.expression(element))
+                    .collect(),
+            });
+        }
+        if any.is::<expressions::HashLiteral>() {
+            let hash: expressions::HashLiteral = downcast(expression.into_any());
+            return Box::new(expressions::HashLiteral {
+                token: hash.token,
+                pairs: hash
+                    .pairs
+                    .into_iter()
+                    .map(|(key, value)| (self.expression(key), self.expression(value)))
+                    .collect(),
+            });
+        }
+        if any.is::<expressions::IndexExpression>() {
+            let index: expressions::IndexExpression = downcast(expression.into_any());
+            return Box::new(expressions::IndexExpression {
+                token: index.token,
+                left: self.optional(index.left),
+                index: self.optional(index.index),
+            });
+        }
+        if any.is::<expressions::StructLiteral>() {
+            let literal: expressions::StructLiteral = downcast(expression.into_any());
+            return Box::new(expressions::StructLiteral {
+                token: literal.token,
+                struct_type: self.optional(literal.struct_type),
+                fields: literal
+                    .fields
+                    .into_iter()
+                    .map(|(name, value)| (name, self.expression(value)))
+                    .collect(),
+                base: self.optional(literal.base),
+            });
+        }
+        expression
+    }
+
+    // An `if` whose condition is known keeps only the branch that runs,
+    // behind an `if (true)` so that it is still evaluated as a block.
+    fn if_expression(&mut self, if_expr: expressions::If) -> expressions::If {
+        let condition = self.optional(if_expr.condition);
+        let taken = condition
+            .as_deref()
+            .and_then(constant)
+            .map(|value| match value {
+                Constant::Boolean(value) => value,
+                Constant::Integer(_) => true,
+            });
+        // Reading a variable that is bound nowhere is an error even in a
+        // branch that never runs, so such a branch is kept.
+        let dropped = match taken {
+            Some(true) => if_expr.second.as_ref(),
+            Some(false) => if_expr.first.as_ref(),
+            None => None,
+        };
+        let taken = match dropped {
+            Some(block) if !self.binds_all_read(block) => None,
+            _ => taken,
+        };
+        let (first, second) = match taken {
+            Some(true) => (if_expr.first, None),
+            Some(false) => (if_expr.second, None),
+            None => (if_expr.first, if_expr.second),
+        };
+        let condition = match taken {
+            Some(_) => Some(literal(Constant::Boolean(true), if_expr.token.span)),
+            None => condition,
+        };
+        expressions::If {
+            token: if_expr.token,
+            condition,
+            first: first.map(|block| self.block(block)),
+            second: second.map(|block| self.block(block)),
+        }
+    }
+
+    fn binds_all_read(&self, block: &statements::BlockStatement) -> bool {
+        let mut names = HashMap::new();
+        count_block(block, &mut names);
+        names
+            .keys()
+            .all(|name| self.bindings.get(name).is_some_and(|&count| count > 0))
+    }
+
+    // A function that has already been evaluated shares its body with the
+    // function objects made from it, and is left as it is.
+    fn function(&mut self, func: expressions::FunctionLiteral) -> expressions::FunctionLiteral {
+        let parameters = func
+            .parameters
+            .map(|parameters| match Rc::try_unwrap(parameters) {
+                Ok(parameters) => Rc::new(
+                    parameters
+                        .into_iter()
+                        .map(|parameter| patterns::Parameter {
+                            pattern: parameter.pattern,
+                            default: self.optional(parameter.default),
+                        })
+                        .collect(),
+                ),
+                Err(parameters) => parameters,
+            });
+        let body = func.body.map(|body| match Rc::try_unwrap(body) {
+            Ok(body) => Rc::new(self.block(body)),
+            Err(body) => body,
+        });
+        expressions::FunctionLiteral {
+            token: func.token,
+            parameters,
+            rest: func.rest,
+            body,
+            locals: func.locals,
+            generator: func.generator,
+        }
+    }
+}
+
+fn downcast<T: 'static>(any: Box<dyn std::any::Any>) -> T {
+    *any.downcast::<T>().unwrap()
+}
+
+fn constant(expression: &dyn Expression) -> Option<Constant> {
+    let any = expression.as_any();
+    if let Some(literal) = any.downcast_ref::<expressions::IntegerLiteral>() {
+        return Some(Constant::Integer(literal.value));
+    }
+    if let Some(literal) = any.downcast_ref::<expressions::Bool>() {
+        return Some(Constant::Boolean(literal.value));
+    }
+    None
+}
+
+fn literal(value: Constant, span: Span) -> Box<dyn Expression> {
+    match value {
+        Constant::Integer(value) => Box::new(expressions::IntegerLiteral {
+            token: Token {
+                token_type: TokenType::Int,
+                literal: value.to_string(),
+                symbol: None,
+                span,
+            },
+            value,
+        }),
+        Constant::Boolean(value) => Box::new(expressions::Bool {
+            token: Token {
+                token_type: match value {
+                    true => TokenType::True,
+                    false => TokenType::False,
+                },
+                literal: value.to_string(),
+                symbol: None,
+                span,
+            },
+            value,
+        }),
+    }
+}
+
+// These mirror the evaluator's operators, but give up instead of failing.
+fn fold_prefix(operator: &str, right: Constant) -> Option<Constant> {
+    match (operator, right) {
+        ("!", Constant::Boolean(value)) => Some(Constant::Boolean(!value)),
+        ("!", Constant::Integer(_)) => Some(Constant::Boolean(false)),
+        ("-", Constant::Integer(value)) => value.checked_neg().map(Constant::Integer),
+        _ => None,
+    }
+}
+
+fn fold_infix(operator: &str, left: Constant, right: Constant) -> Option<Constant> {
+    match (left, right) {
+        (Constant::Integer(left), Constant::Integer(right)) => match operator {
+            "+" => left.checked_add(right).map(Constant::Integer),
+            "-" => left.checked_sub(right).map(Constant::Integer),
+            "*" => left.checked_mul(right).map(Constant::Integer),
+            "/" => left.checked_div(right).map(Constant::Integer),
+            "<" => Some(Constant::Boolean(left < right)),
+            ">" => Some(Constant::Boolean(left > right)),
+            "==" => Some(Constant::Boolean(left == right)),
+            "!=" => Some(Constant::Boolean(left != right)),
+            _ => None,
+        },
+        (Constant::Boolean(left), Constant::Boolean(right)) => match operator {
+            "==" => Some(Constant::Boolean(left == right)),
+            "!=" => Some(Constant::Boolean(left != right)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The functions below count every binding of every name, including
+// parameters and bindings inside functions. A name that is only read is
+// counted as bound zero times.
+
+fn count(name: Symbol, bindings: &mut HashMap<Symbol, usize>) {
+    *bindings.entry(name).or_insert(0) += 1;
+}
+
+fn count_pattern(pattern: &patterns::Pattern, bindings: &mut HashMap<Symbol, usize>) {
+    match pattern {
+        patterns::Pattern::Identifier(identifier) => count(identifier.value, bindings),
+        patterns::Pattern::Array(array) => {
+            for element in array.elements.iter() {
+                count_pattern(element, bindings);
+            }
+            if let Some(rest) = &array.rest {
+                count(rest.value, bindings);
+            }
+        }
+        patterns::Pattern::Hash(hash) => {
+            for entry in hash.entries.iter() {
+                count_pattern(&entry.pattern, bindings);
+            }
+        }
+    }
+}
+
+fn count_match_pattern(pattern: &patterns::MatchPattern, bindings: &mut HashMap<Symbol, usize>) {
+    match pattern {
+        patterns::MatchPattern::Binding(identifier) => count(identifier.value, bindings),
+        patterns::MatchPattern::Variant(variant) => {
+            for field in variant.fields.iter() {
+                count_match_pattern(field, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn count_statement(statement: &dyn Statement, bindings: &mut HashMap<Symbol, usize>) {
+    let any = statement.as_any();
+    if let Some(let_stmt) = any.downcast_ref::<statements::LetStatement>() {
+        count_pattern(&let_stmt.pattern, bindings);
+        count_optional(&let_stmt.value, bindings);
+    } else if let Some(return_stmt) = any.downcast_ref::<statements::ReturnStatement>() {
+        count_optional(&return_stmt.return_value, bindings);
+    } else if let Some(throw_stmt) = any.downcast_ref::<statements::ThrowStatement>() {
+        count_optional(&throw_stmt.value, bindings);
+    } else if let Some(import) = any.downcast_ref::<statements::ImportStatement>() {
+        count(import.alias.value, bindings);
+    } else if let Some(struct_stmt) = any.downcast_ref::<statements::StructStatement>() {
+        count(struct_stmt.name.value, bindings);
+    } else if let Some(enum_stmt) = any.downcast_ref::<statements::EnumStatement>() {
+        count(enum_stmt.name.value, bindings);
+    } else if let Some(class_stmt) = any.downcast_ref::<statements::ClassStatement>() {
+        count(class_stmt.name.value, bindings);
+        // Every method binds `self`, so it is never a constant.
+        for method in class_stmt.methods.iter() {
+            count(Symbol::intern("self"), bindings);
+            count_function(&method.function, bindings);
+        }
+    } else if let Some(assign) = any.downcast_ref::<statements::AssignStatement>() {
+        count_optional(&assign.object, bindings);
+        count_optional(&assign.value, bindings);
+    } else if let Some(for_stmt) = any.downcast_ref::<statements::ForStatement>() {
+        count_pattern(&for_stmt.pattern, bindings);
+        count_optional(&for_stmt.iterable, bindings);
+        count_block(&for_stmt.body, bindings);
+    } else if let Some(yield_stmt) = any.downcast_ref::<statements::YieldStatement>() {
+        count_optional(&yield_stmt.value, bindings);
+    } else if let Some(expr_stmt) = any.downcast_ref::<statements::ExpressionStatement>() {
+        count_optional(&expr_stmt.expression, bindings);
+    }
+}
+
+fn count_function(func: &expressions::FunctionLiteral, bindings: &mut HashMap<Symbol, usize>) {
+    for parameter in func.parameters.iter().flat_map(|p| p.iter()) {
+        count_pattern(&parameter.pattern, bindings);
+        count_optional(&parameter.default, bindings);
+    }
+    if let Some(rest) = &func.rest {
+        count(rest.value, bindings);
+    }
+    if let Some(body) = &func.body {
+        count_block(body, bindings);
+    }
+}
+
+fn count_block(block: &statements::BlockStatement, bindings: &mut HashMap<Symbol, usize>) {
+    for statement in block.statements.iter().flatten() {
+        count_statement(statement.as_ref(), bindings);
+    }
+}
+
+fn count_optional(expression: &Option<Box<dyn Expression>>, bindings: &mut HashMap<Symbol, usize>) {
+    if let Some(expression) = expression {
+        count_expression(expression.as_ref(), bindings);
+    }
+}
+
+fn count_expression(expression: &dyn Expression, bindings: &mut HashMap<Symbol, usize>) {
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+        bindings.entry(identifier.value).or_insert(0);
+    } else if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+        count_optional(&prefix.right, bindings);
+    } else if let Some(propagate) = any.downcast_ref::<expressions::Propagate>() {
+        count_optional(&propagate.value, bindings);
+    } else if let Some(member) = any.downcast_ref::<expressions::Member>() {
+        count_optional(&member.object, bindings);
+    } else if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+        count_optional(&infix.left, bindings);
+        count_optional(&infix.right, bindings);
+    } else if let Some(if_expr) = any.downcast_ref::<expressions::If>() {
+        count_optional(&if_expr.condition, bindings);
+        if let Some(block) = &if_expr.first {
+            count_block(block, bindings);
+        }
+        if let Some(block) = &if_expr.second {
+            count_block(block, bindings);
+        }
+    } else if let Some(match_expr) = any.downcast_ref::<expressions::Match>() {
+        count_optional(&match_expr.subject, bindings);
+        for arm in match_expr.arms.iter() {
+            count_match_pattern(&arm.pattern, bindings);
+            count_expression(arm.body.as_ref(), bindings);
+        }
+    } else if let Some(try_expr) = any.downcast_ref::<expressions::Try>() {
+        if let Some(parameter) = &try_expr.parameter {
+            count(parameter.value, bindings);
+        }
+        for block in [&try_expr.body, &try_expr.handler, &try_expr.finalizer]
+            .into_iter()
+            .flatten()
+        {
+            count_block(block, bindings);
+        }
+    } else if let Some(func) = any.downcast_ref::<expressions::FunctionLiteral>() {
+        count_function(func, bindings);
+    } else if let Some(call) = any.downcast_ref::<expressions::CallExpression>() {
+        count_optional(&call.function, bindings);
+        for argument in call.arguments.iter().flatten() {
+            count_expression(argument.as_ref(), bindings);
+        }
+        for argument in call.named_arguments.iter() {
+            count_expression(argument.value.as_ref(), bindings);
+        }
+    } else if let Some(array) = any.downcast_ref::<expressions::ArrayLiteral>() {
+        for element in array.elements.iter() {
+            count_expression(element.as_ref(), bindings);
+        }
+    } else if let Some(hash) = any.downcast_ref::<expressions::HashLiteral>() {
+        for (key, value) in hash.pairs.iter() {
+            count_expression(key.as_ref(), bindings);
+            count_expression(value.as_ref(), bindings);
+        }
+    } else if let Some(index) = any.downcast_ref::<expressions::IndexExpression>() {
+        count_optional(&index.left, bindings);
+        count_optional(&index.index, bindings);
+    } else if let Some(literal) = any.downcast_ref::<expressions::StructLiteral>() {
+        count_optional(&literal.struct_type, bindings);
+        for (_, value) in literal.fields.iter() {
+            count_expression(value.as_ref(), bindings);
+        }
+        count_optional(&literal.base, bindings);
+    }
+}
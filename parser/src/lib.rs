@@ -1,6 +1,7 @@
 use ast::ast::{Expression, Statement};
-use ast::{expressions, statements};
-use lexer::{Lexer, Token, TokenType};
+use ast::{expressions, patterns, statements};
+use lexer::{Lexer, Symbol, Token, TokenType};
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 // use std::fmt::{Debug, Display, Formatter};
@@ -13,7 +14,8 @@ enum Precedence {
     SUM = 3,         // +
     PRODUCT = 4,     // *
     PREFIX = 5,      // -x
-    CALL = 6,        // func(x)
+    CALL = 6,        // func(x), x?, x.y or T { y: 1 }
+    INDEX = 7,       // array[index]
 }
 
 pub struct Parser {
@@ -45,6 +47,12 @@ impl Parser {
         prefix_parse_funcs.insert(TokenType::False, Parser::parse_prefix_bool);
         prefix_parse_funcs.insert(TokenType::If, Parser::parse_if_expression);
         prefix_parse_funcs.insert(TokenType::Function, Parser::parse_function_literal);
+        prefix_parse_funcs.insert(TokenType::String, Parser::parse_string_literal);
+        prefix_parse_funcs.insert(TokenType::LBracket, Parser::parse_array_literal);
+        prefix_parse_funcs.insert(TokenType::LBrace, Parser::parse_hash_literal);
+        prefix_parse_funcs.insert(TokenType::Try, Parser::parse_try_expression);
+        prefix_parse_funcs.insert(TokenType::Match, Parser::parse_match_expression);
+        prefix_parse_funcs.insert(TokenType::Super, Parser::parse_super_expression);
         return prefix_parse_funcs;
     }
 
@@ -65,6 +73,10 @@ impl Parser {
         infix_parse_funcs.insert(TokenType::LT, Parser::parse_infix_expression);
         infix_parse_funcs.insert(TokenType::RT, Parser::parse_infix_expression);
         infix_parse_funcs.insert(TokenType::LParen, Parser::parse_call_expression);
+        infix_parse_funcs.insert(TokenType::LBracket, Parser::parse_index_expression);
+        infix_parse_funcs.insert(TokenType::Question, Parser::parse_propagate_expression);
+        infix_parse_funcs.insert(TokenType::Dot, Parser::parse_member_expression);
+        infix_parse_funcs.insert(TokenType::LBrace, Parser::parse_struct_literal);
         return infix_parse_funcs;
     }
 
@@ -86,7 +98,8 @@ impl Parser {
     fn parse_identifier(parser: &mut Parser) -> Option<Box<dyn Expression>> {
         return Some(Box::new(expressions::Identifier {
             token: parser.current_token.clone(),
-            value: parser.current_token.literal.clone(),
+            value: parser.current_token.symbol(),
+            slot: Cell::new(None),
         }));
     }
 
@@ -103,6 +116,50 @@ impl Parser {
         }));
     }
 
+    fn parse_string_literal(parser: &mut Parser) -> Option<Box<dyn Expression>> {
+        return Some(Box::new(expressions::StringLiteral {
+            token: parser.current_token.clone(),
+            value: parser.current_token.literal.clone(),
+        }));
+    }
+
+    fn parse_array_literal(parser: &mut Parser) -> Option<Box<dyn Expression>> {
+        let token = parser.current_token.clone();
+        let elements = parser.parse_expression_list(TokenType::RBracket)?;
+        return Some(Box::new(expressions::ArrayLiteral { token, elements }));
+    }
+
+    fn parse_hash_literal(parser: &mut Parser) -> Option<Box<dyn Expression>> {
+        let mut expression = expressions::HashLiteral {
+            token: parser.current_token.clone(),
+            pairs: vec![],
+        };
+
+        while !matches!(parser.peek_token.token_type, TokenType::RBrace) {
+            parser.next_token();
+            let key = parser.parse_expression(Precedence::LOWEST)?;
+
+            if !parser.expect_peek(TokenType::Colon) {
+                return None;
+            }
+
+            parser.next_token();
+            let value = parser.parse_expression(Precedence::LOWEST)?;
+            expression.pairs.push((key, value));
+
+            if !matches!(parser.peek_token.token_type, TokenType::RBrace)
+                && !parser.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
+
+        if !parser.expect_peek(TokenType::RBrace) {
+            return None;
+        }
+        return Some(Box::new(expression));
+    }
+
     fn parse_prefix_bool(parser: &mut Parser) -> Option<Box<dyn Expression>> {
         let current_token = parser.current_token.clone();
         let value: bool = match current_token.token_type {
@@ -154,19 +211,75 @@ impl Parser {
         return Some(Box::new(expression));
     }
 
+    fn parse_try_expression(parser: &mut Parser) -> Option<Box<dyn Expression>> {
+        let mut expression = expressions::Try {
+            token: parser.current_token.clone(),
+            body: None,
+            parameter: None,
+            handler: None,
+            finalizer: None,
+        };
+
+        if !parser.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        expression.body = parser.parse_block_statement();
+
+        if matches!(parser.peek_token.token_type, TokenType::Catch) {
+            parser.next_token();
+            if !parser.expect_peek(TokenType::LParen) || !parser.expect_peek(TokenType::Ident) {
+                return None;
+            }
+            expression.parameter = Some(expressions::Identifier {
+                token: parser.current_token.clone(),
+                value: parser.current_token.symbol(),
+                slot: Cell::new(None),
+            });
+            if !parser.expect_peek(TokenType::RParen) || !parser.expect_peek(TokenType::LBrace) {
+                return None;
+            }
+            expression.handler = parser.parse_block_statement();
+        }
+
+        if matches!(parser.peek_token.token_type, TokenType::Finally) {
+            parser.next_token();
+            if !parser.expect_peek(TokenType::LBrace) {
+                return None;
+            }
+            expression.finalizer = parser.parse_block_statement();
+        }
+
+        if expression.handler.is_none() && expression.finalizer.is_none() {
+            parser.errors.push(format!(
+                "Expected catch or finally but got {} instead",
+                parser.peek_token
+            ));
+            return None;
+        }
+        return Some(Box::new(expression));
+    }
+
     fn parse_function_literal(parser: &mut Parser) -> Option<Box<dyn Expression>> {
         let mut expression = expressions::FunctionLiteral {
             token: Token::new(TokenType::Function, None),
             parameters: None,
+            rest: None,
             body: None,
+            locals: Cell::new(0),
+            generator: false,
         };
 
+        if parser.peek_token.token_type == TokenType::Asterisk {
+            parser.next_token();
+            expression.generator = true;
+        }
         if !parser.expect_peek(TokenType::LParen) {
             return None;
         }
 
-        let parameters = parser.parse_function_params();
-        expression.parameters = parameters;
+        let (parameters, rest) = parser.parse_function_params()?;
+        expression.parameters = Some(Rc::new(parameters));
+        expression.rest = rest;
 
         if !parser.expect_peek(TokenType::LBrace) {
             return None;
@@ -178,34 +291,48 @@ impl Parser {
         return Some(Box::new(expression));
     }
 
-    fn parse_function_params(&mut self) -> Option<Vec<expressions::Identifier>> {
-        let mut identifiers: Vec<expressions::Identifier> = vec![];
-        if matches!(self.peek_token.token_type, TokenType::RParen) {
-            self.next_token();
-            return Some(identifiers);
-        }
+    fn parse_function_params(
+        &mut self,
+    ) -> Option<(Vec<patterns::Parameter>, Option<expressions::Identifier>)> {
+        let mut parameters: Vec<patterns::Parameter> = vec![];
+        let mut rest: Option<expressions::Identifier> = None;
 
-        self.next_token();
-        let identifier = expressions::Identifier {
-            token: self.current_token.clone(),
-            value: self.current_token.literal.clone(),
-        };
-        identifiers.push(identifier);
-
-        while matches!(self.peek_token.token_type, TokenType::Comma) {
-            self.next_token();
+        while !matches!(self.peek_token.token_type, TokenType::RParen) {
             self.next_token();
-            let identifier = expressions::Identifier {
-                token: self.current_token.clone(),
-                value: self.current_token.literal.clone(),
+            if matches!(self.current_token.token_type, TokenType::Ellipsis) {
+                if !self.expect_peek(TokenType::Ident) {
+                    return None;
+                }
+                rest = Some(expressions::Identifier {
+                    token: self.current_token.clone(),
+                    value: self.current_token.symbol(),
+                    slot: Cell::new(None),
+                });
+                break;
+            }
+
+            let mut parameter = patterns::Parameter {
+                pattern: self.parse_pattern()?,
+                default: None,
             };
-            identifiers.push(identifier);
+            if matches!(self.peek_token.token_type, TokenType::Assign) {
+                self.next_token();
+                self.next_token();
+                parameter.default = Some(self.parse_expression(Precedence::LOWEST)?);
+            }
+            parameters.push(parameter);
+
+            if !matches!(self.peek_token.token_type, TokenType::RParen)
+                && !self.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
         }
 
         if !self.expect_peek(TokenType::RParen) {
             return None;
         }
-        return Some(identifiers);
+        return Some((parameters, rest));
     }
 
     fn parse_grouped_expression(parser: &mut Parser) -> Option<Box<dyn Expression>> {
@@ -237,27 +364,322 @@ impl Parser {
         return Some(Box::new(expression));
     }
 
-    fn parse_call_arguments(parser: &mut Parser) -> Option<Vec<Box<dyn Expression>>> {
+    fn parse_call_arguments(
+        parser: &mut Parser,
+    ) -> Option<(Vec<Box<dyn Expression>>, Vec<expressions::NamedArgument>)> {
         let mut arguments: Vec<Box<dyn Expression>> = Vec::new();
-        if matches!(parser.peek_token.token_type, TokenType::RParen) {
+        let mut named_arguments: Vec<expressions::NamedArgument> = Vec::new();
+
+        while !matches!(parser.peek_token.token_type, TokenType::RParen) {
             parser.next_token();
-            return Some(arguments);
+            if matches!(parser.current_token.token_type, TokenType::Ident)
+                && matches!(parser.peek_token.token_type, TokenType::Colon)
+            {
+                let name = expressions::Identifier {
+                    token: parser.current_token.clone(),
+                    value: parser.current_token.symbol(),
+                    slot: Cell::new(None),
+                };
+                parser.next_token();
+                parser.next_token();
+                let value = parser.parse_expression(Precedence::LOWEST)?;
+                named_arguments.push(expressions::NamedArgument { name, value });
+            } else if !named_arguments.is_empty() {
+                parser
+                    .errors
+                    .push("Positional argument follows keyword argument".to_string());
+                return None;
+            } else {
+                arguments.push(parser.parse_expression(Precedence::LOWEST)?);
+            }
+
+            if !matches!(parser.peek_token.token_type, TokenType::RParen)
+                && !parser.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
+
+        if !parser.expect_peek(TokenType::RParen) {
+            return None;
+        }
+        return Some((arguments, named_arguments));
+    }
+
+    fn parse_expression_list(&mut self, end: TokenType) -> Option<Vec<Box<dyn Expression>>> {
+        let mut list: Vec<Box<dyn Expression>> = Vec::new();
+        if self.peek_token.token_type == end {
+            self.next_token();
+            return Some(list);
         }
 
+        self.next_token();
+        list.push(self.parse_expression(Precedence::LOWEST)?);
+
+        while matches!(self.peek_token.token_type, TokenType::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::LOWEST)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        return Some(list);
+    }
+
+    fn parse_index_expression(
+        parser: &mut Parser,
+        left: Option<Box<dyn Expression>>,
+    ) -> Option<Box<dyn Expression>> {
+        let mut expression = expressions::IndexExpression {
+            token: parser.current_token.clone(),
+            left,
+            index: None,
+        };
+
         parser.next_token();
-        arguments.push(parser.parse_expression(Precedence::LOWEST).unwrap());
+        expression.index = parser.parse_expression(Precedence::LOWEST);
 
-        while matches!(parser.peek_token.token_type, TokenType::Comma) {
+        if !parser.expect_peek(TokenType::RBracket) {
+            return None;
+        }
+        return Some(Box::new(expression));
+    }
+
+    fn parse_propagate_expression(
+        parser: &mut Parser,
+        left: Option<Box<dyn Expression>>,
+    ) -> Option<Box<dyn Expression>> {
+        return Some(Box::new(expressions::Propagate {
+            token: parser.current_token.clone(),
+            value: left,
+        }));
+    }
+
+    fn parse_member_expression(
+        parser: &mut Parser,
+        left: Option<Box<dyn Expression>>,
+    ) -> Option<Box<dyn Expression>> {
+        let token = parser.current_token.clone();
+        if !parser.expect_peek(TokenType::Ident) {
+            return None;
+        }
+        return Some(Box::new(expressions::Member {
+            token,
+            object: left,
+            property: expressions::Identifier {
+                token: parser.current_token.clone(),
+                value: parser.current_token.symbol(),
+                slot: Cell::new(None),
+            },
+        }));
+    }
+
+    fn parse_super_expression(parser: &mut Parser) -> Option<Box<dyn Expression>> {
+        let token = parser.current_token.clone();
+        if !parser.expect_peek(TokenType::Dot) || !parser.expect_peek(TokenType::Ident) {
+            return None;
+        }
+        return Some(Box::new(expressions::Super {
+            method: expressions::Identifier {
+                token: parser.current_token.clone(),
+                value: parser.current_token.symbol(),
+                slot: Cell::new(None),
+            },
+            class: expressions::Identifier {
+                token: token.clone(),
+                value: Symbol::intern("super"),
+                slot: Cell::new(None),
+            },
+            receiver: expressions::Identifier {
+                token: token.clone(),
+                value: Symbol::intern("self"),
+                slot: Cell::new(None),
+            },
+            token,
+        }));
+    }
+
+    fn parse_match_expression(parser: &mut Parser) -> Option<Box<dyn Expression>> {
+        let token = parser.current_token.clone();
+        if !parser.expect_peek(TokenType::LParen) {
+            return None;
+        }
+        parser.next_token();
+        let subject = parser.parse_expression(Precedence::LOWEST);
+        if !parser.expect_peek(TokenType::RParen) || !parser.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+
+        let mut arms = vec![];
+        while !matches!(parser.peek_token.token_type, TokenType::RBrace) {
             parser.next_token();
+            let pattern = parser.parse_match_pattern()?;
+            if !parser.expect_peek(TokenType::FatArrow) {
+                return None;
+            }
             parser.next_token();
-            arguments.push(parser.parse_expression(Precedence::LOWEST).unwrap());
-        }
+            let body = parser.parse_expression(Precedence::LOWEST)?;
+            arms.push(expressions::MatchArm { pattern, body });
 
-        if !parser.expect_peek(TokenType::RParen) {
+            if !matches!(parser.peek_token.token_type, TokenType::RBrace)
+                && !parser.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
+        if !parser.expect_peek(TokenType::RBrace) {
             return None;
         }
+        if arms.is_empty() {
+            parser
+                .errors
+                .push("A match needs at least one arm".to_string());
+            return None;
+        }
+        return Some(Box::new(expressions::Match {
+            token,
+            subject,
+            arms,
+        }));
+    }
+
+    fn parse_match_pattern(&mut self) -> Option<patterns::MatchPattern> {
+        let token = self.current_token.clone();
+        match token.token_type {
+            TokenType::Ident if token.symbol() == "_" => {
+                Some(patterns::MatchPattern::Wildcard(token))
+            }
+            TokenType::Ident if self.peek_token.token_type == TokenType::Dot => {
+                let enum_name = expressions::Identifier {
+                    value: token.symbol(),
+                    token: token.clone(),
+                    slot: Cell::new(None),
+                };
+                self.next_token();
+                if !self.expect_peek(TokenType::Ident) {
+                    return None;
+                }
+                let variant = expressions::Identifier {
+                    token: self.current_token.clone(),
+                    value: self.current_token.symbol(),
+                    slot: Cell::new(None),
+                };
+                let mut fields = vec![];
+                if self.peek_token.token_type == TokenType::LParen {
+                    self.next_token();
+                    while self.peek_token.token_type != TokenType::RParen {
+                        self.next_token();
+                        fields.push(self.parse_match_pattern()?);
+                        if self.peek_token.token_type != TokenType::RParen
+                            && !self.expect_peek(TokenType::Comma)
+                        {
+                            return None;
+                        }
+                    }
+                    self.next_token();
+                }
+                Some(patterns::MatchPattern::Variant(patterns::VariantPattern {
+                    token,
+                    enum_name,
+                    variant,
+                    fields,
+                }))
+            }
+            TokenType::Ident => Some(patterns::MatchPattern::Binding(expressions::Identifier {
+                value: token.symbol(),
+                token,
+                slot: Cell::new(None),
+            })),
+            TokenType::Int => token
+                .literal
+                .parse()
+                .ok()
+                .map(patterns::MatchPattern::Integer),
+            TokenType::Minus if self.peek_token.token_type == TokenType::Int => {
+                self.next_token();
+                let value: i64 = self.current_token.literal.parse().ok()?;
+                Some(patterns::MatchPattern::Integer(-value))
+            }
+            TokenType::String => Some(patterns::MatchPattern::String(token.literal)),
+            TokenType::True => Some(patterns::MatchPattern::Boolean(true)),
+            TokenType::False => Some(patterns::MatchPattern::Boolean(false)),
+            _ => {
+                self.errors.push(format!(
+                    "Expected a match pattern but got {} instead",
+                    token
+                ));
+                None
+            }
+        }
+    }
+
+    fn parse_struct_literal(
+        parser: &mut Parser,
+        struct_type: Option<Box<dyn Expression>>,
+    ) -> Option<Box<dyn Expression>> {
+        let mut expression = expressions::StructLiteral {
+            token: parser.current_token.clone(),
+            struct_type,
+            fields: vec![],
+            base: None,
+        };
+
+        while !matches!(parser.peek_token.token_type, TokenType::RBrace) {
+            parser.next_token();
+            if parser.current_token.token_type == TokenType::Ellipsis {
+                if expression.base.is_some() {
+                    parser
+                        .errors
+                        .push("A struct literal can only spread one value".to_string());
+                    return None;
+                }
+                parser.next_token();
+                expression.base = Some(parser.parse_expression(Precedence::LOWEST)?);
+            } else {
+                if parser.current_token.token_type != TokenType::Ident {
+                    parser.errors.push(format!(
+                        "Expected a field name but got {} instead",
+                        parser.current_token
+                    ));
+                    return None;
+                }
+                let name = expressions::Identifier {
+                    token: parser.current_token.clone(),
+                    value: parser.current_token.symbol(),
+                    slot: Cell::new(None),
+                };
+                if expression
+                    .fields
+                    .iter()
+                    .any(|(field, _)| field.value == name.value)
+                {
+                    parser
+                        .errors
+                        .push(format!("Duplicate field {} in struct literal", name.value));
+                    return None;
+                }
+                if !parser.expect_peek(TokenType::Colon) {
+                    return None;
+                }
+                parser.next_token();
+                let value = parser.parse_expression(Precedence::LOWEST)?;
+                expression.fields.push((name, value));
+            }
+
+            if !matches!(parser.peek_token.token_type, TokenType::RBrace)
+                && !parser.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
 
-        return Some(arguments);
+        if !parser.expect_peek(TokenType::RBrace) {
+            return None;
+        }
+        return Some(Box::new(expression));
     }
 
     fn parse_call_expression(
@@ -268,9 +690,11 @@ impl Parser {
             token: parser.current_token.clone(),
             function,
             arguments: None,
+            named_arguments: vec![],
         };
-        let arguments = Parser::parse_call_arguments(parser);
-        expression.arguments = arguments;
+        let (arguments, named_arguments) = Parser::parse_call_arguments(parser)?;
+        expression.arguments = Some(arguments);
+        expression.named_arguments = named_arguments;
 
         return Some(Box::new(expression));
     }
@@ -286,6 +710,10 @@ impl Parser {
             (TokenType::Slash, Precedence::PRODUCT),
             (TokenType::Asterisk, Precedence::PRODUCT),
             (TokenType::LParen, Precedence::CALL),
+            (TokenType::Question, Precedence::CALL),
+            (TokenType::Dot, Precedence::CALL),
+            (TokenType::LBrace, Precedence::CALL),
+            (TokenType::LBracket, Precedence::INDEX),
         ]);
         return precedence_lookup;
     }
@@ -347,6 +775,14 @@ impl Parser {
         let statement: Option<Box<dyn Statement>> = match self.current_token.token_type {
             TokenType::Let => self.parse_let_statement(),
             TokenType::Return => self.parse_return_statement(),
+            TokenType::Throw => self.parse_throw_statement(),
+            TokenType::Import => self.parse_import_statement(),
+            TokenType::Struct => self.parse_struct_statement(),
+            TokenType::Enum => self.parse_enum_statement(),
+            TokenType::Class => self.parse_class_statement(),
+            TokenType::For => self.parse_for_statement(),
+            TokenType::Yield => self.parse_yield_statement(),
+            TokenType::Export => self.parse_export_statement(),
             _ => self.parse_expression_statement(),
         };
         return statement;
@@ -370,15 +806,9 @@ impl Parser {
     }
 
     fn parse_let_statement(&mut self) -> Option<Box<dyn Statement>> {
-        if !self.expect_peek(TokenType::Ident) {
-            return None;
-        };
-
-        let ident_token = self.current_token.clone();
-        let identifier = expressions::Identifier {
-            token: ident_token.clone(),
-            value: ident_token.literal,
-        };
+        let token = self.current_token.clone();
+        self.next_token();
+        let pattern = self.parse_pattern()?;
 
         if !self.expect_peek(TokenType::Assign) {
             return None;
@@ -392,9 +822,10 @@ impl Parser {
         }
 
         let statement = statements::LetStatement {
-            token: Token::new(TokenType::Let, None),
-            name: identifier,
+            token,
+            pattern,
             value: expression,
+            exported: false,
         };
 
         if !(TokenType::Semicolon == self.current_token.token_type) {
@@ -403,6 +834,105 @@ impl Parser {
         return Some(Box::new(statement));
     }
 
+    fn parse_pattern(&mut self) -> Option<patterns::Pattern> {
+        match self.current_token.token_type {
+            TokenType::Ident => Some(patterns::Pattern::Identifier(expressions::Identifier {
+                token: self.current_token.clone(),
+                value: self.current_token.symbol(),
+                slot: Cell::new(None),
+            })),
+            TokenType::LBracket => self.parse_array_pattern(),
+            TokenType::LBrace => self.parse_hash_pattern(),
+            _ => {
+                self.errors.push(format!(
+                    "Expected a binding pattern but got {} instead",
+                    self.current_token
+                ));
+                None
+            }
+        }
+    }
+
+    fn parse_array_pattern(&mut self) -> Option<patterns::Pattern> {
+        let mut pattern = patterns::ArrayPattern {
+            token: self.current_token.clone(),
+            elements: vec![],
+            rest: None,
+        };
+
+        while !matches!(self.peek_token.token_type, TokenType::RBracket) {
+            self.next_token();
+            if matches!(self.current_token.token_type, TokenType::Ellipsis) {
+                if !self.expect_peek(TokenType::Ident) {
+                    return None;
+                }
+                pattern.rest = Some(expressions::Identifier {
+                    token: self.current_token.clone(),
+                    value: self.current_token.symbol(),
+                    slot: Cell::new(None),
+                });
+                break;
+            }
+
+            pattern.elements.push(self.parse_pattern()?);
+
+            if !matches!(self.peek_token.token_type, TokenType::RBracket)
+                && !self.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenType::RBracket) {
+            return None;
+        }
+        return Some(patterns::Pattern::Array(pattern));
+    }
+
+    fn parse_hash_pattern(&mut self) -> Option<patterns::Pattern> {
+        let mut pattern = patterns::HashPattern {
+            token: self.current_token.clone(),
+            entries: vec![],
+        };
+
+        while !matches!(self.peek_token.token_type, TokenType::RBrace) {
+            if !self.expect_peek(TokenType::Ident) {
+                return None;
+            }
+            let key = expressions::Identifier {
+                token: self.current_token.clone(),
+                value: self.current_token.symbol(),
+                slot: Cell::new(None),
+            };
+
+            let entry = if matches!(self.peek_token.token_type, TokenType::Colon) {
+                self.next_token();
+                self.next_token();
+                patterns::HashPatternEntry {
+                    key: key.value.to_string(),
+                    pattern: self.parse_pattern()?,
+                }
+            } else {
+                patterns::HashPatternEntry {
+                    key: key.value.to_string(),
+                    pattern: patterns::Pattern::Identifier(key),
+                }
+            };
+            pattern.entries.push(entry);
+
+            if !matches!(self.peek_token.token_type, TokenType::RBrace)
+                && !self.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenType::RBrace) {
+            return None;
+        }
+        return Some(patterns::Pattern::Hash(pattern));
+    }
+
     fn parse_return_statement(&mut self) -> Option<Box<dyn Statement>> {
         let mut statement = statements::ReturnStatement {
             token: Token::new(TokenType::Return, None),
@@ -420,6 +950,347 @@ impl Parser {
         return Some(Box::new(statement));
     }
 
+    fn parse_for_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        if !self.expect_peek(TokenType::LParen) {
+            return None;
+        }
+        self.next_token();
+        let pattern = self.parse_pattern()?;
+        if !self.expect_peek(TokenType::In) {
+            return None;
+        }
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::LOWEST);
+        if iterable.is_none() {
+            self.errors.push(format!(
+                "Expected an expression to iterate over but got {} instead",
+                self.current_token
+            ));
+            return None;
+        }
+        if !self.expect_peek(TokenType::RParen) || !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement()?;
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::ForStatement {
+            token,
+            pattern,
+            iterable,
+            body,
+        }));
+    }
+
+    // `yield;` yields null.
+    fn parse_yield_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        let mut value = None;
+        if self.peek_token.token_type != TokenType::Semicolon {
+            self.next_token();
+            value = self.parse_expression(Precedence::LOWEST);
+            if value.is_none() {
+                self.errors.push(format!(
+                    "Expected an expression to yield but got {} instead",
+                    self.current_token
+                ));
+                return None;
+            }
+        }
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::YieldStatement { token, value }));
+    }
+
+    fn parse_throw_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::LOWEST);
+        if value.is_none() {
+            self.errors.push(format!(
+                "Expected an expression to throw but got {} instead",
+                self.current_token
+            ));
+            return None;
+        }
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::ThrowStatement { token, value }));
+    }
+
+    fn parse_import_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        if !self.expect_peek(TokenType::String) {
+            return None;
+        }
+        let path = self.current_token.literal.clone();
+        if !self.expect_peek(TokenType::As) || !self.expect_peek(TokenType::Ident) {
+            return None;
+        }
+        let alias = expressions::Identifier {
+            token: self.current_token.clone(),
+            value: self.current_token.symbol(),
+            slot: Cell::new(None),
+        };
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::ImportStatement { token, path, alias }));
+    }
+
+    // Only `let`, `struct`, `enum` and `class` can be exported.
+    fn parse_export_statement(&mut self) -> Option<Box<dyn Statement>> {
+        if self.peek_token.token_type == TokenType::Class {
+            self.next_token();
+            let statement = self.parse_class_statement()?;
+            let mut statement = statement
+                .into_any()
+                .downcast::<statements::ClassStatement>()
+                .ok()?;
+            statement.exported = true;
+            return Some(statement);
+        }
+        if self.peek_token.token_type == TokenType::Enum {
+            self.next_token();
+            let statement = self.parse_enum_statement()?;
+            let mut statement = statement
+                .into_any()
+                .downcast::<statements::EnumStatement>()
+                .ok()?;
+            statement.exported = true;
+            return Some(statement);
+        }
+        if self.peek_token.token_type == TokenType::Struct {
+            self.next_token();
+            let statement = self.parse_struct_statement()?;
+            let mut statement = statement
+                .into_any()
+                .downcast::<statements::StructStatement>()
+                .ok()?;
+            statement.exported = true;
+            return Some(statement);
+        }
+        if !self.expect_peek(TokenType::Let) {
+            return None;
+        }
+        let statement = self.parse_let_statement()?;
+        let mut statement = statement
+            .into_any()
+            .downcast::<statements::LetStatement>()
+            .ok()?;
+        statement.exported = true;
+        return Some(statement);
+    }
+
+    fn parse_struct_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        if !self.expect_peek(TokenType::Ident) {
+            return None;
+        }
+        let name = expressions::Identifier {
+            token: self.current_token.clone(),
+            value: self.current_token.symbol(),
+            slot: Cell::new(None),
+        };
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        let owner = format!("struct {}", name.value);
+        let fields = self.parse_field_names(TokenType::RBrace, &owner)?;
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::StructStatement {
+            token,
+            name,
+            fields,
+            exported: false,
+        }));
+    }
+
+    fn parse_class_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        if !self.expect_peek(TokenType::Ident) {
+            return None;
+        }
+        let name = expressions::Identifier {
+            token: self.current_token.clone(),
+            value: self.current_token.symbol(),
+            slot: Cell::new(None),
+        };
+        let mut superclass = None;
+        if self.peek_token.token_type == TokenType::Extends {
+            self.next_token();
+            if !self.expect_peek(TokenType::Ident) {
+                return None;
+            }
+            superclass = Some(expressions::Identifier {
+                token: self.current_token.clone(),
+                value: self.current_token.symbol(),
+                slot: Cell::new(None),
+            });
+        }
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+
+        let mut methods: Vec<statements::ClassMethod> = vec![];
+        while self.peek_token.token_type != TokenType::RBrace {
+            if !self.expect_peek(TokenType::Function) {
+                return None;
+            }
+            let generator = self.peek_token.token_type == TokenType::Asterisk;
+            if generator {
+                self.next_token();
+            }
+            if !self.expect_peek(TokenType::Ident) {
+                return None;
+            }
+            let method = expressions::Identifier {
+                token: self.current_token.clone(),
+                value: self.current_token.symbol(),
+                slot: Cell::new(None),
+            };
+            if methods
+                .iter()
+                .any(|existing| existing.name.value == method.value)
+            {
+                self.errors.push(format!(
+                    "Duplicate method {} in class {}",
+                    method.value, name.value
+                ));
+                return None;
+            }
+            if !self.expect_peek(TokenType::LParen) {
+                return None;
+            }
+            let (parameters, rest) = self.parse_function_params()?;
+            if !self.expect_peek(TokenType::LBrace) {
+                return None;
+            }
+            let body = self.parse_block_statement()?;
+            methods.push(statements::ClassMethod {
+                name: method,
+                function: expressions::FunctionLiteral {
+                    token: Token::new(TokenType::Function, None),
+                    parameters: Some(Rc::new(parameters)),
+                    rest,
+                    body: Some(Rc::new(body)),
+                    locals: Cell::new(0),
+                    generator,
+                },
+            });
+        }
+        self.next_token();
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::ClassStatement {
+            token,
+            name,
+            superclass,
+            methods,
+            exported: false,
+        }));
+    }
+
+    fn parse_enum_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.current_token.clone();
+        if !self.expect_peek(TokenType::Ident) {
+            return None;
+        }
+        let name = expressions::Identifier {
+            token: self.current_token.clone(),
+            value: self.current_token.symbol(),
+            slot: Cell::new(None),
+        };
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+
+        let mut variants: Vec<statements::Variant> = vec![];
+        while self.peek_token.token_type != TokenType::RBrace {
+            if !self.expect_peek(TokenType::Ident) {
+                return None;
+            }
+            let variant = expressions::Identifier {
+                token: self.current_token.clone(),
+                value: self.current_token.symbol(),
+                slot: Cell::new(None),
+            };
+            if variants
+                .iter()
+                .any(|existing| existing.name.value == variant.value)
+            {
+                self.errors.push(format!(
+                    "Duplicate variant {} in enum {}",
+                    variant.value, name.value
+                ));
+                return None;
+            }
+            let mut fields = vec![];
+            if self.peek_token.token_type == TokenType::LParen {
+                self.next_token();
+                let owner = format!("variant {}.{}", name.value, variant.value);
+                fields = self.parse_field_names(TokenType::RParen, &owner)?;
+            }
+            variants.push(statements::Variant {
+                name: variant,
+                fields,
+            });
+            if self.peek_token.token_type != TokenType::RBrace
+                && !self.expect_peek(TokenType::Comma)
+            {
+                return None;
+            }
+        }
+        self.next_token();
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::EnumStatement {
+            token,
+            name,
+            variants,
+            exported: false,
+        }));
+    }
+
+    // A comma separated list of distinct names, up to and including `end`.
+    fn parse_field_names(
+        &mut self,
+        end: TokenType,
+        owner: &str,
+    ) -> Option<Vec<expressions::Identifier>> {
+        let mut fields: Vec<expressions::Identifier> = vec![];
+        while self.peek_token.token_type != end {
+            if !self.expect_peek(TokenType::Ident) {
+                return None;
+            }
+            let field = self.current_token.symbol();
+            if fields.iter().any(|existing| existing.value == field) {
+                self.errors
+                    .push(format!("Duplicate field {} in {}", field, owner));
+                return None;
+            }
+            fields.push(expressions::Identifier {
+                token: self.current_token.clone(),
+                value: field,
+                slot: Cell::new(None),
+            });
+            if self.peek_token.token_type != end && !self.expect_peek(TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+        return Some(fields);
+    }
+
     fn parse_expression_statement(&mut self) -> Option<Box<dyn Statement>> {
         let mut statement = statements::ExpressionStatement {
             token: self.current_token.clone(),
@@ -429,6 +1300,9 @@ impl Parser {
         if expression.is_none() {
             return None;
         }
+        if self.peek_token.token_type == TokenType::Assign {
+            return self.parse_assign_statement(expression?);
+        }
         statement.expression = expression;
 
         if TokenType::Semicolon == self.peek_token.token_type {
@@ -438,6 +1312,37 @@ impl Parser {
         return Some(Box::new(statement));
     }
 
+    // `x.y = z`, which is only written as a statement.
+    fn parse_assign_statement(
+        &mut self,
+        target: Box<dyn Expression>,
+    ) -> Option<Box<dyn Statement>> {
+        let member = match target.into_any().downcast::<expressions::Member>() {
+            Ok(member) => member,
+            Err(_) => {
+                self.errors
+                    .push("Only a field such as x.y can be assigned".to_string());
+                return None;
+            }
+        };
+        self.next_token();
+        let token = self.current_token.clone();
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST);
+        if value.is_none() {
+            return None;
+        }
+        if self.peek_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        return Some(Box::new(statements::AssignStatement {
+            token,
+            object: member.object,
+            property: member.property,
+            value,
+        }));
+    }
+
     fn parse_block_statement(&mut self) -> Option<statements::BlockStatement> {
         let mut block_statement = statements::BlockStatement {
             token: self.current_token.clone(),
@@ -485,6 +1390,7 @@ impl Parser {
     fn next_token(&mut self) {
         self.current_token = self.peek_token.clone();
         self.peek_token = self.lexer.next_token();
+        self.errors.append(&mut self.lexer.errors);
         return ();
     }
 }
@@ -1,7 +1,10 @@
-use evaluator::{eval, Environment};
+use compiler::Compiler;
+use evaluator::{resolve, Environment, Evaluator, FileLoader, ModuleLoader, Object};
 use lexer::Lexer;
+use optimizer::optimize;
 use parser::Parser;
-use std::fs;
+use std::{env, fs};
+use vm::Vm;
 
 fn main() {
     let source_file = "file.lang";
@@ -24,7 +27,63 @@ fn main() {
         return;
     }
 
-    let env = Environment::new();
-    let evaluated = eval(&program, env);
+    // Undefined variables are reported before the optimizer can drop the
+    // dead code they are in.
+    let environment = Environment::new();
+    match resolve(&program, &environment) {
+        Ok(warnings) => {
+            for warning in warnings.iter() {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        Err(message) => {
+            println!("ERROR: {}", message);
+            return;
+        }
+    }
+
+    // The optimizer is on by default, `--no-opt` runs the program as written.
+    let program = match env::args().any(|arg| arg == "--no-opt") {
+        true => program,
+        false => optimize(program),
+    };
+
+    // Runs the program on the bytecode VM instead of the tree-walker.
+    if env::args().any(|arg| arg == "--vm") {
+        let mut compiler = Compiler::new();
+        let bytecode = compiler.compile(&program);
+        if !compiler.errors.is_empty() {
+            eprintln!("Compiler errors:");
+            for error in compiler.errors.iter() {
+                eprintln!(" - {}", error);
+            }
+            return;
+        }
+        println!("{}", Vm::new().run(&bytecode));
+        return;
+    }
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_module_id(FileLoader.resolve(source_file, None).ok());
+    let evaluated = evaluator.eval(&program, environment);
     println!("{}", evaluated);
+    // Runtime errors are followed by where they were raised and the calls
+    // they propagated out of, innermost first. A run of calls from the same
+    // place, as deep recursion makes, is printed once with its count.
+    if let Object::Error(error) = &evaluated {
+        if let Some(span) = error.span {
+            println!("  at {}", span);
+        }
+        let mut frames = error.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            let mut count = 1;
+            while frames.next_if_eq(&frame).is_some() {
+                count += 1;
+            }
+            match count {
+                1 => println!("  in {}", frame),
+                _ => println!("  in {} (x{})", frame, count),
+            }
+        }
+    }
 }
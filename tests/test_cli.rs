@@ -0,0 +1,36 @@
+use std::fs;
+use std::process::Command;
+
+// Runs the interpreter on `input` as the `file.lang` of a directory of its
+// own, and returns what it printed.
+fn run_file(name: &str, input: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("interpreter-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("file.lang"), input).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .current_dir(&dir)
+        .output()
+        .expect("Couldn't run the interpreter");
+    fs::remove_dir_all(&dir).unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn cli_prints_result() {
+    assert_eq!(run_file("result", "let x = 2; x * 21;"), "42\n");
+}
+
+#[test]
+fn cli_collapses_recursion_trace() {
+    let input = "let f = fn(n) { 1 + f(n + 1) };\nlet g = fn() { f(0) };\ng();\n";
+    let expected = [
+        "ERROR: maximum recursion depth exceeded (limit 400): f (at 2:17) -> f (at 1:22) x400",
+        "  at 1:22",
+        "  in f (at 1:22) (x399)",
+        "  in f (at 2:17)",
+    ];
+    assert_eq!(
+        run_file("recursion", input).lines().collect::<Vec<_>>(),
+        expected
+    );
+}
@@ -0,0 +1,580 @@
+use ast::ast::{Expression, Statement};
+use ast::{expressions, patterns, statements};
+use compiler::Compiler;
+use evaluator::{eval, Environment};
+use lexer::{Lexer, Symbol, Token, TokenType};
+use parser::Parser;
+use std::cell::Cell;
+use std::fs;
+use std::rc::Rc;
+use vm::Vm;
+
+const CORPUS: &str = "tests/corpus";
+
+fn parse_input(input: &str) -> statements::ProgramStatement {
+    let lex = Lexer::new(input.bytes().collect());
+    let mut parser = Parser::new(lex);
+    let program = parser.parse();
+    if !parser.errors.is_empty() {
+        panic!("Parser errors: {:?}", parser.errors);
+    }
+    program
+}
+
+// The error's category, such as "type mismatch", without the details.
+// `None` when the program produced a value.
+fn error_kind(output: &str) -> Option<String> {
+    if output.starts_with("INTERRUPTED:") {
+        return Some("interrupted".to_string());
+    }
+    let message = output.strip_prefix("ERROR: ")?;
+    let kind = message.split(':').next().unwrap_or(message);
+    let kind = match kind.find(" in call to ") {
+        Some(end) => &kind[..end],
+        None => kind,
+    };
+    Some(kind.to_string())
+}
+
+// Runs the program on both engines and checks that they agree.
+fn check(program: &statements::ProgramStatement, name: &str) {
+    let evaluated = eval(program, Environment::new()).to_string();
+
+    let mut compiler = Compiler::new();
+    let bytecode = compiler.compile(program);
+    let executed = match compiler.errors.first() {
+        None => Vm::new().run(&bytecode).to_string(),
+        // Both engines reject a variable that is bound nowhere before
+        // running anything.
+        Some(error) if error.starts_with("identifier not found") => format!("ERROR: {}", error),
+        Some(_) => panic!("{}: compiler errors: {:?}", name, compiler.errors),
+    };
+
+    assert_eq!(
+        error_kind(&executed),
+        error_kind(&evaluated),
+        "{}: error kinds differ",
+        name
+    );
+    assert_eq!(executed, evaluated, "{}: outputs differ", name);
+}
+
+// Deep recursion in the evaluator needs the main thread's stack.
+fn on_large_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn differential_error_kind() {
+    assert_eq!(error_kind("5"), None);
+    assert_eq!(
+        error_kind("ERROR: type mismatch: INTEGER + STRING"),
+        Some("type mismatch".to_string())
+    );
+    assert_eq!(
+        error_kind("ERROR: wrong number of arguments in call to add: expected 2, got 1"),
+        Some("wrong number of arguments".to_string())
+    );
+    assert_eq!(
+        error_kind("INTERRUPTED: out of fuel"),
+        Some("interrupted".to_string())
+    );
+}
+
+#[test]
+fn differential_corpus() {
+    on_large_stack(|| {
+        let mut paths: Vec<_> = fs::read_dir(CORPUS)
+            .expect("Couldn't read the corpus")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lang"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths.iter() {
+            let input = fs::read_to_string(path).expect("Couldn't open file");
+            check(&parse_input(&input), &path.display().to_string());
+        }
+    });
+}
+
+#[test]
+fn differential_generated() {
+    on_large_stack(|| {
+        for seed in 1..=300 {
+            let program = Generator::new(seed).program();
+            check(&program, &format!("generated program, seed {}", seed));
+        }
+    });
+}
+
+// xorshift64, so that a failing seed can be replayed anywhere.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+// Largest integer any generated expression can produce, which keeps the
+// programs clear of overflow.
+const MAX_INT: i64 = 1_000_000_000;
+// What generated functions assume about their arguments.
+const MAX_ARG: i64 = 1_000;
+
+#[derive(Clone)]
+enum Type {
+    // An integer no larger than this in magnitude.
+    Int(i64),
+    Bool,
+    Str,
+    IntArray(usize, i64),
+    // A function taking this many integers and returning the type.
+    Func(usize, Box<Type>),
+}
+
+// Builds well-typed programs out of AST nodes: every operator is applied
+// to operands it accepts, every call passes the right number of
+// arguments, and no program loops forever, divides by zero or overflows.
+// A few end on a deliberate error so that errors are compared too.
+struct Generator {
+    rng: Rng,
+    scope: Vec<(String, Type)>,
+    names: usize,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Generator {
+        Generator {
+            rng: Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1),
+            scope: Vec::new(),
+            names: 0,
+        }
+    }
+
+    fn fresh_name(&mut self) -> String {
+        self.names += 1;
+        format!("v{}", self.names)
+    }
+
+    fn program(&mut self) -> statements::ProgramStatement {
+        let mut body: Vec<Box<dyn Statement>> = Vec::new();
+        for _ in 0..1 + self.rng.below(6) {
+            let (name, value, ty) = self.binding(3);
+            body.push(let_statement(&name, value));
+            self.scope.push((name, ty));
+        }
+
+        let mut results = Vec::new();
+        for _ in 0..1 + self.rng.below(4) {
+            let ty = self.any_type();
+            results.push(self.expression(&ty, 3));
+        }
+        if self.rng.chance(10) {
+            results.push(self.error());
+        }
+        body.push(expression_statement(array(results)));
+        statements::ProgramStatement { body }
+    }
+
+    fn any_type(&mut self) -> Type {
+        match self.rng.below(5) {
+            0 => Type::Bool,
+            1 => Type::Str,
+            2 => Type::IntArray(1 + self.rng.below(3), MAX_ARG),
+            _ => Type::Int(MAX_INT),
+        }
+    }
+
+    fn binding(&mut self, depth: usize) -> (String, Box<dyn Expression>, Type) {
+        let name = self.fresh_name();
+        if self.rng.chance(40) {
+            let (value, ty) = self.function(depth);
+            return (name, value, ty);
+        }
+        let ty = self.any_type();
+        let (value, ty) = match ty {
+            Type::Int(_) => {
+                let (value, bound) = self.int(MAX_INT, depth);
+                (value, Type::Int(bound))
+            }
+            ty => (self.expression(&ty, depth), ty),
+        };
+        (name, value, ty)
+    }
+
+    // A function over integers, possibly returning another one that
+    // closes over its parameters.
+    fn function(&mut self, depth: usize) -> (Box<dyn Expression>, Type) {
+        let arity = self.rng.below(3);
+        let params: Vec<String> = (0..arity).map(|_| self.fresh_name()).collect();
+        let saved = self.scope.len();
+        for param in params.iter() {
+            self.scope.push((param.clone(), Type::Int(MAX_ARG)));
+        }
+
+        let mut body: Vec<Box<dyn Statement>> = Vec::new();
+        for _ in 0..self.rng.below(3) {
+            let name = self.fresh_name();
+            let (value, bound) = self.int(MAX_INT, depth.saturating_sub(1));
+            body.push(let_statement(&name, value));
+            self.scope.push((name, Type::Int(bound)));
+        }
+        let (result, ty) = if depth > 1 && self.rng.chance(30) {
+            self.function(depth - 1)
+        } else {
+            let (result, bound) = self.int(MAX_INT, depth.saturating_sub(1));
+            (result, Type::Int(bound))
+        };
+        body.push(expression_statement(result));
+
+        self.scope.truncate(saved);
+        (function(&params, body), Type::Func(arity, Box::new(ty)))
+    }
+
+    fn variable(&mut self, matches: impl Fn(&Type) -> bool) -> Option<(String, Type)> {
+        let candidates: Vec<&(String, Type)> =
+            self.scope.iter().filter(|(_, ty)| matches(ty)).collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let (name, ty) = candidates[self.rng.below(candidates.len())];
+        Some((name.clone(), ty.clone()))
+    }
+
+    fn expression(&mut self, ty: &Type, depth: usize) -> Box<dyn Expression> {
+        match ty {
+            Type::Int(max) => self.int(*max, depth).0,
+            Type::Bool => self.boolean(depth),
+            Type::Str => self.string(depth),
+            Type::IntArray(len, max) => {
+                let elements = (0..*len).map(|_| self.int(*max, depth).0).collect();
+                array(elements)
+            }
+            Type::Func(..) => self.function(depth).0,
+        }
+    }
+
+    // An integer expression and the largest magnitude it can have, which
+    // is at most `max`.
+    fn int(&mut self, max: i64, depth: usize) -> (Box<dyn Expression>, i64) {
+        let (expression, bound) = self.int_unchecked(max, depth);
+        if bound <= max {
+            return (expression, bound);
+        }
+        let value = self.rng.below(max.min(20) as usize + 1) as i64;
+        (integer(value), value)
+    }
+
+    fn int_unchecked(&mut self, max: i64, depth: usize) -> (Box<dyn Expression>, i64) {
+        if depth == 0 {
+            return match self.variable(|ty| matches!(ty, Type::Int(_))) {
+                Some((name, Type::Int(bound))) if self.rng.chance(50) => (identifier(&name), bound),
+                _ => {
+                    let value = self.rng.below(21) as i64;
+                    (integer(value), value)
+                }
+            };
+        }
+
+        match self.rng.below(9) {
+            0 => {
+                let (left, l) = self.int(max, depth - 1);
+                let (right, r) = self.int(max, depth - 1);
+                (infix("+", left, right), l + r)
+            }
+            1 => {
+                let (left, l) = self.int(max, depth - 1);
+                let (right, r) = self.int(max, depth - 1);
+                (infix("-", left, right), l + r)
+            }
+            2 => {
+                let (left, l) = self.int(max, depth - 1);
+                let (right, r) = self.int(max, depth - 1);
+                (infix("*", left, right), l.saturating_mul(r))
+            }
+            3 => {
+                let (left, l) = self.int(max, depth - 1);
+                let divisor = 1 + self.rng.below(5) as i64;
+                (infix("/", left, integer(divisor)), l)
+            }
+            4 => {
+                let (right, r) = self.int(max, depth - 1);
+                (prefix("-", right), r)
+            }
+            5 => {
+                let condition = self.boolean(depth - 1);
+                let (first, f) = self.int(max, depth - 1);
+                let (second, s) = self.int(max, depth - 1);
+                (
+                    if_expression(condition, vec![expression_statement(first)], second),
+                    f.max(s),
+                )
+            }
+            6 => match self.variable(|ty| matches!(ty, Type::IntArray(_, _))) {
+                Some((name, Type::IntArray(len, bound))) => {
+                    let i = self.rng.below(len) as i64;
+                    (index(identifier(&name), integer(i)), bound)
+                }
+                _ => self.int_unchecked(max, 0),
+            },
+            7 => match self.variable(|ty| returns_int(ty).is_some()) {
+                Some((name, ty)) => self.call(identifier(&name), &ty, depth),
+                None => self.int_unchecked(max, 0),
+            },
+            _ => self.int_unchecked(max, 0),
+        }
+    }
+
+    // Calls the function, and then whatever it returns, until there is an
+    // integer.
+    fn call(
+        &mut self,
+        callee: Box<dyn Expression>,
+        ty: &Type,
+        depth: usize,
+    ) -> (Box<dyn Expression>, i64) {
+        match ty {
+            Type::Func(arity, ret) => {
+                let args = (0..*arity)
+                    .map(|_| self.int(MAX_ARG, depth - 1).0)
+                    .collect();
+                self.call(call(callee, args), ret, depth)
+            }
+            Type::Int(bound) => (callee, *bound),
+            _ => unreachable!("only functions returning integers are called"),
+        }
+    }
+
+    fn boolean(&mut self, depth: usize) -> Box<dyn Expression> {
+        if depth == 0 {
+            return boolean(self.rng.chance(50));
+        }
+        match self.rng.below(5) {
+            0 => {
+                let operator = ["<", ">", "==", "!="][self.rng.below(4)];
+                let left = self.int(MAX_INT, depth - 1).0;
+                let right = self.int(MAX_INT, depth - 1).0;
+                infix(operator, left, right)
+            }
+            1 => prefix("!", self.boolean(depth - 1)),
+            2 => {
+                let operator = ["==", "!="][self.rng.below(2)];
+                let left = self.boolean(depth - 1);
+                let right = self.boolean(depth - 1);
+                infix(operator, left, right)
+            }
+            3 => match self.variable(|ty| matches!(ty, Type::Bool)) {
+                Some((name, _)) => identifier(&name),
+                None => boolean(true),
+            },
+            _ => boolean(self.rng.chance(50)),
+        }
+    }
+
+    fn string(&mut self, depth: usize) -> Box<dyn Expression> {
+        if depth == 0 || self.rng.chance(30) {
+            return string(["", "a", "bc", "def"][self.rng.below(4)]);
+        }
+        match self.variable(|ty| matches!(ty, Type::Str)) {
+            Some((name, _)) if self.rng.chance(50) => identifier(&name),
+            _ => {
+                let left = self.string(depth - 1);
+                let right = self.string(depth - 1);
+                infix("+", left, right)
+            }
+        }
+    }
+
+    fn error(&mut self) -> Box<dyn Expression> {
+        match self.rng.below(4) {
+            0 => infix("+", integer(1), boolean(true)),
+            1 => infix("-", string("a"), string("b")),
+            2 => identifier("undefined"),
+            _ => call(function(&[], vec![]), vec![integer(1)]),
+        }
+    }
+}
+
+fn returns_int(ty: &Type) -> Option<i64> {
+    match ty {
+        Type::Int(bound) => Some(*bound),
+        Type::Func(_, ret) => returns_int(ret),
+        _ => None,
+    }
+}
+
+fn token(token_type: TokenType, literal: &str) -> Token {
+    Token::new(token_type, Some(literal.to_string()))
+}
+
+fn identifier(name: &str) -> Box<dyn Expression> {
+    Box::new(ident(name))
+}
+
+fn ident(name: &str) -> expressions::Identifier {
+    let symbol = Symbol::intern(name);
+    expressions::Identifier {
+        token: Token::identifier(symbol),
+        value: symbol,
+        slot: Cell::new(None),
+    }
+}
+
+fn integer(value: i64) -> Box<dyn Expression> {
+    Box::new(expressions::IntegerLiteral {
+        token: token(TokenType::Int, &value.to_string()),
+        value,
+    })
+}
+
+fn boolean(value: bool) -> Box<dyn Expression> {
+    let token_type = match value {
+        true => TokenType::True,
+        false => TokenType::False,
+    };
+    Box::new(expressions::Bool {
+        token: token(token_type, &value.to_string()),
+        value,
+    })
+}
+
+fn string(value: &str) -> Box<dyn Expression> {
+    Box::new(expressions::StringLiteral {
+        token: token(TokenType::String, value),
+        value: value.to_string(),
+    })
+}
+
+fn array(elements: Vec<Box<dyn Expression>>) -> Box<dyn Expression> {
+    Box::new(expressions::ArrayLiteral {
+        token: token(TokenType::LBracket, "["),
+        elements,
+    })
+}
+
+fn index(left: Box<dyn Expression>, index: Box<dyn Expression>) -> Box<dyn Expression> {
+    Box::new(expressions::IndexExpression {
+        token: token(TokenType::LBracket, "["),
+        left: Some(left),
+        index: Some(index),
+    })
+}
+
+fn prefix(operator: &str, right: Box<dyn Expression>) -> Box<dyn Expression> {
+    let token_type = match operator {
+        "!" => TokenType::Bang,
+        _ => TokenType::Minus,
+    };
+    Box::new(expressions::Prefix {
+        token: token(token_type, operator),
+        operator: operator.to_string(),
+        right: Some(right),
+    })
+}
+
+fn infix(
+    operator: &str,
+    left: Box<dyn Expression>,
+    right: Box<dyn Expression>,
+) -> Box<dyn Expression> {
+    let token_type = match operator {
+        "+" => TokenType::Plus,
+        "-" => TokenType::Minus,
+        "*" => TokenType::Asterisk,
+        "/" => TokenType::Slash,
+        "<" => TokenType::LT,
+        ">" => TokenType::RT,
+        "==" => TokenType::Eq,
+        _ => TokenType::NotEq,
+    };
+    Box::new(expressions::Infix {
+        token: token(token_type, operator),
+        operator: operator.to_string(),
+        left: Some(left),
+        right: Some(right),
+    })
+}
+
+fn block(body: Vec<Box<dyn Statement>>) -> statements::BlockStatement {
+    statements::BlockStatement {
+        token: token(TokenType::LBrace, "{"),
+        statements: body.into_iter().map(Some).collect(),
+    }
+}
+
+fn if_expression(
+    condition: Box<dyn Expression>,
+    first: Vec<Box<dyn Statement>>,
+    second: Box<dyn Expression>,
+) -> Box<dyn Expression> {
+    Box::new(expressions::If {
+        token: token(TokenType::If, "if"),
+        condition: Some(condition),
+        first: Some(block(first)),
+        second: Some(block(vec![expression_statement(second)])),
+    })
+}
+
+fn function(params: &[String], body: Vec<Box<dyn Statement>>) -> Box<dyn Expression> {
+    let parameters = params
+        .iter()
+        .map(|name| patterns::Parameter {
+            pattern: patterns::Pattern::Identifier(ident(name)),
+            default: None,
+        })
+        .collect();
+    Box::new(expressions::FunctionLiteral {
+        token: token(TokenType::Function, "fn"),
+        parameters: Some(Rc::new(parameters)),
+        rest: None,
+        body: Some(Rc::new(block(body))),
+        locals: Cell::new(0),
+        generator: false,
+    })
+}
+
+fn call(function: Box<dyn Expression>, arguments: Vec<Box<dyn Expression>>) -> Box<dyn Expression> {
+    Box::new(expressions::CallExpression {
+        token: token(TokenType::LParen, "("),
+        function: Some(function),
+        arguments: Some(arguments),
+        named_arguments: Vec::new(),
+    })
+}
+
+fn let_statement(name: &str, value: Box<dyn Expression>) -> Box<dyn Statement> {
+    Box::new(statements::LetStatement {
+        token: token(TokenType::Let, "let"),
+        pattern: patterns::Pattern::Identifier(ident(name)),
+        value: Some(value),
+        exported: false,
+    })
+}
+
+fn expression_statement(expression: Box<dyn Expression>) -> Box<dyn Statement> {
+    Box::new(statements::ExpressionStatement {
+        token: token(TokenType::Ident, ""),
+        expression: Some(expression),
+    })
+}
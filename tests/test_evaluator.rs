@@ -0,0 +1,1848 @@
+use evaluator::{
+    eval, resolve, CancellationToken, Environment, ErrorKind, Evaluator, Interrupt, LoadError,
+    MemoryLoader, ModuleId, ModuleLoader, Object, DEFAULT_MAX_DEPTH,
+};
+use lexer::{Lexer, Span};
+use parser::Parser;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+fn parse_input(input: &str) -> ast::statements::ProgramStatement {
+    let lex = Lexer::new(input.bytes().collect());
+    let mut parser = Parser::new(lex);
+    let program = parser.parse();
+    if !parser.errors.is_empty() {
+        panic!("Parser errors: {:?}", parser.errors);
+    }
+    program
+}
+
+fn eval_input(input: &str) -> Object {
+    eval(&parse_input(input), Environment::new())
+}
+
+#[test]
+fn eval_collections() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: r#""foo" + "bar";"#.to_string(),
+            expected: "foobar".to_string(),
+        },
+        TestInput {
+            input: "[1, 2 * 2, 3 + 3];".to_string(),
+            expected: "[1, 4, 6]".to_string(),
+        },
+        TestInput {
+            input: "[1, 2, 3][1];".to_string(),
+            expected: "2".to_string(),
+        },
+        TestInput {
+            input: "[1, 2, 3][3];".to_string(),
+            expected: "null".to_string(),
+        },
+        TestInput {
+            input: r#"let h = {"b": 2, "a": 1, true: 3}; h["a"] + h[true];"#.to_string(),
+            expected: "4".to_string(),
+        },
+        TestInput {
+            input: r#"{"b": 2, "a": 1};"#.to_string(),
+            expected: "{a: 1, b: 2}".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_let_destructuring() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let [a, b] = [1, 2]; a + b;".to_string(),
+            expected: "3".to_string(),
+        },
+        TestInput {
+            input: "let [first, ...rest] = [1, 2, 3]; rest;".to_string(),
+            expected: "[2, 3]".to_string(),
+        },
+        TestInput {
+            input: "let [...all] = []; all;".to_string(),
+            expected: "[]".to_string(),
+        },
+        TestInput {
+            input: r#"let {name, age} = {"name": "ada", "age": 36}; name;"#.to_string(),
+            expected: "ada".to_string(),
+        },
+        TestInput {
+            input: r#"let {name: {first}, tags: [tag, ...tags]} = {"name": {"first": "ada"}, "tags": [1, 2]}; [first, tag, tags];"#.to_string(),
+            expected: "[ada, 1, [2]]".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_let_destructuring_errors() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let [a, b] = [1, 2, 3];".to_string(),
+            expected: "ERROR: cannot destructure: expected array of 2 elements, got array of 3 elements".to_string(),
+        },
+        TestInput {
+            input: "let [a, b, ...rest] = [1];".to_string(),
+            expected: "ERROR: cannot destructure: expected array of at least 2 elements, got array of 1 elements".to_string(),
+        },
+        TestInput {
+            input: "let [a] = 5;".to_string(),
+            expected: "ERROR: cannot destructure: expected array of 1 elements, got INTEGER".to_string(),
+        },
+        TestInput {
+            input: r#"let {name, age} = {"name": "ada"};"#.to_string(),
+            expected: r#"ERROR: cannot destructure: expected hash with keys "name", "age", got hash without key "age""#.to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_function_parameters() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let add = fn(x, y) { x + y }; add(1, 2);".to_string(),
+            expected: "3".to_string(),
+        },
+        TestInput {
+            input: "let add = fn(x, y = 10) { x + y }; add(1);".to_string(),
+            expected: "11".to_string(),
+        },
+        TestInput {
+            input: "let add = fn(x, y = x * 2) { x + y }; add(3);".to_string(),
+            expected: "9".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(first, ...rest) { rest }; f(1, 2, 3);".to_string(),
+            expected: "[2, 3]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(first, ...rest) { rest }; f(1);".to_string(),
+            expected: "[]".to_string(),
+        },
+        TestInput {
+            input: "let sub = fn(x, y) { x - y }; sub(y: 2, x: 1);".to_string(),
+            expected: "-1".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x, y = 2, z = 3) { [x, y, z] }; f(1, z: 4);".to_string(),
+            expected: "[1, 2, 4]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn([a, b], {c}) { a + b + c }; f([1, 2], {\"c\": 3});".to_string(),
+            expected: "6".to_string(),
+        },
+        TestInput {
+            input: "fn(x, y = 1, ...rest) { x };".to_string(),
+            expected: "fn(x, y = ..., ...rest) { ... }".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_function_parameter_errors() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let add = fn(x, y) { x + y }; add(1);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to add: expected 2, got 1"
+                .to_string(),
+        },
+        TestInput {
+            input: "let add = fn(x, y) { x + y }; add(1, 2, 3);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to add: expected 2, got 3"
+                .to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x, y = 1) { x }; f();".to_string(),
+            expected: "ERROR: wrong number of arguments in call to f: expected 1 to 2, got 0"
+                .to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x, ...rest) { x }; f();".to_string(),
+            expected: "ERROR: wrong number of arguments in call to f: expected at least 1, got 0"
+                .to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x, y = 1) { x }; f(y: 2);".to_string(),
+            expected: "ERROR: missing argument in call to f: x".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x) { x }; f(1, x: 2);".to_string(),
+            expected: "ERROR: multiple values for argument in call to f: x".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x) { x }; f(z: 2);".to_string(),
+            expected: "ERROR: unexpected keyword argument in call to f: z".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_call_site_errors() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "fn(x, y) { x + y }(1);".to_string(),
+            expected: "ERROR: wrong number of arguments: expected 2, got 1".to_string(),
+        },
+        TestInput {
+            input: "let add = fn(x, y) { x + y };\nlet sum = add(1, 2);\n  add(sum);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to add: expected 2, got 1"
+                .to_string(),
+        },
+        TestInput {
+            input: "let add = fn(x, y) { x + y }; let plus = add; plus(1);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to add: expected 2, got 1"
+                .to_string(),
+        },
+        TestInput {
+            input: "let x = 5;\nx(1);".to_string(),
+            expected: "ERROR: not a function: INTEGER".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_arithmetic_errors() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "1 / 0;".to_string(),
+            expected: "ERROR: division by zero".to_string(),
+        },
+        TestInput {
+            input: "9223372036854775807 + 1;".to_string(),
+            expected: "ERROR: integer overflow: 9223372036854775807 + 1".to_string(),
+        },
+        TestInput {
+            input: "let min = -9223372036854775807 - 1; [min / -1, -min];".to_string(),
+            expected: "ERROR: integer overflow: -9223372036854775808 / -1".to_string(),
+        },
+        TestInput {
+            input: "let min = -9223372036854775807 - 1; -min;".to_string(),
+            expected: "ERROR: integer overflow: --9223372036854775808".to_string(),
+        },
+        TestInput {
+            input: "4294967296 * 4294967296;".to_string(),
+            expected: "ERROR: integer overflow: 4294967296 * 4294967296".to_string(),
+        },
+        // They are errors like any other, so they can be caught.
+        TestInput {
+            input: "try { 1 / 0 } catch (e) { [e[\"kind\"], e[\"message\"]] };".to_string(),
+            expected: "[arithmetic error, division by zero]".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+}
+
+#[test]
+fn eval_tail_calls() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let count = fn(n, acc) { if (n == 0) { acc } else { count(n - 1, acc + 1) } }; count(100000, 0);".to_string(),
+            expected: "100000".to_string(),
+        },
+        TestInput {
+            input: "let count = fn(n) { if (n == 0) { return 0; } return count(n - 1); }; count(100000);".to_string(),
+            expected: "0".to_string(),
+        },
+        TestInput {
+            input: "let count = fn(n) { if (n > 0) { return count(n - 1); } n }; count(100000);".to_string(),
+            expected: "0".to_string(),
+        },
+        TestInput {
+            input: "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(100001);".to_string(),
+            expected: "false".to_string(),
+        },
+        TestInput {
+            input: "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);".to_string(),
+            expected: "610".to_string(),
+        },
+        TestInput {
+            input: "let id = fn(x) { x }; let f = fn() { let y = id(1); id(y + 1) }; f();".to_string(),
+            expected: "2".to_string(),
+        },
+        TestInput {
+            input: "let add = fn(x, y) { x + y }; let f = fn() { add(1) }; f();".to_string(),
+            expected: "ERROR: wrong number of arguments in call to add: expected 2, got 1".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+}
+
+#[test]
+fn eval_recursion_depth_limit() {
+    let mut evaluator = Evaluator::new();
+    evaluator.max_depth = 50;
+
+    let program =
+        parse_input("let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };\nf(40);\nf(100);");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "ERROR: maximum recursion depth exceeded (limit 50): f (at 3:2) -> f (at 1:47) x50"
+    );
+
+    let program = parse_input(
+        "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; let g = fn() { f(45) }; g();",
+    );
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "45"
+    );
+
+    let program = parse_input(
+        "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; let g = fn() { 1 + f(49) }; g();",
+    );
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "ERROR: maximum recursion depth exceeded (limit 50): g (at 1:89) -> f (at 1:80) -> f (at 1:47) x49"
+    );
+
+    let program =
+        parse_input("let count = fn(n) { if (n == 0) { 0 } else { count(n - 1) } }; count(1000);");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "0"
+    );
+}
+
+#[test]
+fn eval_default_recursion_depth_limit() {
+    // Same stack as the main thread, which the default is sized for.
+    let result = std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(|| {
+            eval_input("let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(100000);")
+                .to_string()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(
+        result,
+        format!(
+            "ERROR: maximum recursion depth exceeded (limit {}): f (at 1:61) -> f (at 1:47) x{}",
+            DEFAULT_MAX_DEPTH, DEFAULT_MAX_DEPTH
+        )
+    );
+}
+
+#[test]
+fn eval_fuel() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.fuel(), None);
+
+    let env = Environment::new();
+    evaluator.set_fuel(Some(10_000));
+    let program = parse_input("let x = 1; let forever = fn() { forever() }; forever();");
+    assert_eq!(
+        evaluator.eval(&program, env.clone()).to_string(),
+        "INTERRUPTED: out of fuel"
+    );
+    assert!(matches!(
+        evaluator.eval(&program, env.clone()),
+        Object::Interrupt(Interrupt::OutOfFuel)
+    ));
+    assert_eq!(evaluator.fuel(), Some(0));
+
+    evaluator.add_fuel(5);
+    assert_eq!(evaluator.fuel(), Some(5));
+    assert_eq!(
+        evaluator
+            .eval(&parse_input("x + 1;"), env.clone())
+            .to_string(),
+        "2"
+    );
+    assert_eq!(evaluator.fuel(), Some(1));
+
+    let program = parse_input("1 + 2 + 3;");
+    assert_eq!(
+        evaluator.eval(&program, env.clone()).to_string(),
+        "INTERRUPTED: out of fuel"
+    );
+
+    evaluator.set_fuel(None);
+    assert_eq!(evaluator.eval(&program, env).to_string(), "6");
+}
+
+#[test]
+fn eval_cancellation() {
+    let forever = parse_input("let forever = fn() { forever() }; forever();");
+
+    let token = CancellationToken::new();
+    let mut evaluator = Evaluator::new();
+    evaluator.set_cancellation_token(Some(token.clone()));
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+    assert!(matches!(
+        evaluator.eval(&forever, Environment::new()),
+        Object::Interrupt(Interrupt::Cancelled)
+    ));
+    canceller.join().unwrap();
+
+    let token = CancellationToken::with_deadline(Instant::now() + Duration::from_millis(50));
+    evaluator.set_cancellation_token(Some(token));
+    assert_eq!(
+        evaluator.eval(&forever, Environment::new()).to_string(),
+        "INTERRUPTED: deadline exceeded"
+    );
+
+    evaluator.set_cancellation_token(None);
+    assert_eq!(
+        evaluator
+            .eval(&parse_input("1 + 1;"), Environment::new())
+            .to_string(),
+        "2"
+    );
+}
+
+#[test]
+fn eval_cancelled_before_start() {
+    let token = CancellationToken::new();
+    token.cancel();
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_cancellation_token(Some(token));
+    assert_eq!(
+        evaluator
+            .eval(&parse_input("1 + 1;"), Environment::new())
+            .to_string(),
+        "INTERRUPTED: cancelled"
+    );
+}
+
+// An array literal of 1000 strings.
+fn grow_literal() -> String {
+    let elements: Vec<&str> = vec!["\"abcdefghijklmnop\""; 1000];
+    format!("[{}];", elements.join(", "))
+}
+
+#[test]
+fn eval_memory_limit() {
+    let mut evaluator = Evaluator::new();
+    evaluator.set_memory_limit(Some(64 * 1024));
+
+    let program = parse_input(r#"let grow = fn(s) { grow(s + s) }; grow("abcdefgh");"#);
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "INTERRUPTED: memory limit exceeded"
+    );
+    assert!(evaluator.memory_used() <= 64 * 1024);
+
+    // Arrays are shared, so each level only pays for its own four elements.
+    evaluator.reset_memory_used();
+    let grow =
+        "let grow = fn(xs, n) { if (n == 0) { xs } else { grow([xs, xs, xs, xs], n - 1) } };";
+    let program = parse_input(&format!("{} grow([], 100); 1;", grow));
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "1"
+    );
+    assert!(evaluator.memory_used() < 64 * 1024);
+
+    evaluator.reset_memory_used();
+    let program = parse_input(&format!("{} grow([], 10000);", grow));
+    assert!(matches!(
+        evaluator.eval(&program, Environment::new()),
+        Object::Interrupt(Interrupt::MemoryLimitExceeded)
+    ));
+
+    // Reading a variable does not copy what it holds.
+    let mut unlimited = Evaluator::new();
+    let program = parse_input(&format!("let xs = {} [xs, xs];", grow_literal()));
+    unlimited.eval(&program, Environment::new());
+    let with_one_read = unlimited.memory_used();
+    unlimited.reset_memory_used();
+    let program = parse_input(&format!(
+        "let xs = {} [xs, xs, xs, xs, xs, xs, xs, xs, xs, xs];",
+        grow_literal()
+    ));
+    unlimited.eval(&program, Environment::new());
+    let extra = unlimited.memory_used() - with_one_read;
+    assert_eq!(extra, 8 * std::mem::size_of::<Object>());
+
+    evaluator.reset_memory_used();
+    assert_eq!(evaluator.memory_used(), 0);
+    let program = parse_input(r#"let h = {"a": [1, 2, 3], "b": "xyz"}; h["b"];"#);
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "xyz"
+    );
+    assert!(evaluator.memory_used() > 0);
+
+    // Values that are dropped are credited back, so only what is alive at
+    // any one time counts against the limit.
+    evaluator.reset_memory_used();
+    let churn = r#"
+        let churn = fn(s, n) { if (n == 0) { s.len() } else { let t = s + s; churn(s, n - 1) } };
+        churn("abcdefghabcdefgh", 10000);
+    "#;
+    let program = parse_input(churn);
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "16"
+    );
+    assert!(evaluator.memory_used() <= 64 * 1024);
+
+    // Methods check the size of their result before building it.
+    evaluator.reset_memory_used();
+    let program = parse_input(&format!(
+        "let xs = {} let s = xs.join(\"\"); [s, s, s, s].join(\"-\");",
+        grow_literal()
+    ));
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "INTERRUPTED: memory limit exceeded"
+    );
+    assert!(evaluator.memory_used() <= 64 * 1024);
+}
+
+#[test]
+fn eval_garbage_collection() {
+    let leak = r#"
+        let make = fn() { let f = fn() { f }; f };
+        let leak = fn(n) { if (n == 0) { 0 } else { make(); leak(n - 1) } };
+    "#;
+
+    let mut evaluator = Evaluator::new();
+    let program = parse_input(&format!("{} leak(100); gc();", leak));
+    let collected = evaluator.eval(&program, Environment::new());
+    assert!(matches!(collected, Object::Integer(n) if n >= 100));
+    let stats = evaluator.gc_stats();
+    assert_eq!(stats.collections, 1);
+    assert!(stats.collected >= 100);
+
+    // Closures that are still reachable survive a collection.
+    let env = Environment::new();
+    let program = parse_input(
+        "let adder = fn(x) { fn(y) { x + y } }; let add_two = adder(2); gc(); add_two(3);",
+    );
+    assert_eq!(evaluator.eval(&program, env.clone()).to_string(), "5");
+
+    // Cycles through shared arrays and hashes are found as well, and
+    // closures held in an array that is still referenced survive.
+    let program = parse_input(
+        r#"let make = fn() { let xs = [fn() { xs }]; let h = {"f": fn() { h }}; 0 };
+        make(); make(); let kept = [adder(1)]; gc(); kept[0](41);"#,
+    );
+    assert_eq!(evaluator.eval(&program, env).to_string(), "42");
+    assert!(evaluator.gc_stats().collected >= 102);
+
+    // A caught error can hold a closure over the frame it is bound in.
+    let before = evaluator.gc_stats().collected;
+    let program = parse_input(
+        "let trap = fn() { try { throw fn() { 0 }; } catch (e) { 0 } }; trap(); trap(); gc();",
+    );
+    evaluator.eval(&program, Environment::new());
+    assert!(evaluator.gc_stats().collected >= before + 2);
+
+    // An instance can refer to itself through its fields, or through a
+    // method bound to it.
+    let before = evaluator.gc_stats().collected;
+    let program = parse_input(
+        "class Node { fn init() { self.me = self; self.f = self.init; } } \
+         let make = fn() { Node(); 0 }; make(); make(); let kept = Node(); gc(); kept.me == kept;",
+    );
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "true"
+    );
+    assert!(evaluator.gc_stats().collected >= before + 2);
+
+    // A cycle through the host's environment is freed once the host drops it.
+    let mut evaluator = Evaluator::new();
+    let env = Environment::new();
+    evaluator.eval(&parse_input("let f = fn() { f };"), env.clone());
+    assert_eq!(evaluator.gc(), 0);
+    drop(env);
+    assert_eq!(evaluator.gc(), 1);
+
+    // Collections also run on their own as calls create environments.
+    let mut evaluator = Evaluator::new();
+    let program = parse_input(&format!("{} leak(5000);", leak));
+    evaluator.eval(&program, Environment::new());
+    let stats = evaluator.gc_stats();
+    assert!(stats.collections > 0);
+    assert!(stats.tracked < 5000);
+
+    let program = parse_input("gc(1);");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "ERROR: wrong number of arguments in call to gc: expected 0, got 1"
+    );
+}
+
+#[test]
+fn eval_scope_resolution() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let f = fn(x) { let y = x * 2; fn(z) { x + y + z } }; f(1)(10);".to_string(),
+            expected: "13".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x) { if (x > 0) { let y = x; } y }; f(5);".to_string(),
+            expected: "5".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { let g = fn() { n }; let n = 7; g() }; f();".to_string(),
+            expected: "7".to_string(),
+        },
+        TestInput {
+            input: "let x = 1; let f = fn(x) { x }; f(2) + x;".to_string(),
+            expected: "3".to_string(),
+        },
+        TestInput {
+            input: "let f = fn([a, b], {c}, ...rest) { a + b + c + rest[0] }; f([1, 2], {\"c\": 3}, 4);".to_string(),
+            expected: "10".to_string(),
+        },
+        // Undefined variables are reported before anything runs, even in
+        // code that would never be reached.
+        TestInput {
+            input: "let f = fn() { missing }; 1;".to_string(),
+            expected: "ERROR: identifier not found: missing".to_string(),
+        },
+        TestInput {
+            input: "if (false) { missing };".to_string(),
+            expected: "ERROR: identifier not found: missing".to_string(),
+        },
+        // A local read before it is bound is the variable of the same name
+        // further out, so `let x = x + 1` shadows an outer `x`.
+        TestInput {
+            input: "let x = 1; let f = fn() { let x = x + 1; x }; [f(), x];".to_string(),
+            expected: "[2, 1]".to_string(),
+        },
+        TestInput {
+            input: "let x = 1; let f = fn() { let g = fn() { x }; let x = x + 10; g() }; f();"
+                .to_string(),
+            expected: "11".to_string(),
+        },
+        // With no such variable, the read is reported like any other.
+        TestInput {
+            input: "let f = fn() { let a = b; let b = 1; a }; f();".to_string(),
+            expected: "ERROR: identifier not found: b".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected
+        );
+    }
+
+    // Globals set by an earlier program in the same environment resolve.
+    let mut evaluator = Evaluator::new();
+    let env = Environment::new();
+    evaluator.eval(&parse_input("let x = 40;"), env.clone());
+    assert_eq!(
+        evaluator
+            .eval(&parse_input("let f = fn() { x + 2 }; f();"), env)
+            .to_string(),
+        "42"
+    );
+}
+
+#[test]
+fn eval_error_trace() {
+    let program = parse_input(
+        "let add = fn(a, b) { a + b };\nlet twice = fn(x) { add(x, true) * 2 };\nlet run = fn() { 1 + twice(1) };\nrun();",
+    );
+    let error = match eval(&program, Environment::new()) {
+        Object::Error(error) => error,
+        other => panic!("expected an error, got {}", other),
+    };
+    assert_eq!(error.kind, ErrorKind::TypeMismatch);
+    assert_eq!(error.message, "type mismatch: INTEGER + BOOLEAN");
+    assert_eq!(
+        error.span,
+        Some(Span {
+            line: 1,
+            column: 24
+        })
+    );
+    let trace: Vec<String> = error.trace.iter().map(|frame| frame.to_string()).collect();
+    assert_eq!(trace, ["add (at 2:24)", "twice (at 3:27)", "run (at 4:4)"]);
+
+    // A tail call replaces its caller's frame.
+    let program =
+        parse_input("let f = fn() { -true };\nlet g = fn() { f() };\nlet fs = [g];\nfs[0]();");
+    let error = match eval(&program, Environment::new()) {
+        Object::Error(error) => error,
+        other => panic!("expected an error, got {}", other),
+    };
+    assert_eq!(error.kind, ErrorKind::UnknownOperator);
+    assert_eq!(
+        error.span,
+        Some(Span {
+            line: 1,
+            column: 16
+        })
+    );
+    let trace: Vec<String> = error.trace.iter().map(|frame| frame.to_string()).collect();
+    assert_eq!(trace, ["f (at 2:17)"]);
+
+    let program = parse_input("let fs = [fn(x) { x }];\nfs[0](1, 2);");
+    let error = match eval(&program, Environment::new()) {
+        Object::Error(error) => error,
+        other => panic!("expected an error, got {}", other),
+    };
+    assert_eq!(error.kind, ErrorKind::Arguments);
+    assert_eq!(error.span, Some(Span { line: 2, column: 6 }));
+    let trace: Vec<String> = error.trace.iter().map(|frame| frame.to_string()).collect();
+    assert_eq!(trace, ["<anonymous> (at 2:6)"]);
+
+    // The location is in the span rather than the message.
+    let error = match eval_input("let x = 1;\nx(2);") {
+        Object::Error(error) => error,
+        other => panic!("expected an error, got {}", other),
+    };
+    assert_eq!(error.message, "not a function: INTEGER");
+    assert_eq!(error.span, Some(Span { line: 2, column: 2 }));
+
+    let error = match eval_input("let x = 1;\nlet [a, b] = [x];") {
+        Object::Error(error) => error,
+        other => panic!("expected an error, got {}", other),
+    };
+    assert_eq!(error.kind, ErrorKind::Destructure);
+    assert_eq!(error.span, Some(Span { line: 2, column: 1 }));
+
+    // Errors outside of any call have no trace.
+    let error = match eval_input("let h = {};\nh[[]];") {
+        Object::Error(error) => error,
+        other => panic!("expected an error, got {}", other),
+    };
+    assert_eq!(error.kind, ErrorKind::Index);
+    assert_eq!(error.span, Some(Span { line: 2, column: 2 }));
+    assert!(error.trace.is_empty());
+}
+
+#[test]
+fn eval_try_catch() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "try { 5 } catch (e) { 0 };".to_string(),
+            expected: "5".to_string(),
+        },
+        TestInput {
+            input: "try { 1 + true } catch (e) { [e[\"kind\"], e[\"message\"]] };".to_string(),
+            expected: "[type mismatch, type mismatch: INTEGER + BOOLEAN]".to_string(),
+        },
+        TestInput {
+            input: "try { throw \"boom\"; } catch (e) { [e[\"kind\"], e[\"message\"]] };"
+                .to_string(),
+            expected: "[thrown, boom]".to_string(),
+        },
+        TestInput {
+            input: "try { throw [1, 2]; } catch (e) { e[\"value\"] };".to_string(),
+            expected: "[1, 2]".to_string(),
+        },
+        TestInput {
+            input: "try { throw \"a\"; } catch (e) { e };".to_string(),
+            expected: "error: a".to_string(),
+        },
+        TestInput {
+            input: "throw \"boom\";".to_string(),
+            expected: "ERROR: boom".to_string(),
+        },
+        TestInput {
+            input: "try { throw 1; } catch (e) { throw 2; };".to_string(),
+            expected: "ERROR: 2".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { throw \"x\"; };\nlet g = fn() { 1 + f() };\ntry { g() } catch (e) { e[\"trace\"] };"
+                .to_string(),
+            expected: "[f (at 2:21), g (at 3:8)]".to_string(),
+        },
+        // Throwing a caught error raises it again as it was.
+        TestInput {
+            input: "let f = fn() { try { [] + 1 } catch (e) { throw e; } };\ntry { f() } catch (e) { [e[\"kind\"], e[\"trace\"]] };"
+                .to_string(),
+            expected: "[type mismatch, [f (at 2:8)]]".to_string(),
+        },
+        // Calls in a `try` are not tail calls, so their errors are caught.
+        TestInput {
+            input: "let f = fn() { throw \"x\"; }; let g = fn() { try { f() } catch (e) { \"caught\" } }; g();"
+                .to_string(),
+            expected: "caught".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { throw 1; } catch (e) { fn() { e[\"value\"] } } }; f()();"
+                .to_string(),
+            expected: "1".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { 1 } finally { 2 } }; f();".to_string(),
+            expected: "1".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { throw 1; } finally { 2 } }; f();".to_string(),
+            expected: "ERROR: 1".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { return 1; } finally { return 2; } }; f();".to_string(),
+            expected: "2".to_string(),
+        },
+        TestInput {
+            input: "let log = fn(xs) { try { return xs; } finally { 0 } }; log([1]);".to_string(),
+            expected: "[1]".to_string(),
+        },
+        TestInput {
+            input: "try { throw 1; } catch (e) { 2 } finally { throw 3; };".to_string(),
+            expected: "ERROR: 3".to_string(),
+        },
+        // A `throw` at the end of a block needs no semicolon.
+        TestInput {
+            input: "try { throw 1 } catch (e) { 2 };".to_string(),
+            expected: "2".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { throw 5 } catch (e) { e.value } }; f();".to_string(),
+            expected: "5".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { throw 5 }; 7;".to_string(),
+            expected: "7".to_string(),
+        },
+        TestInput {
+            input: "try { 1 + true } catch (e) { [e.kind, e.message, e.trace, e.value] };"
+                .to_string(),
+            expected: "[type mismatch, type mismatch: INTEGER + BOOLEAN, [], null]".to_string(),
+        },
+        TestInput {
+            input: "try { throw 1; } catch (e) { e.stack };".to_string(),
+            expected: "ERROR: no member stack on EXCEPTION".to_string(),
+        },
+        // The parameter is only bound in the handler, and leaves a variable
+        // of the same name alone.
+        TestInput {
+            input: "let x = 1; try { throw 5; } catch (x) { 0 }; x;".to_string(),
+            expected: "1".to_string(),
+        },
+        TestInput {
+            input: "let x = 1; let f = fn() { x }; try { throw 5; } catch (x) { [x.value, f()] };"
+                .to_string(),
+            expected: "[5, 1]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { let x = 1; try { throw 5; } catch (x) { 0 }; x }; f();"
+                .to_string(),
+            expected: "1".to_string(),
+        },
+        TestInput {
+            input: "try { throw 1; } catch (e) { 0 }; e;".to_string(),
+            expected: "ERROR: identifier not found: e".to_string(),
+        },
+        TestInput {
+            input: "try { throw 1; } catch (e) { let y = e.value + 1; }; y;".to_string(),
+            expected: "2".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { throw 1; } catch (e) { let y = e.value + 1; }; y }; f();"
+                .to_string(),
+            expected: "2".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+
+    // Interrupts from the host are not caught.
+    let mut evaluator = Evaluator::new();
+    evaluator.set_fuel(Some(1000));
+    let program = parse_input(
+        "let spin = fn(n) { spin(n + 1) }; try { spin(0) } catch (e) { 0 } finally { 1 };",
+    );
+    assert!(matches!(
+        evaluator.eval(&program, Environment::new()),
+        Object::Interrupt(Interrupt::OutOfFuel)
+    ));
+}
+
+#[test]
+fn eval_results() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let halves = "let half = fn(n) { if (n / 2 * 2 == n) { ok(n / 2) } else { err(\"odd\") } };";
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "[ok(5), err(\"bad\")];".to_string(),
+            expected: "[ok(5), err(bad)]".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} let quarter = fn(n) {{ let h = half(n)?; half(h) }}; [quarter(8), quarter(6), quarter(3)];",
+                halves
+            ),
+            expected: "[ok(2), err(odd), err(odd)]".to_string(),
+        },
+        // `?` returns from the middle of an expression.
+        TestInput {
+            input: "let f = fn(r) { 1 + r? }; [f(ok(1)), f(err(0))];".to_string(),
+            expected: "[2, err(0)]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(r) { [r?, r?] }; f(ok(1));".to_string(),
+            expected: "[1, 1]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x = err(\"no\")?) { x }; [f(), f(1)];".to_string(),
+            expected: "[err(no), 1]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { try { err(1)? } finally { 0 } }; f();".to_string(),
+            expected: "err(1)".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { 1 + if (true) { return 5; } }; f();".to_string(),
+            expected: "5".to_string(),
+        },
+        TestInput {
+            input: "err(1)?; 5;".to_string(),
+            expected: "err(1)".to_string(),
+        },
+        TestInput {
+            input: "5?;".to_string(),
+            expected: "ERROR: unknown operator: INTEGER?".to_string(),
+        },
+        TestInput {
+            input: "ok(1) + 1;".to_string(),
+            expected: "ERROR: type mismatch: RESULT + INTEGER".to_string(),
+        },
+        TestInput {
+            input: "ok(1, 2);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to ok: expected 1, got 2"
+                .to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+}
+
+fn eval_module_input(input: &str) -> (Object, u64) {
+    let mut evaluator = Evaluator::new();
+    evaluator.set_module_id(Some(ModuleId::new("tests/test_files/modules/main.lang")));
+    let result = evaluator.eval(&parse_input(input), Environment::new());
+    (result, evaluator.steps())
+}
+
+#[test]
+fn eval_modules() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "import \"math.lang\" as m; [m.square(4), m.zero, m.one, m.answer];"
+                .to_string(),
+            expected: "[16, 0, 1, 42]".to_string(),
+        },
+        TestInput {
+            input: "import \"math.lang\" as m; m;".to_string(),
+            expected: "module math.lang".to_string(),
+        },
+        // Imports inside a module are relative to that module.
+        TestInput {
+            input: "import \"geometry/shapes.lang\" as shapes; shapes.area(3);".to_string(),
+            expected: "9".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(n) { import \"math.lang\" as m; m.square(n) }; f(5);".to_string(),
+            expected: "25".to_string(),
+        },
+        TestInput {
+            input: "import \"math.lang\" as m; m.hidden;".to_string(),
+            expected: "ERROR: module math.lang does not export hidden".to_string(),
+        },
+        TestInput {
+            input: "let x = 5; x.y;".to_string(),
+            expected: "ERROR: no member y on INTEGER".to_string(),
+        },
+        TestInput {
+            input: "import \"cycle_a.lang\" as a;".to_string(),
+            expected: "ERROR: import cycle: cycle_a.lang -> cycle_b.lang -> cycle_a.lang"
+                .to_string(),
+        },
+        TestInput {
+            input: "import \"failing.lang\" as f;".to_string(),
+            expected: "ERROR: type mismatch: INTEGER + BOOLEAN".to_string(),
+        },
+        TestInput {
+            input: "import \"broken.lang\" as b;".to_string(),
+            expected: "ERROR: cannot import broken.lang: Expected Token::String but got Token::Ident(math) instead".to_string(),
+        },
+        TestInput {
+            input: "if (true) { export let a = 1; }".to_string(),
+            expected: "ERROR: export is only allowed at the top level".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_module_input(&test_input.input).0.to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+
+    match eval_module_input("import \"missing.lang\" as m;").0 {
+        Object::Error(error) => {
+            assert_eq!(error.kind, ErrorKind::Import);
+            assert!(error.message.starts_with("cannot import missing.lang"));
+            assert_eq!(error.span, Some(Span { line: 1, column: 1 }));
+        }
+        other => panic!("expected an error, got {}", other),
+    }
+
+    // A module is evaluated once, importing it again only binds the name.
+    let (_, once) = eval_module_input("import \"math.lang\" as a;");
+    let (_, twice) = eval_module_input("import \"math.lang\" as a; import \"./math.lang\" as b;");
+    assert_eq!(twice, once + 1);
+}
+
+#[test]
+fn eval_module_loader() {
+    let mut loader = MemoryLoader::new();
+    loader.insert("math", "export let square = fn(x) { x * x };");
+    loader.insert(
+        "shapes",
+        "import \"math\" as m; export let area = fn(s) { m.square(s) };",
+    );
+    loader.insert("a", "import \"b\" as b;");
+    loader.insert("b", "import \"a\" as a;");
+
+    let test_inputs = [
+        ("import \"shapes\" as s; s.area(4);", "16"),
+        ("import \"a\" as a;", "ERROR: import cycle: a -> b -> a"),
+        (
+            "import \"nope\" as n;",
+            "ERROR: cannot import nope: module not found",
+        ),
+    ];
+    for (input, expected) in test_inputs {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_module_loader(Box::new(loader.clone()));
+        let result = evaluator.eval(&parse_input(input), Environment::new());
+        assert_eq!(result.to_string(), expected, "{}", input);
+    }
+
+    // Loaders are told which module is importing.
+    struct Recording(Rc<RefCell<Vec<String>>>);
+    impl ModuleLoader for Recording {
+        fn load(&self, spec: &str, from: Option<&ModuleId>) -> Result<String, LoadError> {
+            self.0
+                .borrow_mut()
+                .push(format!("{} from {:?}", spec, from.map(ModuleId::as_str)));
+            match spec {
+                "outer" => Ok("import \"inner\" as i; export let x = i.x;".to_string()),
+                "inner" => Ok("export let x = 7;".to_string()),
+                _ => Err(LoadError::Other("forbidden".to_string())),
+            }
+        }
+    }
+    let loads = Rc::new(RefCell::new(Vec::new()));
+    let mut evaluator = Evaluator::new();
+    evaluator.set_module_loader(Box::new(Recording(Rc::clone(&loads))));
+    evaluator.set_module_id(Some(ModuleId::new("main")));
+    let program = parse_input("import \"outer\" as o; import \"outer\" as p; o.x + p.x;");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "14"
+    );
+    assert_eq!(
+        *loads.borrow(),
+        vec!["outer from Some(\"main\")", "inner from Some(\"outer\")"]
+    );
+
+    let program = parse_input("import \"secret\" as s;");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "ERROR: cannot import secret: forbidden"
+    );
+}
+
+#[test]
+fn eval_methods() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "let s = \" Hello \"; [s.len(), s.trim().upper(), s.lower()];".to_string(),
+            expected: "[7, HELLO,  hello ]".to_string(),
+        },
+        TestInput {
+            input: "[\"a,b,c\".split(\",\"), \"abc\".contains(\"bc\")];".to_string(),
+            expected: "[[a, b, c], true]".to_string(),
+        },
+        TestInput {
+            input: "let xs = [1, 2, 3]; [xs.len(), xs.first(), xs.last(), [].first()];"
+                .to_string(),
+            expected: "[3, 1, 3, null]".to_string(),
+        },
+        // Methods return new values and leave the receiver as it was.
+        TestInput {
+            input: "let xs = [1, 2]; let ys = xs.push(3).reverse(); [xs, ys, ys.join(\"-\")];"
+                .to_string(),
+            expected: "[[1, 2], [3, 2, 1], 3-2-1]".to_string(),
+        },
+        TestInput {
+            input: "let h = {\"b\": 2, \"a\": 1}; [h.len(), h.keys(), h.values(), h.has(\"a\"), h.has(\"z\")];"
+                .to_string(),
+            expected: "[2, [a, b], [1, 2], true, false]".to_string(),
+        },
+        // Hash entries are fields, and take precedence over the built-in
+        // methods.
+        TestInput {
+            input: "let point = {\"x\": 1, \"len\": 10}; [point.x, point.len];".to_string(),
+            expected: "[1, 10]".to_string(),
+        },
+        TestInput {
+            input: "let counter = {\"next\": fn(n) { n + 1 }}; counter.next(counter.next(0));"
+                .to_string(),
+            expected: "2".to_string(),
+        },
+        TestInput {
+            input: "let len = \"abc\".len; [len, len()];".to_string(),
+            expected: "[method len of STRING, 3]".to_string(),
+        },
+        TestInput {
+            input: "{\"a\": 1}.b;".to_string(),
+            expected: "ERROR: no member b on HASH".to_string(),
+        },
+        TestInput {
+            input: "\"abc\".reverse();".to_string(),
+            expected: "ERROR: no member reverse on STRING".to_string(),
+        },
+        TestInput {
+            input: "[1].push();".to_string(),
+            expected: "ERROR: wrong number of arguments in call to push: expected 1, got 0"
+                .to_string(),
+        },
+        TestInput {
+            input: "\"a b\".split(1);".to_string(),
+            expected: "ERROR: argument to split must be STRING, got INTEGER".to_string(),
+        },
+        TestInput {
+            input: "\"a\".len(n: 1);".to_string(),
+            expected: "ERROR: unexpected keyword argument in call to len: n".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+}
+
+#[test]
+fn eval_structs() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let point = "struct Point { x, y }";
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: format!("{} let p = Point {{ y: 2, x: 1 }}; [p, p.x + p.y];", point),
+            expected: "[Point { x: 1, y: 2 }, 3]".to_string(),
+        },
+        TestInput {
+            input: format!("{} Point;", point),
+            expected: "struct Point".to_string(),
+        },
+        TestInput {
+            input: "struct Unit {} Unit {};".to_string(),
+            expected: "Unit {}".to_string(),
+        },
+        // Updating a field makes a new struct from an existing one.
+        TestInput {
+            input: format!(
+                "{} let p = Point {{ x: 1, y: 2 }}; let q = Point {{ ...p, x: 5 }}; [p, q];",
+                point
+            ),
+            expected: "[Point { x: 1, y: 2 }, Point { x: 5, y: 2 }]".to_string(),
+        },
+        // So does assigning to one, and the variable holding the old one
+        // is given the new one.
+        TestInput {
+            input: format!(
+                "{} let p = Point {{ x: 1, y: 2 }}; let q = p; p.x = 3; [p, q];",
+                point
+            ),
+            expected: "[Point { x: 3, y: 2 }, Point { x: 1, y: 2 }]".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} struct Line {{ from, to }} let l = Line {{ from: Point {{ x: 0, y: 0 }}, to: Point {{ x: 1, y: 1 }} }}; \
+                 l.to.y = 5; l;",
+                point
+            ),
+            expected: "Line { from: Point { x: 0, y: 0 }, to: Point { x: 1, y: 5 } }".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} let f = fn(p) {{ p.x = 10; p }}; let p = Point {{ x: 1, y: 2 }}; [f(p), p];",
+                point
+            ),
+            expected: "[Point { x: 10, y: 2 }, Point { x: 1, y: 2 }]".to_string(),
+        },
+        // Structs are equal when they are of the same struct and their
+        // fields are equal.
+        TestInput {
+            input: format!(
+                "{} let p = Point {{ x: 1, y: 2 }}; let q = p; q.x = 5; \
+                 [p == p, p == Point {{ x: 1, y: 2 }}, p == q, p != q, p != Point {{ ...q, x: 1 }}];",
+                point
+            ),
+            expected: "[true, true, false, true, false]".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} struct Size {{ x, y }} Point {{ x: 1, y: 2 }} == Size {{ x: 1, y: 2 }};",
+                point
+            ),
+            expected: "false".to_string(),
+        },
+        TestInput {
+            input: format!("{} let p = Point {{ x: 1, y: 2 }}; p.z = 3;", point),
+            expected: "ERROR: Point has no field z".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} let origin = fn() {{ Point {{ x: 0, y: 0 }} }}; origin().x = 1;",
+                point
+            ),
+            expected: "ERROR: cannot assign to field x of Point that is not held in a variable"
+                .to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} let origin = fn() {{ Point {{ x: 0, y: 0 }} }}; origin().x;",
+                point
+            ),
+            expected: "0".to_string(),
+        },
+        TestInput {
+            input: format!("{} let p = Point {{ x: 1, y: 2 }}; p.z;", point),
+            expected: "ERROR: Point has no field z".to_string(),
+        },
+        TestInput {
+            input: format!("{} Point {{ x: 1, y: 2, z: 3 }};", point),
+            expected: "ERROR: Point has no field z".to_string(),
+        },
+        TestInput {
+            input: format!("{} Point {{ x: 1 }};", point),
+            expected: "ERROR: missing field y in Point".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} struct Size {{ x, y }} let s = Size {{ x: 1, y: 2 }}; Point {{ ...s }};",
+                point
+            ),
+            expected: "ERROR: cannot spread Size into Point".to_string(),
+        },
+        TestInput {
+            input: format!("{} Point {{ ...{{\"x\": 1}} }};", point),
+            expected: "ERROR: cannot spread HASH into Point".to_string(),
+        },
+        TestInput {
+            input: "let x = 5; x { y: 1 };".to_string(),
+            expected: "ERROR: not a struct type: INTEGER".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+
+    let mut loader = MemoryLoader::new();
+    loader.insert("geometry", "export struct Point { x, y }");
+    let mut evaluator = Evaluator::new();
+    evaluator.set_module_loader(Box::new(loader));
+    let program = parse_input("import \"geometry\" as g; g.Point { x: 1, y: 2 };");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "Point { x: 1, y: 2 }"
+    );
+}
+
+#[test]
+fn eval_enums() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let shape = "enum Shape { Circle(r), Rect(w, h), Empty }";
+    let area = "let area = fn(s) { match (s) { Shape.Circle(r) => 3 * r * r, \
+                Shape.Rect(w, h) => w * h, Shape.Empty => 0 } };";
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: format!("{} [Shape.Circle(2), Shape.Rect(1, 2), Shape.Empty];", shape),
+            expected: "[Shape.Circle(2), Shape.Rect(1, 2), Shape.Empty]".to_string(),
+        },
+        TestInput {
+            input: format!("{} [Shape, Shape.Circle];", shape),
+            expected: "[enum Shape, constructor Shape.Circle]".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} {} [area(Shape.Circle(2)), area(Shape.Rect(3, 4)), area(Shape.Empty)];",
+                shape, area
+            ),
+            expected: "[12, 12, 0]".to_string(),
+        },
+        // Variants are equal when their tags and fields are.
+        TestInput {
+            input: format!(
+                "{} [Shape.Rect(1, 2) == Shape.Rect(1, 2), Shape.Rect(1, 2) == Shape.Rect(2, 1), \
+                 Shape.Empty == Shape.Empty, Shape.Circle(1) != Shape.Empty, Shape.Empty == 0];",
+                shape
+            ),
+            expected: "[true, false, true, true, false]".to_string(),
+        },
+        TestInput {
+            input: "enum A { X } enum B { X } A.X == B.X;".to_string(),
+            expected: "false".to_string(),
+        },
+        // Patterns nest, and literals and `_` match fields.
+        TestInput {
+            input: "enum Option { Some(v), None } \
+                    let describe = fn(o) { match (o) { Option.Some(Option.Some(_)) => \"nested\", \
+                    Option.Some(0) => \"zero\", Option.Some(x) => x, Option.None => \"none\" } }; \
+                    [describe(Option.Some(Option.Some(1))), describe(Option.Some(0)), \
+                    describe(Option.Some(\"v\")), describe(Option.None)];"
+                .to_string(),
+            expected: "[nested, zero, v, none]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn(x) { match (x) { 1 => \"one\", true => \"yes\", \"a\" => \"letter\", \
+                    -2 => \"minus two\", _ => \"other\" } }; [f(1), f(true), f(\"a\"), f(-2), f(3)];"
+                .to_string(),
+            expected: "[one, yes, letter, minus two, other]".to_string(),
+        },
+        // Arms in tail position are tail calls.
+        TestInput {
+            input: "enum List { Cons(head, tail), Nil } \
+                    let sum = fn(list, total) { match (list) { List.Nil => total, \
+                    List.Cons(head, tail) => sum(tail, total + head) } }; \
+                    let build = fn(n, list) { if (n == 0) { list } else { build(n - 1, List.Cons(n, list)) } }; \
+                    sum(build(20000, List.Nil), 0);"
+                .to_string(),
+            expected: "200010000".to_string(),
+        },
+        TestInput {
+            input: format!("{} match (Shape.Empty) {{ Shape.Circle(r) => r }};", shape),
+            expected: "ERROR: no match arm for Shape.Empty".to_string(),
+        },
+        TestInput {
+            input: format!("{} match (1) {{ Shape.Square(s) => s }};", shape),
+            expected: "ERROR: Shape has no variant Square".to_string(),
+        },
+        TestInput {
+            input: format!("{} match (1) {{ Shape.Rect(w) => w }};", shape),
+            expected: "ERROR: cannot destructure: expected 2 fields for Shape.Rect, got 1 patterns"
+                .to_string(),
+        },
+        TestInput {
+            input: format!("{} Shape.Rect(1);", shape),
+            expected: "ERROR: wrong number of arguments in call to Shape.Rect: expected 2, got 1"
+                .to_string(),
+        },
+        TestInput {
+            input: format!("{} Shape.Square;", shape),
+            expected: "ERROR: Shape has no variant Square".to_string(),
+        },
+        // The names a pattern binds are only bound in its arm, and leave a
+        // variable of the same name alone.
+        TestInput {
+            input: "let a = 5; let r = match (3) { a => a }; [r, a];".to_string(),
+            expected: "[3, 5]".to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { let a = 5; let r = match (3) { a => a }; [r, a] }; f();"
+                .to_string(),
+            expected: "[3, 5]".to_string(),
+        },
+        TestInput {
+            input: format!(
+                "{} let r = 1; let g = match (Shape.Circle(2)) {{ Shape.Circle(r) => fn() {{ r }} }}; [g(), r];",
+                shape
+            ),
+            expected: "[2, 1]".to_string(),
+        },
+        TestInput {
+            input: "match (1) { x => x }; x;".to_string(),
+            expected: "ERROR: identifier not found: x".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+
+    let mut loader = MemoryLoader::new();
+    loader.insert("shapes", "export enum Shape { Circle(r), Empty }");
+    let mut evaluator = Evaluator::new();
+    evaluator.set_module_loader(Box::new(loader));
+    let program = parse_input("import \"shapes\" as s; s.Shape.Circle(1);");
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "Shape.Circle(1)"
+    );
+}
+
+#[test]
+fn resolve_match_warnings() {
+    let shape = "enum Shape { Circle(r), Rect(w, h), Empty }";
+    let inputs = [
+        // Every variant is covered, or a catch-all arm covers the rest.
+        ("match (s) { Shape.Circle(_) => 1, Shape.Rect(w, h) => 2, Shape.Empty => 3 };", vec![]),
+        ("match (s) { Shape.Circle(r) => 1, _ => 2 };", vec![]),
+        ("match (s) { 1 => 1 };", vec![]),
+        (
+            "match (s) { Shape.Circle(r) => 1 };",
+            vec!["match at 1:66 does not cover Shape.Rect, Shape.Empty"],
+        ),
+        // A variant whose fields are only matched in part is not covered.
+        (
+            "let f = fn(s) { match (s) { Shape.Circle(1) => 1, Shape.Rect(w, h) => 2, Shape.Empty => 3 } };",
+            vec!["match at 1:82 does not cover Shape.Circle"],
+        ),
+    ];
+    for (input, expected) in inputs {
+        let input = format!("{} let s = Shape.Empty; {}", shape, input);
+        let program = parse_input(&input);
+        assert_eq!(
+            resolve(&program, &Environment::new()).unwrap(),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn eval_classes() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let animal = "class Animal { fn init(name) { self.name = name; } \
+                  fn speak() { self.name + \" makes a sound\" } fn kind() { \"animal\" } }";
+    let dog = "class Dog extends Animal { fn init(name, trick) { super.init(name); self.trick = trick; } \
+               fn speak() { super.speak() + \" and barks\" } }";
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: format!("{} let a = Animal(\"Tom\"); [a.name, a.speak(), a];", animal),
+            expected: "[Tom, Tom makes a sound, instance of Animal]".to_string(),
+        },
+        // Methods are looked up through the superclasses, and `super`
+        // calls the overridden one.
+        TestInput {
+            input: format!(
+                "{} {} let d = Dog(\"Rex\", \"sit\"); [d.speak(), d.kind(), d.trick, Dog];",
+                animal, dog
+            ),
+            expected: "[Rex makes a sound and barks, animal, sit, class Dog]".to_string(),
+        },
+        // `super` starts from the class declaring the method, not the
+        // class of the instance.
+        TestInput {
+            input: "class A { fn name() { \"a\" } } class B extends A { fn name() { \"b\" + super.name() } } \
+                    class C extends B { fn name() { \"c\" + super.name() } } C().name();"
+                .to_string(),
+            expected: "cba".to_string(),
+        },
+        // A method looked up on an instance stays bound to it.
+        TestInput {
+            input: format!(
+                "{} let speak = Animal(\"Tom\").speak; let call = fn(f) {{ f() }}; [speak, call(speak)];",
+                animal
+            ),
+            expected: "[method speak of Animal, Tom makes a sound]".to_string(),
+        },
+        // Instances are shared, not copied, so changes to one are seen
+        // everywhere it is held.
+        TestInput {
+            input: "class Counter { fn init() { self.n = 0; } fn add(k = 1) { self.n = self.n + k; self } } \
+                    let c = Counter(); let same = c; c.add().add(k: 5); [same.n, c == same, c == Counter()];"
+                .to_string(),
+            expected: "[6, true, false]".to_string(),
+        },
+        TestInput {
+            input: "let make = fn() { class Point { fn sum() { self.x + self.y } } let p = Point(); \
+                    p.x = 1; p.y = 2; p }; make().sum();"
+                .to_string(),
+            expected: "3".to_string(),
+        },
+        // A struct in a field is replaced by the updated one.
+        TestInput {
+            input: "struct P { x, y } class Body { fn init() { self.at = P { x: 0, y: 0 }; } } \
+                    let b = Body(); let same = b; b.at.x = 4; same.at;"
+                .to_string(),
+            expected: "P { x: 4, y: 0 }".to_string(),
+        },
+        TestInput {
+            input: "class Empty {} Empty(1);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to Empty: expected 0, got 1"
+                .to_string(),
+        },
+        TestInput {
+            input: format!("{} Animal();", animal),
+            expected: "ERROR: wrong number of arguments in call to init: expected 1, got 0"
+                .to_string(),
+        },
+        TestInput {
+            input: format!("{} Animal(\"Tom\").age;", animal),
+            expected: "ERROR: Animal has no member age".to_string(),
+        },
+        TestInput {
+            input: "class A {} class B extends A { fn f() { super.f() } } B().f();".to_string(),
+            expected: "ERROR: A has no method f".to_string(),
+        },
+        TestInput {
+            input: "let A = 1; class B extends A {}".to_string(),
+            expected: "ERROR: cannot extend INTEGER".to_string(),
+        },
+        TestInput {
+            input: "let n = 1; n.x = 2;".to_string(),
+            expected: "ERROR: cannot assign to field x of INTEGER".to_string(),
+        },
+        TestInput {
+            input: "class A { fn f() { super.f() } }".to_string(),
+            expected: "ERROR: super can only be used in a method of a class that extends another"
+                .to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { self };".to_string(),
+            expected: "ERROR: identifier not found: self".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+
+    let mut loader = MemoryLoader::new();
+    loader.insert(
+        "shapes",
+        "export class Square { fn init(side) { self.side = side; } fn area() { self.side * self.side } }",
+    );
+    let mut evaluator = Evaluator::new();
+    evaluator.set_module_loader(Box::new(loader));
+    let program = parse_input(
+        "import \"shapes\" as s; let Square = s.Square; \
+         class Cube extends Square { fn volume() { self.area() * self.side } } Cube(3).volume();",
+    );
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "27"
+    );
+}
+
+#[test]
+fn eval_generators() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        // A generator runs up to the next `yield` each time, and says it is
+        // done once it has finished.
+        TestInput {
+            input: "let g = fn*() { yield 1; yield 2; }; let it = g(); \
+                    [it.next(), it.next(), it.next(), it.next()];"
+                .to_string(),
+            expected: "[{done: false, value: 1}, {done: false, value: 2}, \
+                       {done: true, value: null}, {done: true, value: null}]"
+                .to_string(),
+        },
+        // Yielding null is not the same as finishing.
+        TestInput {
+            input: "let g = fn*() { yield; }; let it = g(); [it.next(), it.next()];"
+                .to_string(),
+            expected: "[{done: false, value: null}, {done: true, value: null}]".to_string(),
+        },
+        TestInput {
+            input: "let count = fn*(from, to) { if (from < to) { yield from; } }; \
+                    let g = count(1, 3); [count, g, g.next().value, g.next().done];"
+                .to_string(),
+            expected: "[fn*(from, to) { ... }, generator count, 1, true]".to_string(),
+        },
+        // Nothing runs until the first `next()`.
+        TestInput {
+            input: "let g = fn*() { throw \"started\"; }; let it = g(); 1;".to_string(),
+            expected: "1".to_string(),
+        },
+        TestInput {
+            input: "let xs = []; for (x in [1, 2, 3]) { let xs = xs.push(x * 10); } xs;"
+                .to_string(),
+            expected: "[10, 20, 30]".to_string(),
+        },
+        TestInput {
+            input: r#"let s = ""; for (c in "abc") { let s = c + s; } s;"#.to_string(),
+            expected: "cba".to_string(),
+        },
+        TestInput {
+            input: r#"let ks = []; for (k in {"b": 2, "a": 1}) { let ks = ks.push(k); } ks;"#
+                .to_string(),
+            expected: "[a, b]".to_string(),
+        },
+        TestInput {
+            input: "let sum = 0; for ([a, b] in [[1, 2], [3, 4]]) { let sum = sum + a * b; } sum;"
+                .to_string(),
+            expected: "14".to_string(),
+        },
+        // Values are only produced as the loop asks for them, so a
+        // generator can go on forever.
+        TestInput {
+            input: "let from = fn*(n) { yield n; for (m in from(n + 1)) { yield m; } }; \
+                    let take = fn(g, n, out) { if (out.len() == n) { out } else { take(g, n, out.push(g.next().value)) } }; \
+                    take(from(0), 5, []);"
+                .to_string(),
+            expected: "[0, 1, 2, 3, 4]".to_string(),
+        },
+        TestInput {
+            input: "let signs = fn*(xs) { for (x in xs) { if (x > 2) { yield x; } else { yield -x; } } }; \
+                    let out = []; for (x in signs([1, 2, 3, 4])) { let out = out.push(x); } out;"
+                .to_string(),
+            expected: "[-1, -2, 3, 4]".to_string(),
+        },
+        // A `return` finishes the generator with the value it returns,
+        // which a loop leaves out, and a `return` in the body of a loop
+        // leaves the function.
+        TestInput {
+            input: "let g = fn*() { yield 1; return 5; yield 2; }; let it = g(); \
+                    [it.next(), it.next(), it.next()];"
+                .to_string(),
+            expected: "[{done: false, value: 1}, {done: true, value: 5}, {done: true, value: null}]"
+                .to_string(),
+        },
+        TestInput {
+            input: "let g = fn*() { yield 1; return 5; }; let out = []; \
+                    for (x in g()) { let out = out.push(x); } out;"
+                .to_string(),
+            expected: "[1]".to_string(),
+        },
+        TestInput {
+            input: "let find = fn(xs, y) { for (x in xs) { if (x == y) { return true; } } false }; \
+                    [find([1, 2], 2), find([1, 2], 3)];"
+                .to_string(),
+            expected: "[true, false]".to_string(),
+        },
+        TestInput {
+            input: "class Range { fn init(n) { self.n = n; } \
+                    fn* each() { for (i in [0, 1, 2, 3]) { if (i < self.n) { yield i; } } } } \
+                    let out = []; for (i in Range(2).each()) { let out = out.push(i); } out;"
+                .to_string(),
+            expected: "[0, 1]".to_string(),
+        },
+        TestInput {
+            input: "let g = fn*() { yield; }; g().next();".to_string(),
+            expected: "{done: false, value: null}".to_string(),
+        },
+        // An error raised in a generator comes out of `next()`, and
+        // finishes it.
+        TestInput {
+            input: "let g = fn*() { yield 1; throw \"boom\"; }; let it = g(); it.next(); \
+                    let caught = try { it.next() } catch (e) { e.message }; [caught, it.next().done];"
+                .to_string(),
+            expected: "[boom, true]".to_string(),
+        },
+        TestInput {
+            input: "for (x in 5) { x; }".to_string(),
+            expected: "ERROR: cannot iterate over INTEGER".to_string(),
+        },
+        TestInput {
+            input: "let g = fn*() { yield it.next(); }; let it = g(); it.next();".to_string(),
+            expected: "ERROR: generator is already running".to_string(),
+        },
+        TestInput {
+            input: "let g = fn*() { yield 1; }; g().next(1);".to_string(),
+            expected: "ERROR: wrong number of arguments in call to next: expected 0, got 1"
+                .to_string(),
+        },
+        TestInput {
+            input: "let f = fn() { yield 1; };".to_string(),
+            expected: "ERROR: yield is only allowed in a generator function".to_string(),
+        },
+        TestInput {
+            input: "yield 1;".to_string(),
+            expected: "ERROR: yield is only allowed in a generator function".to_string(),
+        },
+        TestInput {
+            input: "let g = fn*() { let x = if (true) { yield 1; }; };".to_string(),
+            expected: "ERROR: yield cannot be used inside an expression or a try block"
+                .to_string(),
+        },
+        TestInput {
+            input: "let g = fn*() { try { yield 1; } catch (e) { 0 } };".to_string(),
+            expected: "ERROR: yield cannot be used inside an expression or a try block"
+                .to_string(),
+        },
+        // A function inside a generator is not a generator itself.
+        TestInput {
+            input: "let g = fn*() { let f = fn() { yield 1; }; };".to_string(),
+            expected: "ERROR: yield is only allowed in a generator function".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            eval_input(&test_input.input).to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+
+    // A suspended generator keeps its frame alive through a collection, and
+    // one that is dropped is collected with it.
+    let mut evaluator = Evaluator::new();
+    let program = parse_input(
+        "let make = fn*(n) { let f = fn() { n }; yield f(); yield f() + 1; }; \
+         let kept = make(41); kept.next(); let drop = fn() { let g = make(0); g.next(); 0 }; \
+         drop(); gc(); kept.next().value;",
+    );
+    assert_eq!(
+        evaluator.eval(&program, Environment::new()).to_string(),
+        "42"
+    );
+    assert!(evaluator.gc_stats().collected >= 1);
+}
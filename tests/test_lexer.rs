@@ -1,9 +1,9 @@
-use lexer::{Lexer, Token, TokenType};
+use lexer::{Lexer, Span, Symbol, Token, TokenType};
 
 #[test]
 fn tokenise() {
     let v: Vec<u8> = "let x = 10;".bytes().collect();
-    let expected = vec![
+    let expected = [
         Token::new(TokenType::Let, None),
         Token::new(TokenType::Ident, Some("x".to_string())),
         Token::new(TokenType::Assign, None),
@@ -13,9 +13,166 @@ fn tokenise() {
     ];
 
     let mut lex = Lexer::new(v);
-    for (_, expected_token) in expected.iter().enumerate() {
+    for expected_token in expected.iter() {
         let token = lex.next_token();
         assert_eq!(token, expected_token.clone());
     }
     assert_eq!(lex.next_token().token_type, TokenType::Eof);
 }
+
+#[test]
+fn tokenise_collections() {
+    let v: Vec<u8> = r#"let [a, ...b] = {"k": "v"};"#.bytes().collect();
+    let expected = [
+        Token::new(TokenType::Let, None),
+        Token::new(TokenType::LBracket, None),
+        Token::new(TokenType::Ident, Some("a".to_string())),
+        Token::new(TokenType::Comma, None),
+        Token::new(TokenType::Ellipsis, None),
+        Token::new(TokenType::Ident, Some("b".to_string())),
+        Token::new(TokenType::RBracket, None),
+        Token::new(TokenType::Assign, None),
+        Token::new(TokenType::LBrace, None),
+        Token::new(TokenType::String, Some("k".to_string())),
+        Token::new(TokenType::Colon, None),
+        Token::new(TokenType::String, Some("v".to_string())),
+        Token::new(TokenType::RBrace, None),
+        Token::new(TokenType::Semicolon, None),
+    ];
+
+    let mut lex = Lexer::new(v);
+    for expected_token in expected.iter() {
+        assert_eq!(lex.next_token(), expected_token.clone());
+    }
+    assert_eq!(lex.next_token().token_type, TokenType::Eof);
+}
+
+#[test]
+fn tokenise_match() {
+    let v: Vec<u8> = "match (s) { E.A(_x) => 1, _ => 2 }".bytes().collect();
+    let expected = [
+        Token::new(TokenType::Match, None),
+        Token::new(TokenType::LParen, None),
+        Token::new(TokenType::Ident, Some("s".to_string())),
+        Token::new(TokenType::RParen, None),
+        Token::new(TokenType::LBrace, None),
+        Token::new(TokenType::Ident, Some("E".to_string())),
+        Token::new(TokenType::Dot, None),
+        Token::new(TokenType::Ident, Some("A".to_string())),
+        Token::new(TokenType::LParen, None),
+        Token::new(TokenType::Ident, Some("_x".to_string())),
+        Token::new(TokenType::RParen, None),
+        Token::new(TokenType::FatArrow, None),
+        Token::new(TokenType::Int, Some("1".to_string())),
+        Token::new(TokenType::Comma, None),
+        Token::new(TokenType::Ident, Some("_".to_string())),
+        Token::new(TokenType::FatArrow, None),
+        Token::new(TokenType::Int, Some("2".to_string())),
+        Token::new(TokenType::RBrace, None),
+    ];
+
+    let mut lex = Lexer::new(v);
+    for expected_token in expected.iter() {
+        assert_eq!(lex.next_token(), expected_token.clone());
+    }
+    assert_eq!(lex.next_token().token_type, TokenType::Eof);
+}
+
+#[test]
+fn tokenise_generator() {
+    let v: Vec<u8> = "fn*() { for (x in xs) { yield x; } }".bytes().collect();
+    let expected = [
+        Token::new(TokenType::Function, None),
+        Token::new(TokenType::Asterisk, None),
+        Token::new(TokenType::LParen, None),
+        Token::new(TokenType::RParen, None),
+        Token::new(TokenType::LBrace, None),
+        Token::new(TokenType::For, None),
+        Token::new(TokenType::LParen, None),
+        Token::new(TokenType::Ident, Some("x".to_string())),
+        Token::new(TokenType::In, None),
+        Token::new(TokenType::Ident, Some("xs".to_string())),
+        Token::new(TokenType::RParen, None),
+        Token::new(TokenType::LBrace, None),
+        Token::new(TokenType::Yield, None),
+        Token::new(TokenType::Ident, Some("x".to_string())),
+        Token::new(TokenType::Semicolon, None),
+        Token::new(TokenType::RBrace, None),
+        Token::new(TokenType::RBrace, None),
+    ];
+
+    let mut lex = Lexer::new(v);
+    for expected_token in expected.iter() {
+        assert_eq!(lex.next_token(), expected_token.clone());
+    }
+    assert_eq!(lex.next_token().token_type, TokenType::Eof);
+}
+
+#[test]
+fn token_spans() {
+    let v: Vec<u8> = "let x = 10;\n  x(\"a\nb\", y);".bytes().collect();
+    let expected = [
+        Span { line: 1, column: 1 },
+        Span { line: 1, column: 5 },
+        Span { line: 1, column: 7 },
+        Span { line: 1, column: 9 },
+        Span {
+            line: 1,
+            column: 11,
+        },
+        Span { line: 2, column: 3 },
+        Span { line: 2, column: 4 },
+        Span { line: 2, column: 5 },
+        Span { line: 3, column: 3 },
+        Span { line: 3, column: 5 },
+        Span { line: 3, column: 6 },
+        Span { line: 3, column: 7 },
+    ];
+
+    let mut lex = Lexer::new(v);
+    for expected_span in expected.iter() {
+        assert_eq!(lex.next_token().span, *expected_span);
+    }
+    assert_eq!(lex.next_token().token_type, TokenType::Eof);
+}
+
+#[test]
+fn intern_identifiers() {
+    let v: Vec<u8> = "let total = total_count + total;".bytes().collect();
+    let mut lex = Lexer::new(v);
+    let symbols: Vec<Symbol> = std::iter::from_fn(|| Some(lex.next_token()))
+        .take_while(|token| token.token_type != TokenType::Eof)
+        .filter_map(|token| token.symbol)
+        .collect();
+
+    assert_eq!(symbols.len(), 3);
+    assert_eq!(symbols[0], symbols[2]);
+    assert_ne!(symbols[0], symbols[1]);
+    assert_eq!(symbols[0], Symbol::intern("total"));
+    assert_eq!(symbols[1].as_str(), "total_count");
+    assert_eq!(symbols[1].to_string(), "total_count");
+
+    // Symbols are shared between threads.
+    let total = std::thread::spawn(|| Symbol::intern("total"))
+        .join()
+        .unwrap();
+    assert_eq!(total, symbols[0]);
+}
+
+#[test]
+fn lex_errors() {
+    let v: Vec<u8> = "let x = #;\nlet s = \"abc".bytes().collect();
+    let mut lex = Lexer::new(v);
+    let illegal = std::iter::from_fn(|| Some(lex.next_token()))
+        .take_while(|token| token.token_type != TokenType::Eof)
+        .filter(|token| token.token_type == TokenType::Illegal)
+        .count();
+    assert_eq!(illegal, 2);
+    assert_eq!(
+        lex.errors,
+        vec![
+            "Unknown character # at 1:9",
+            "Unterminated string literal at 2:9"
+        ]
+    );
+}
@@ -0,0 +1,244 @@
+use ast::ast::Expression;
+use ast::{expressions, statements};
+use compiler::Compiler;
+use evaluator::{eval, Environment};
+use lexer::Lexer;
+use optimizer::optimize;
+use parser::Parser;
+use std::fs;
+use vm::Vm;
+
+fn parse_input(input: &str) -> statements::ProgramStatement {
+    let lex = Lexer::new(input.bytes().collect());
+    let mut parser = Parser::new(lex);
+    let program = parser.parse();
+    if !parser.errors.is_empty() {
+        panic!("Parser errors: {:?}", parser.errors);
+    }
+    program
+}
+
+// The expression of the program's last statement, after optimizing.
+fn optimized_expression(input: &str) -> Box<dyn Expression> {
+    let mut program = optimize(parse_input(input));
+    let statement = program.body.pop().unwrap();
+    let statement = statement
+        .into_any()
+        .downcast::<statements::ExpressionStatement>()
+        .unwrap();
+    statement.expression.unwrap()
+}
+
+fn describe(expression: &dyn Expression) -> String {
+    let any = expression.as_any();
+    if let Some(literal) = any.downcast_ref::<expressions::IntegerLiteral>() {
+        return literal.value.to_string();
+    }
+    if let Some(literal) = any.downcast_ref::<expressions::Bool>() {
+        return literal.value.to_string();
+    }
+    if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+        return identifier.value.to_string();
+    }
+    if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+        return format!(
+            "({} {} {})",
+            describe(infix.left.as_deref().unwrap()),
+            infix.operator,
+            describe(infix.right.as_deref().unwrap())
+        );
+    }
+    if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+        return format!(
+            "({}{})",
+            prefix.operator,
+            describe(prefix.right.as_deref().unwrap())
+        );
+    }
+    if let Some(if_expr) = any.downcast_ref::<expressions::If>() {
+        let branches = [&if_expr.first, &if_expr.second].map(|block| match block {
+            Some(block) => format!("{{ {} }}", block.statements.len()),
+            None => "-".to_string(),
+        });
+        return format!(
+            "if ({}) {} {}",
+            describe(if_expr.condition.as_deref().unwrap()),
+            branches[0],
+            branches[1]
+        );
+    }
+    format!("{:?}", expression)
+}
+
+#[test]
+fn optimize_expressions() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "1 + 2 * 3;".to_string(),
+            expected: "7".to_string(),
+        },
+        TestInput {
+            input: "-(4 - 10) / 2;".to_string(),
+            expected: "3".to_string(),
+        },
+        TestInput {
+            input: "1 < 2 == !false;".to_string(),
+            expected: "true".to_string(),
+        },
+        TestInput {
+            input: "!5;".to_string(),
+            expected: "false".to_string(),
+        },
+        TestInput {
+            input: "x + 2 * 3;".to_string(),
+            expected: "(x + 6)".to_string(),
+        },
+        // Anything that fails at runtime is left for the evaluator.
+        TestInput {
+            input: "1 / 0;".to_string(),
+            expected: "(1 / 0)".to_string(),
+        },
+        TestInput {
+            input: "9223372036854775807 + 1;".to_string(),
+            expected: "(9223372036854775807 + 1)".to_string(),
+        },
+        TestInput {
+            input: "true + false;".to_string(),
+            expected: "(true + false)".to_string(),
+        },
+        TestInput {
+            input: "-true;".to_string(),
+            expected: "(-true)".to_string(),
+        },
+        TestInput {
+            input: "if (1 > 2) { 1; 2 } else { 3 };".to_string(),
+            expected: "if (true) { 1 } -".to_string(),
+        },
+        TestInput {
+            input: "if (false) { 1 };".to_string(),
+            expected: "if (true) - -".to_string(),
+        },
+        TestInput {
+            input: "if (x) { 1 } else { 2 };".to_string(),
+            expected: "if (x) { 1 } { 1 }".to_string(),
+        },
+        TestInput {
+            input: "let x = 2 * 5; x * x;".to_string(),
+            expected: "100".to_string(),
+        },
+        TestInput {
+            input: "let x = 1; let y = x + 1; if (y == 2) { 1 } else { 2 };".to_string(),
+            expected: "if (true) { 1 } -".to_string(),
+        },
+        // Only names bound once are inlined.
+        TestInput {
+            input: "let x = 1; let f = fn(x) { x }; x + 1;".to_string(),
+            expected: "(x + 1)".to_string(),
+        },
+        TestInput {
+            input: "let x = 1; let x = 2; x;".to_string(),
+            expected: "x".to_string(),
+        },
+        TestInput {
+            input: "let s = \"a\"; s;".to_string(),
+            expected: "s".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        assert_eq!(
+            describe(optimized_expression(&test_input.input).as_ref()),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+}
+
+#[test]
+fn optimize_preserves_results() {
+    let programs = [
+        "let f = fn() { let g = fn() { n }; let n = 7; g() }; f();",
+        "let f = fn() { let a = b; let b = 1; a }; f();",
+        "let f = fn(x) { if (true) { let y = x; } y }; f(3);",
+        "let f = fn(n) { if (n == 0) { return 0; } f(n - 1) }; f(10000);",
+        "let x = 5; let f = fn(y = x * 2) { y }; [f(), f(1)];",
+        "if (false) { let z = 1; } z;",
+        "let x = 1; try { throw 2; } catch (x) { x };",
+    ];
+    let corpus = fs::read_dir("tests/corpus").unwrap().map(|entry| {
+        let path = entry.unwrap().path();
+        fs::read_to_string(path).unwrap()
+    });
+    let inputs: Vec<String> = programs
+        .iter()
+        .map(|program| program.to_string())
+        .chain(corpus)
+        .collect();
+
+    // Deep recursion in the evaluator needs a larger stack.
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            for input in inputs.iter() {
+                let expected = eval(&parse_input(input), Environment::new()).to_string();
+                let optimized = eval(&optimize(parse_input(input)), Environment::new());
+                assert_eq!(optimized.to_string(), expected, "{}", input);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn optimize_keeps_runtime_errors() {
+    struct TestInput {
+        input: String,
+        expected: String,
+    }
+    let test_inputs: Vec<TestInput> = vec![
+        TestInput {
+            input: "1 / 0;".to_string(),
+            expected: "ERROR: division by zero".to_string(),
+        },
+        TestInput {
+            input: "let zero = 0; 10 / zero;".to_string(),
+            expected: "ERROR: division by zero".to_string(),
+        },
+        TestInput {
+            input: "9223372036854775807 + 1;".to_string(),
+            expected: "ERROR: integer overflow: 9223372036854775807 + 1".to_string(),
+        },
+        TestInput {
+            input: "-(-9223372036854775807 - 1);".to_string(),
+            expected: "ERROR: integer overflow: --9223372036854775808".to_string(),
+        },
+    ];
+
+    for test_input in test_inputs.iter() {
+        let program = optimize(parse_input(&test_input.input));
+        let evaluated = eval(&program, Environment::new());
+        assert_eq!(
+            evaluated.to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+
+        let mut compiler = Compiler::new();
+        let bytecode = compiler.compile(&program);
+        assert!(compiler.errors.is_empty(), "{}", test_input.input);
+        let executed = Vm::new().run(&bytecode);
+        assert_eq!(
+            executed.to_string(),
+            test_input.expected,
+            "{}",
+            test_input.input
+        );
+    }
+}
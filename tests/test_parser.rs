@@ -1,21 +1,30 @@
+use ast::ast::Expression;
 use ast::expressions;
+use ast::patterns;
 use ast::statements;
-use lexer::{Lexer, Token, TokenType};
+use lexer::{Lexer, Symbol, Token, TokenType};
 use parser::Parser;
+use std::cell::Cell;
 use std::fs;
 
 fn file_to_parser(path: &str) -> Parser {
     let v: Vec<u8> = fs::read(path).expect("Couldn't open file");
     let lex = Lexer::new(v);
-    let parser = Parser::new(lex);
-    return parser;
+    Parser::new(lex)
 }
 
 fn string_to_parser(string: &str) -> Parser {
     let v: Vec<u8> = string.bytes().collect();
     let lex = Lexer::new(v);
-    let parser = Parser::new(lex);
-    return parser;
+    Parser::new(lex)
+}
+
+#[test]
+fn parse_file() {
+    let mut parser = file_to_parser("file.lang");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty());
+    assert_eq!(parsed_statement.body.len(), 5);
 }
 
 #[test]
@@ -26,11 +35,13 @@ fn parse_let() {
     let expected = statements::ProgramStatement {
         body: vec![Box::new(statements::LetStatement {
             token: Token::new(TokenType::Let, None),
-            name: expressions::Identifier {
+            pattern: patterns::Pattern::Identifier(expressions::Identifier {
                 token: Token::new(TokenType::Ident, Some(String::new())),
-                value: String::new(),
-            },
+                value: Symbol::intern(""),
+                slot: Cell::new(None),
+            }),
             value: None,
+            exported: false,
         })],
     };
     assert_eq!(parsed_statement, expected);
@@ -62,7 +73,7 @@ fn parse_identifier_token() {
         })],
     };
 
-    if parser.errors.len() > 0 {
+    if !parser.errors.is_empty() {
         panic!("Failed")
     }
     assert_eq!(parsed_statement, expected);
@@ -87,7 +98,7 @@ fn parse_prefix_expression() {
         })],
     };
 
-    if parser.errors.len() > 0 {
+    if !parser.errors.is_empty() {
         panic!("Failed")
     }
     assert_eq!(parsed_statement, expected);
@@ -107,7 +118,7 @@ fn parse_integer_literal_expression() {
             })),
         })],
     };
-    if parser.errors.len() > 0 {
+    if !parser.errors.is_empty() {
         panic!("Failed")
     }
     assert_eq!(parsed_statement, expected);
@@ -202,7 +213,7 @@ fn parse_infix_expression() {
                 })),
             })],
         };
-        if parser.errors.len() > 0 {
+        if !parser.errors.is_empty() {
             dbg!("Errors:");
             for err in parser.errors.iter() {
                 dbg!("{}", err);
@@ -246,7 +257,7 @@ fn parse_prefix_bool() {
                 })),
             })],
         };
-        if parser.errors.len() > 0 {
+        if !parser.errors.is_empty() {
             dbg!("Errors:");
             for err in parser.errors.iter() {
                 dbg!("{}", err);
@@ -256,3 +267,649 @@ fn parse_prefix_bool() {
         assert_eq!(parsed_statement, expected);
     }
 }
+
+#[test]
+fn parse_let_destructuring() {
+    let test_inputs = [
+        "let [a, b] = xs;",
+        "let [first, ...rest] = [1, 2, 3];",
+        "let {name, age} = person;",
+        "let {name: {first}, tags: [tag, ...tags]} = person;",
+    ];
+
+    for input in test_inputs.iter() {
+        let mut parser = string_to_parser(input);
+        let parsed_statement = parser.parse();
+
+        if !parser.errors.is_empty() {
+            dbg!("Errors:");
+            for err in parser.errors.iter() {
+                dbg!("{}", err);
+            }
+            panic!("Failed")
+        }
+        assert_eq!(parsed_statement.body.len(), 1);
+        let let_statement = parsed_statement.body[0]
+            .as_any()
+            .downcast_ref::<statements::LetStatement>()
+            .expect("Expected a let statement");
+        assert!(!matches!(
+            let_statement.pattern,
+            patterns::Pattern::Identifier(_)
+        ));
+    }
+}
+
+#[test]
+fn parse_let_nested_pattern() {
+    let mut parser = string_to_parser("let [a, {b}, ...rest] = xs;");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty());
+
+    let let_statement = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::LetStatement>()
+        .unwrap();
+    let array = match &let_statement.pattern {
+        patterns::Pattern::Array(array) => array,
+        _ => panic!("Expected an array pattern"),
+    };
+    assert_eq!(array.elements.len(), 2);
+    assert!(matches!(array.elements[1], patterns::Pattern::Hash(_)));
+    assert_eq!(array.rest.as_ref().unwrap().value, "rest");
+}
+
+#[test]
+fn parse_let_invalid_pattern() {
+    let mut parser = string_to_parser("let 5 = x;");
+    parser.parse();
+    assert!(!parser.errors.is_empty());
+}
+
+#[test]
+fn parse_function_parameters() {
+    let mut parser = string_to_parser("fn(x, [a, b], y = 10, ...rest) { x };");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty());
+
+    let statement = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let function = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::FunctionLiteral>()
+        .unwrap();
+    let parameters = function.parameters.as_ref().unwrap();
+    assert_eq!(parameters.len(), 3);
+    assert_eq!(parameters[0].name(), Some(Symbol::intern("x")));
+    assert!(matches!(parameters[1].pattern, patterns::Pattern::Array(_)));
+    assert!(parameters[2].default.is_some());
+    assert_eq!(function.rest.as_ref().unwrap().value, "rest");
+}
+
+#[test]
+fn parse_named_arguments() {
+    let mut parser = string_to_parser("f(1, y: 2, z: 3);");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty());
+
+    let statement = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let call = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::CallExpression>()
+        .unwrap();
+    assert_eq!(call.arguments.as_ref().unwrap().len(), 1);
+    assert_eq!(call.named_arguments.len(), 2);
+    assert_eq!(call.named_arguments[0].name.value, "y");
+
+    let mut parser = string_to_parser("f(y: 2, 1);");
+    parser.parse();
+    assert!(!parser.errors.is_empty());
+}
+
+#[test]
+fn parse_try_and_throw() {
+    let mut parser =
+        string_to_parser("try { f(); } catch (e) { e; } finally { g(); }; throw \"boom\";");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    assert_eq!(parsed_statement.body.len(), 2);
+
+    let statement = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let try_expr = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::Try>()
+        .unwrap();
+    assert_eq!(try_expr.body.as_ref().unwrap().statements.len(), 1);
+    assert_eq!(try_expr.parameter.as_ref().unwrap().value, "e");
+    assert_eq!(try_expr.handler.as_ref().unwrap().statements.len(), 1);
+    assert_eq!(try_expr.finalizer.as_ref().unwrap().statements.len(), 1);
+
+    assert!(parsed_statement.body[1]
+        .as_any()
+        .is::<statements::ThrowStatement>());
+
+    let mut parser = string_to_parser("try { 1 } finally { 2 };");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    let statement = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let try_expr = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::Try>()
+        .unwrap();
+    assert!(try_expr.parameter.is_none());
+    assert!(try_expr.handler.is_none());
+
+    // A `throw` at the end of a block needs no semicolon.
+    let mut parser = string_to_parser("try { throw 1 } catch (e) { throw e } finally { 2 }; 3;");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    assert_eq!(parsed_statement.body.len(), 2);
+    let statement = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let try_expr = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::Try>()
+        .unwrap();
+    assert!(try_expr.body.as_ref().unwrap().statements[0]
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .is::<statements::ThrowStatement>());
+    assert_eq!(try_expr.handler.as_ref().unwrap().statements.len(), 1);
+
+    for input in ["try { 1 };", "try { 1 } catch { 2 };", "throw;"] {
+        let mut parser = string_to_parser(input);
+        parser.parse();
+        assert!(!parser.errors.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_propagate() {
+    let inputs = [
+        ("f(x)?;", "f(x)?"),
+        ("-x?;", "-(x?)"),
+        ("a? + b?;", "(a? + b?)"),
+        ("xs[0]?;", "xs[0]?"),
+    ];
+    for (input, expected) in inputs {
+        let mut parser = string_to_parser(input);
+        let parsed_statement = parser.parse();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let statement = parsed_statement.body[0]
+            .as_any()
+            .downcast_ref::<statements::ExpressionStatement>()
+            .unwrap();
+        assert_eq!(
+            describe(statement.expression.as_deref().unwrap()),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn parse_import_and_export() {
+    let mut parser =
+        string_to_parser("import \"lib/math.lang\" as math; export let area = math.square(2);");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    assert_eq!(parsed_statement.body.len(), 2);
+
+    let import = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ImportStatement>()
+        .unwrap();
+    assert_eq!(import.path, "lib/math.lang");
+    assert_eq!(import.alias.value, "math");
+
+    let let_stmt = parsed_statement.body[1]
+        .as_any()
+        .downcast_ref::<statements::LetStatement>()
+        .unwrap();
+    assert!(let_stmt.exported);
+    assert_eq!(
+        describe(let_stmt.value.as_deref().unwrap()),
+        "math.square(2)"
+    );
+
+    for input in [
+        "import math;",
+        "import \"math.lang\";",
+        "import \"math.lang\" as;",
+        "export 5;",
+        "m.;",
+    ] {
+        let mut parser = string_to_parser(input);
+        parser.parse();
+        assert!(!parser.errors.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_struct() {
+    let mut parser = string_to_parser(
+        "struct Point { x, y } let p = Point { x: 1, y: 2 }; Point { ...p, y: 3 };",
+    );
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    assert_eq!(parsed_statement.body.len(), 3);
+
+    let struct_stmt = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::StructStatement>()
+        .unwrap();
+    assert_eq!(struct_stmt.name.value, "Point");
+    let fields: Vec<String> = struct_stmt
+        .fields
+        .iter()
+        .map(|field| field.value.to_string())
+        .collect();
+    assert_eq!(fields, vec!["x", "y"]);
+
+    let let_stmt = parsed_statement.body[1]
+        .as_any()
+        .downcast_ref::<statements::LetStatement>()
+        .unwrap();
+    let literal = let_stmt
+        .value
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::StructLiteral>()
+        .unwrap();
+    assert_eq!(describe(literal.struct_type.as_deref().unwrap()), "Point");
+    assert_eq!(literal.fields.len(), 2);
+    assert!(literal.base.is_none());
+
+    let statement = parsed_statement.body[2]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let literal = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::StructLiteral>()
+        .unwrap();
+    assert_eq!(describe(literal.base.as_deref().unwrap()), "p");
+    assert_eq!(literal.fields[0].0.value, "y");
+
+    for input in [
+        "struct { x };",
+        "struct P { x, x };",
+        "struct P { 1 };",
+        "P { x: 1, x: 2 };",
+        "P { 1: 2 };",
+        "P { ...a, ...b };",
+    ] {
+        let mut parser = string_to_parser(input);
+        parser.parse();
+        assert!(!parser.errors.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_enum_and_match() {
+    let mut parser = string_to_parser(
+        "enum Shape { Circle(r), Rect(w, h), Empty } \
+         match (s) { Shape.Circle(r) => r, Shape.Rect(_, 2) => 1, -1 => 2, \"a\" => 3, true => 4, x => x, };",
+    );
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    assert_eq!(parsed_statement.body.len(), 2);
+
+    let enum_stmt = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::EnumStatement>()
+        .unwrap();
+    assert_eq!(enum_stmt.name.value, "Shape");
+    let variants: Vec<String> = enum_stmt
+        .variants
+        .iter()
+        .map(|variant| format!("{}/{}", variant.name.value, variant.fields.len()))
+        .collect();
+    assert_eq!(variants, vec!["Circle/1", "Rect/2", "Empty/0"]);
+
+    let statement = parsed_statement.body[1]
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap();
+    let match_expr = statement
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::Match>()
+        .unwrap();
+    assert_eq!(describe(match_expr.subject.as_deref().unwrap()), "s");
+    assert_eq!(match_expr.arms.len(), 6);
+    match &match_expr.arms[1].pattern {
+        patterns::MatchPattern::Variant(variant) => {
+            assert_eq!(variant.enum_name.value, "Shape");
+            assert_eq!(variant.variant.value, "Rect");
+            assert!(matches!(
+                variant.fields[..],
+                [
+                    patterns::MatchPattern::Wildcard(_),
+                    patterns::MatchPattern::Integer(2)
+                ]
+            ));
+        }
+        pattern => panic!("expected a variant pattern, got {:?}", pattern),
+    }
+    assert!(matches!(
+        match_expr.arms[2].pattern,
+        patterns::MatchPattern::Integer(-1)
+    ));
+    assert!(matches!(
+        &match_expr.arms[3].pattern,
+        patterns::MatchPattern::String(value) if value == "a"
+    ));
+    assert!(matches!(
+        match_expr.arms[4].pattern,
+        patterns::MatchPattern::Boolean(true)
+    ));
+    assert!(matches!(
+        match_expr.arms[5].pattern,
+        patterns::MatchPattern::Binding(_)
+    ));
+
+    let mut parser = string_to_parser("export enum E { A }");
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    let enum_stmt = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::EnumStatement>()
+        .unwrap();
+    assert!(enum_stmt.exported);
+
+    for input in [
+        "enum { A };",
+        "enum E { A, A };",
+        "enum E { A(x, x) };",
+        "match (x) { };",
+        "match (x) { 1 2 };",
+        "match (x) { 1 => 2 3 => 4 };",
+        "match (x) { [a] => a };",
+        "match x { _ => 1 };",
+    ] {
+        let mut parser = string_to_parser(input);
+        parser.parse();
+        assert!(!parser.errors.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_class() {
+    let mut parser = string_to_parser(
+        "class Dog extends Animal { fn init(name) { super.init(name); self.tricks = []; } fn bark() { 1 } } \
+         export class Cat {}",
+    );
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    assert_eq!(parsed_statement.body.len(), 2);
+
+    let class_stmt = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::ClassStatement>()
+        .unwrap();
+    assert_eq!(class_stmt.name.value, "Dog");
+    assert_eq!(class_stmt.superclass.as_ref().unwrap().value, "Animal");
+    assert!(!class_stmt.exported);
+    let methods: Vec<String> = class_stmt
+        .methods
+        .iter()
+        .map(|method| method.name.value.to_string())
+        .collect();
+    assert_eq!(methods, vec!["init", "bark"]);
+    assert_eq!(
+        class_stmt.methods[0]
+            .function
+            .parameters
+            .as_ref()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let body = &class_stmt.methods[0]
+        .function
+        .body
+        .as_ref()
+        .unwrap()
+        .statements;
+    let call = body[0]
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<statements::ExpressionStatement>()
+        .unwrap()
+        .expression
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::CallExpression>()
+        .unwrap();
+    let super_expr = call
+        .function
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::Super>()
+        .unwrap();
+    assert_eq!(super_expr.method.value, "init");
+    let assign = body[1]
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<statements::AssignStatement>()
+        .unwrap();
+    assert_eq!(describe(assign.object.as_deref().unwrap()), "self");
+    assert_eq!(assign.property.value, "tricks");
+
+    let class_stmt = parsed_statement.body[1]
+        .as_any()
+        .downcast_ref::<statements::ClassStatement>()
+        .unwrap();
+    assert!(class_stmt.exported);
+    assert!(class_stmt.superclass.is_none());
+
+    for input in [
+        "class { }",
+        "class A extends { }",
+        "class A { fn f() {} fn f() {} }",
+        "class A { f() {} }",
+        "super;",
+        "x = 1;",
+        "a[0] = 1;",
+    ] {
+        let mut parser = string_to_parser(input);
+        parser.parse();
+        assert!(!parser.errors.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_lexer_errors() {
+    let mut parser = string_to_parser("let x = 1 # 2;");
+    parser.parse();
+    assert_eq!(parser.errors[0], "Unknown character # at 1:11");
+}
+
+#[test]
+fn parse_generator() {
+    let mut parser = string_to_parser(
+        "let g = fn*(xs) { for ([k, v] in xs) { yield k; } yield; }; \
+         class A { fn* each() { yield 1; } }",
+    );
+    let parsed_statement = parser.parse();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+    let let_stmt = parsed_statement.body[0]
+        .as_any()
+        .downcast_ref::<statements::LetStatement>()
+        .unwrap();
+    let func = let_stmt
+        .value
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<expressions::FunctionLiteral>()
+        .unwrap();
+    assert!(func.generator);
+    let body = &func.body.as_ref().unwrap().statements;
+    let for_stmt = body[0]
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<statements::ForStatement>()
+        .unwrap();
+    assert_eq!(for_stmt.pattern.to_string(), "[k, v]");
+    assert_eq!(describe(for_stmt.iterable.as_deref().unwrap()), "xs");
+    let yield_stmt = for_stmt.body.statements[0]
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<statements::YieldStatement>()
+        .unwrap();
+    assert_eq!(describe(yield_stmt.value.as_deref().unwrap()), "k");
+    let yield_stmt = body[1]
+        .as_ref()
+        .unwrap()
+        .as_any()
+        .downcast_ref::<statements::YieldStatement>()
+        .unwrap();
+    assert!(yield_stmt.value.is_none());
+
+    let class_stmt = parsed_statement.body[1]
+        .as_any()
+        .downcast_ref::<statements::ClassStatement>()
+        .unwrap();
+    assert!(class_stmt.methods[0].function.generator);
+
+    for input in [
+        "for x in xs { }",
+        "for (x xs) { }",
+        "for (x in) { }",
+        "for (x in xs) x;",
+        "yield );",
+    ] {
+        let mut parser = string_to_parser(input);
+        parser.parse();
+        assert!(!parser.errors.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_member() {
+    let inputs = [
+        ("a.b.c;", "a.b.c"),
+        ("-a.b;", "-(a.b)"),
+        ("a.f(1)?;", "a.f(1)?"),
+        ("xs[0].y + a.b;", "(xs[0].y + a.b)"),
+    ];
+    for (input, expected) in inputs {
+        let mut parser = string_to_parser(input);
+        let parsed_statement = parser.parse();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let statement = parsed_statement.body[0]
+            .as_any()
+            .downcast_ref::<statements::ExpressionStatement>()
+            .unwrap();
+        assert_eq!(
+            describe(statement.expression.as_deref().unwrap()),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
+
+// A compact rendering of the expression, enough to check how `?` and `.`
+// bind.
+fn describe(expression: &dyn Expression) -> String {
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<expressions::Identifier>() {
+        return identifier.value.to_string();
+    }
+    if let Some(literal) = any.downcast_ref::<expressions::IntegerLiteral>() {
+        return literal.value.to_string();
+    }
+    if let Some(propagate) = any.downcast_ref::<expressions::Propagate>() {
+        return format!("{}?", describe(propagate.value.as_deref().unwrap()));
+    }
+    if let Some(member) = any.downcast_ref::<expressions::Member>() {
+        return format!(
+            "{}.{}",
+            describe(member.object.as_deref().unwrap()),
+            member.property.value
+        );
+    }
+    if let Some(prefix) = any.downcast_ref::<expressions::Prefix>() {
+        return format!(
+            "{}({})",
+            prefix.operator,
+            describe(prefix.right.as_deref().unwrap())
+        );
+    }
+    if let Some(infix) = any.downcast_ref::<expressions::Infix>() {
+        return format!(
+            "({} {} {})",
+            describe(infix.left.as_deref().unwrap()),
+            infix.operator,
+            describe(infix.right.as_deref().unwrap())
+        );
+    }
+    if let Some(call) = any.downcast_ref::<expressions::CallExpression>() {
+        let arguments: Vec<String> = call
+            .arguments
+            .iter()
+            .flatten()
+            .map(|argument| describe(argument.as_ref()))
+            .collect();
+        return format!(
+            "{}({})",
+            describe(call.function.as_deref().unwrap()),
+            arguments.join(", ")
+        );
+    }
+    if let Some(index) = any.downcast_ref::<expressions::IndexExpression>() {
+        return format!(
+            "{}[{}]",
+            describe(index.left.as_deref().unwrap()),
+            describe(index.index.as_deref().unwrap())
+        );
+    }
+    format!("{:?}", expression)
+}
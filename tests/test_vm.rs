@@ -0,0 +1,283 @@
+use compiler::code::disassemble;
+use compiler::{Compiler, Constant};
+use evaluator::{eval, Environment};
+use lexer::Lexer;
+use parser::Parser;
+use vm::{Vm, DEFAULT_MAX_DEPTH};
+
+fn parse_input(input: &str) -> ast::statements::ProgramStatement {
+    let lex = Lexer::new(input.bytes().collect());
+    let mut parser = Parser::new(lex);
+    let program = parser.parse();
+    if !parser.errors.is_empty() {
+        panic!("Parser errors: {:?}", parser.errors);
+    }
+    program
+}
+
+fn run_input(input: &str) -> String {
+    let mut compiler = Compiler::new();
+    let bytecode = compiler.compile(&parse_input(input));
+    match compiler.errors.first() {
+        None => Vm::new().run(&bytecode).to_string(),
+        // Reported before running, as the evaluator does.
+        Some(error) if error.starts_with("identifier not found") => format!("ERROR: {}", error),
+        Some(_) => panic!("Compiler errors: {:?}", compiler.errors),
+    }
+}
+
+#[test]
+fn vm_matches_evaluator() {
+    let inputs = [
+        r#""foo" + "bar";"#,
+        "[1, 2 * 2, 3 + 3];",
+        "[1, 2, 3][1];",
+        "[1, 2, 3][3];",
+        "[1, 2, 3][-1];",
+        r#"let h = {"b": 2, "a": 1, true: 3}; h["a"] + h[true];"#,
+        r#"{"b": 2, "a": 1};"#,
+        "if (1 > 2) { 10 };",
+        "if (1 < 2) { 10 } else { 20 };",
+        "!5; !!true; -(3 - 10);",
+        "1 == 1; null == null; [1] == [1];",
+        "let x = 1; if (x == 1) { let x = 2; }; x;",
+        "let x = 1; return x + 1; 10;",
+        "let [a, b] = [1, 2]; a + b;",
+        "let [first, ...rest] = [1, 2, 3]; rest;",
+        "let [...all] = []; all;",
+        r#"let {name, age} = {"name": "ada", "age": 36}; name;"#,
+        r#"let {name: {first}, tags: [tag, ...tags]} = {"name": {"first": "ada"}, "tags": [1, 2]}; [first, tag, tags];"#,
+        "let [a, b] = [1, 2, 3];",
+        "let [a, b, ...rest] = [1];",
+        "let [a] = 5;",
+        r#"let {name, age} = {"name": "ada"};"#,
+        "let add = fn(x, y) { x + y }; add(1, 2);",
+        "let add = fn(x, y = 10) { x + y }; add(1);",
+        "let add = fn(x, y = x * 2) { x + y }; add(3);",
+        "let f = fn(first, ...rest) { rest }; f(1, 2, 3);",
+        "let f = fn(first, ...rest) { rest }; f(1);",
+        "let sub = fn(x, y) { x - y }; sub(y: 2, x: 1);",
+        "let f = fn(x, y = 2, z = 3) { [x, y, z] }; f(1, z: 4);",
+        "let f = fn([a, b], {c}) { a + b + c }; f([1, 2], {\"c\": 3});",
+        "fn(x, y = 1, ...rest) { x };",
+        "1 / 0;",
+        "9223372036854775807 + 1;",
+        "let min = -9223372036854775807 - 1; -min;",
+        "let add = fn(x, y) { x + y }; add(1);",
+        "let add = fn(x, y) { x + y }; add(1, 2, 3);",
+        "let f = fn(x, y = 1) { x }; f();",
+        "let f = fn(x, ...rest) { x }; f();",
+        "let f = fn(x, y = 1) { x }; f(y: 2);",
+        "let f = fn(x) { x }; f(1, x: 2);",
+        "let f = fn(x) { x }; f(z: 2);",
+        "fn(x, y) { x + y }(1);",
+        "let add = fn(x, y) { x + y };\nlet sum = add(1, 2);\n  add(sum);",
+        "let add = fn(x, y) { x + y }; let plus = add; plus(1);",
+        "let x = 5;\nx(1);",
+        "let count = fn(n, acc) { if (n == 0) { acc } else { count(n - 1, acc + 1) } }; count(100000, 0);",
+        "let count = fn(n) { if (n == 0) { return 0; } return count(n - 1); }; count(100000);",
+        "let count = fn(n) { if (n > 0) { return count(n - 1); } n }; count(100000);",
+        "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(100001);",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);",
+        "let id = fn(x) { x }; let f = fn() { let y = id(1); id(y + 1) }; f();",
+        "let add = fn(x, y) { x + y }; let f = fn() { add(1) }; f();",
+        "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(100000);",
+        "let adder = fn(x) { fn(y) { x + y } }; let add_two = adder(2); add_two(3);",
+        "let make = fn() { let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f }; make()(10);",
+        "let outer = fn(a) { fn(b) { fn(c) { a + b + c } } }; outer(1)(2)(3);",
+        "let f = fn() { let g = fn() { x }; let x = 5; g() }; f();",
+        "let f = fn() { let g = fn() { x }; g() }; f();",
+        "let make = fn(x) { let get = fn() { x }; let x = x + 1; get }; make(1)();",
+        "let x = 1; let f = fn() { let x = x + 1; x }; [f(), x];",
+        "let x = 1; let f = fn() { let g = fn() { x }; let x = x + 10; g() }; f();",
+        "let f = fn(x, x) { x }; f(1, 2);",
+        "let f = fn(a, ...a) { a }; f(1, 2);",
+        "let f = fn(x) { fn() { x } }; let g = f(1); g;",
+        "1 + true;",
+        "true + false;",
+        r#""a" - "b";"#,
+        "-true;",
+        "undefined_name;",
+        "{[1]: 2};",
+        "1[0];",
+    ];
+
+    // The evaluator needs the main thread's stack for the deepest of these.
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            for input in inputs.iter() {
+                let expected = eval(&parse_input(input), Environment::new()).to_string();
+                assert_eq!(run_input(input), expected, "{}", input);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn vm_recursion_depth_limit() {
+    let mut compiler = Compiler::new();
+    let bytecode = compiler.compile(&parse_input(
+        "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; let g = fn() { 1 + f(49) }; g();",
+    ));
+    let mut vm = Vm::new();
+    vm.max_depth = 50;
+    assert_eq!(
+        vm.run(&bytecode).to_string(),
+        "ERROR: maximum recursion depth exceeded (limit 50): g (at 1:89) -> f (at 1:80) -> f (at 1:47) x49"
+    );
+    assert_eq!(DEFAULT_MAX_DEPTH, evaluator::DEFAULT_MAX_DEPTH);
+}
+
+#[test]
+fn vm_globals_persist() {
+    let mut compiler = Compiler::new();
+    let mut vm = Vm::new();
+    let bytecode = compiler.compile(&parse_input("let x = 40;"));
+    assert_eq!(vm.run(&bytecode).to_string(), "null");
+    let bytecode = compiler.compile(&parse_input("x + 2;"));
+    assert_eq!(vm.run(&bytecode).to_string(), "42");
+}
+
+#[test]
+fn compile_jumps() {
+    let mut compiler = Compiler::new();
+    let bytecode = compiler.compile(&parse_input("if (true) { 10 } else { 20 }; 30;"));
+    assert!(compiler.errors.is_empty());
+    assert_eq!(
+        disassemble(&bytecode.main.instructions),
+        "0000 True\n\
+         0001 JumpIfFalse 10\n\
+         0004 Constant 0\n\
+         0007 Jump 13\n\
+         0010 Constant 1\n\
+         0013 Pop\n\
+         0014 Constant 2\n\
+         0017 Return\n"
+    );
+}
+
+#[test]
+fn compile_closures() {
+    let mut compiler = Compiler::new();
+    let bytecode = compiler.compile(&parse_input("fn(x) { let y = 1; fn() { x + y } };"));
+    assert!(compiler.errors.is_empty());
+
+    let functions: Vec<_> = bytecode
+        .constants
+        .iter()
+        .filter_map(|c| match c {
+            Constant::Function(function) => Some(function),
+            _ => None,
+        })
+        .collect();
+    let (inner, outer) = (functions[0], functions[1]);
+    assert_eq!(
+        disassemble(&inner.instructions),
+        "0000 GetUpvalue 0\n\
+         0003 GetUpvalue 1\n\
+         0006 Add\n\
+         0007 Return\n"
+    );
+    assert_eq!(
+        disassemble(&outer.instructions),
+        "0000 MakeCell 0\n\
+         0003 MakeCell 1\n\
+         0006 RequireArg 0 0\n\
+         0011 Constant 0\n\
+         0014 SetCell 1\n\
+         0017 Closure 1\n\
+         0020 Return\n"
+    );
+}
+
+#[test]
+fn compile_errors() {
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("gc();"));
+    assert_eq!(compiler.errors, vec!["gc() is not supported by the vm"]);
+
+    compiler.compile(&parse_input("let gc = fn() { 1 }; gc();"));
+    assert!(compiler.errors.is_empty());
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("try { throw 1; } catch (e) { e };"));
+    assert_eq!(compiler.errors, vec!["try is not supported by the vm"]);
+
+    // A variable bound nowhere is an error even where it is never read.
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("if (1 < 2) { 10 } else { y };"));
+    assert_eq!(compiler.errors, vec!["identifier not found: y"]);
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("throw 1;"));
+    assert_eq!(compiler.errors, vec!["throw is not supported by the vm"]);
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("let f = fn(r) { r? }; f(ok(1));"));
+    assert_eq!(
+        compiler.errors,
+        vec![
+            "? is not supported by the vm",
+            "ok() is not supported by the vm"
+        ]
+    );
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("import \"math.lang\" as m; m.square(2);"));
+    assert_eq!(
+        compiler.errors,
+        vec![
+            "import is not supported by the vm",
+            "member access is not supported by the vm"
+        ]
+    );
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("struct P { x } P { x: 1 };"));
+    assert_eq!(
+        compiler.errors,
+        vec![
+            "struct is not supported by the vm",
+            "struct literals are not supported by the vm"
+        ]
+    );
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input("enum E { A } match (1) { _ => 2 };"));
+    assert_eq!(
+        compiler.errors,
+        vec![
+            "enum is not supported by the vm",
+            "match is not supported by the vm"
+        ]
+    );
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input(
+        "class A {} class B extends A { fn f() { super.f() } } let b = B(); b.x = 1;",
+    ));
+    assert_eq!(
+        compiler.errors,
+        vec![
+            "class is not supported by the vm",
+            "class is not supported by the vm",
+            "field assignment is not supported by the vm"
+        ]
+    );
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse_input(
+        "let g = fn*() { yield 1; }; for (x in [1]) { x; } yield 2;",
+    ));
+    assert_eq!(
+        compiler.errors,
+        vec![
+            "generators are not supported by the vm",
+            "for is not supported by the vm",
+            "yield is not supported by the vm"
+        ]
+    );
+}
@@ -0,0 +1,673 @@
+mod value;
+
+use compiler::code::{read_u16, Opcode};
+use compiler::{Bytecode, CallSite, Capture, Constant};
+use lexer::Span;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub use value::{Closure, HashKey, Value};
+
+// The same limit as the evaluator, so both engines fail on the same
+// programs. Frames live on the VM's own stack, so this could be raised.
+pub const DEFAULT_MAX_DEPTH: usize = 400;
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Index of the frame's first slot on the stack. The function being
+    // called sits just below it.
+    base: usize,
+    call_site: Span,
+}
+
+pub struct Vm {
+    // Calls nested deeper than this fail with an error. Tail calls do not
+    // count towards it.
+    pub max_depth: usize,
+    stack: Vec<Value>,
+    // The frames of the callers of the one being executed.
+    frames: Vec<Frame>,
+    globals: Vec<Value>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        Vm {
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: Vec::new(),
+        }
+    }
+
+    // Returns the value of the program, or `Value::Error` if it failed.
+    // Globals are kept between runs.
+    pub fn run(&mut self, bytecode: &Bytecode) -> Value {
+        self.stack.clear();
+        self.frames.clear();
+        if self.globals.len() < bytecode.globals.len() {
+            self.globals
+                .resize(bytecode.globals.len(), Value::Undefined);
+        }
+
+        let main = Frame {
+            closure: Rc::new(Closure {
+                function: Rc::clone(&bytecode.main),
+                upvalues: Vec::new(),
+                name: None,
+            }),
+            ip: 0,
+            base: 0,
+            call_site: Span::default(),
+        };
+        self.stack
+            .resize(bytecode.main.num_locals, Value::Undefined);
+        match self.execute(main, bytecode) {
+            Ok(value) => value,
+            Err(message) => Value::Error(message),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Null)
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn execute(&mut self, mut frame: Frame, bytecode: &Bytecode) -> Result<Value, String> {
+        loop {
+            let (op, a, b) = {
+                let instructions = &frame.closure.function.instructions;
+                let op = match Opcode::from_byte(instructions[frame.ip]) {
+                    Some(op) => op,
+                    None => return Err(format!("unknown opcode {}", instructions[frame.ip])),
+                };
+                let count = op.operand_count();
+                let a = match count {
+                    0 => 0,
+                    _ => read_u16(instructions, frame.ip + 1) as usize,
+                };
+                let b = match count {
+                    2 => read_u16(instructions, frame.ip + 3) as usize,
+                    _ => 0,
+                };
+                frame.ip += 1 + count * 2;
+                (op, a, b)
+            };
+
+            match op {
+                Opcode::Constant => {
+                    let value = match &bytecode.constants[a] {
+                        Constant::Integer(value) => Value::Integer(*value),
+                        Constant::String(value) => Value::String(Rc::from(value.as_str())),
+                        Constant::Function(_) => return Err("function used as a value".to_string()),
+                    };
+                    self.push(value);
+                }
+                Opcode::Null => self.push(Value::Null),
+                Opcode::True => self.push(Value::Boolean(true)),
+                Opcode::False => self.push(Value::Boolean(false)),
+                Opcode::Pop => {
+                    self.pop();
+                }
+
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = infix(op, left, right)?;
+                    self.push(result);
+                }
+                Opcode::Minus => match self.pop() {
+                    Value::Integer(value) => match value.checked_neg() {
+                        Some(value) => self.push(Value::Integer(value)),
+                        None => return Err(format!("integer overflow: -{}", value)),
+                    },
+                    right => return Err(format!("unknown operator: -{}", value_type(&right))),
+                },
+                Opcode::Bang => {
+                    let right = self.pop();
+                    self.push(Value::Boolean(!is_truthy(&right)));
+                }
+
+                Opcode::Jump => frame.ip = a,
+                Opcode::JumpIfFalse => {
+                    if !is_truthy(&self.pop()) {
+                        frame.ip = a;
+                    }
+                }
+
+                Opcode::GetGlobal => match &self.globals[a] {
+                    Value::Undefined => {
+                        return Err(format!("identifier not found: {}", bytecode.globals[a]))
+                    }
+                    value => {
+                        let value = value.clone();
+                        self.push(value);
+                    }
+                },
+                Opcode::SetGlobal => self.globals[a] = self.pop(),
+                Opcode::GetLocal => match &self.stack[frame.base + a] {
+                    Value::Undefined => {
+                        return Err(undefined(&frame.closure.function.local_names[a]))
+                    }
+                    value => {
+                        let value = value.clone();
+                        self.push(value);
+                    }
+                },
+                Opcode::SetLocal => self.stack[frame.base + a] = self.pop(),
+                Opcode::MakeCell => {
+                    let slot = &mut self.stack[frame.base + a];
+                    let value = std::mem::replace(slot, Value::Undefined);
+                    *slot = Value::Cell(Rc::new(RefCell::new(value)));
+                }
+                Opcode::GetCell => {
+                    let value = match &self.stack[frame.base + a] {
+                        Value::Cell(cell) => cell.borrow().clone(),
+                        value => value.clone(),
+                    };
+                    if let Value::Undefined = value {
+                        return Err(undefined(&frame.closure.function.local_names[a]));
+                    }
+                    self.push(value);
+                }
+                Opcode::SetCell => {
+                    let value = self.pop();
+                    match &self.stack[frame.base + a] {
+                        Value::Cell(cell) => *cell.borrow_mut() = value,
+                        _ => self.stack[frame.base + a] = value,
+                    }
+                }
+                Opcode::GetUpvalue => {
+                    let value = frame.closure.upvalues[a].borrow().clone();
+                    if let Value::Undefined = value {
+                        return Err(undefined(&frame.closure.function.upvalue_names[a]));
+                    }
+                    self.push(value);
+                }
+
+                Opcode::Array => {
+                    let elements = self.stack.split_off(self.stack.len() - a);
+                    self.push(Value::Array(Rc::new(elements)));
+                }
+                Opcode::Hash => {
+                    let values = self.stack.split_off(self.stack.len() - a * 2);
+                    let mut pairs = BTreeMap::new();
+                    let mut values = values.into_iter();
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        match HashKey::from_value(&key) {
+                            Some(key) => pairs.insert(key, value),
+                            None => return Err(unusable_key(&key)),
+                        };
+                    }
+                    self.push(Value::Hash(Rc::new(pairs)));
+                }
+                Opcode::CheckHashKey => {
+                    let key = self.stack.last().unwrap_or(&Value::Null);
+                    if HashKey::from_value(key).is_none() {
+                        return Err(unusable_key(key));
+                    }
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    let result = index_value(left, index)?;
+                    self.push(result);
+                }
+
+                Opcode::Closure => {
+                    let function = match &bytecode.constants[a] {
+                        Constant::Function(function) => Rc::clone(function),
+                        _ => return Err("not a function constant".to_string()),
+                    };
+                    let upvalues = function
+                        .captures
+                        .iter()
+                        .map(|capture| match capture {
+                            Capture::Local(slot) => {
+                                match &self.stack[frame.base + *slot as usize] {
+                                    Value::Cell(cell) => Rc::clone(cell),
+                                    value => Rc::new(RefCell::new(value.clone())),
+                                }
+                            }
+                            Capture::Upvalue(index) => {
+                                Rc::clone(&frame.closure.upvalues[*index as usize])
+                            }
+                        })
+                        .collect();
+                    self.push(Value::Closure(Rc::new(Closure {
+                        function,
+                        upvalues,
+                        name: None,
+                    })));
+                }
+                Opcode::SetName => {
+                    if let Some(Value::Closure(closure)) = self.stack.last_mut() {
+                        if closure.name.is_none() {
+                            let name = match &bytecode.constants[a] {
+                                Constant::String(name) => Rc::from(name.as_str()),
+                                _ => return Err("not a name constant".to_string()),
+                            };
+                            let mut named = Closure::clone(closure);
+                            named.name = Some(name);
+                            *closure = Rc::new(named);
+                        }
+                    }
+                }
+                Opcode::Call => {
+                    let site = frame.closure.function.call_sites[a].clone();
+                    let callee = self.stack.len() - site.args - site.names.len() - 1;
+                    if self.frames.len() >= self.max_depth {
+                        return Err(self.recursion_error(&frame, &site, callee));
+                    }
+                    let closure = self.callee(callee)?;
+                    self.prepare_call(&closure, &site, callee)?;
+                    let caller = std::mem::replace(
+                        &mut frame,
+                        Frame {
+                            closure,
+                            ip: 0,
+                            base: callee + 1,
+                            call_site: site.span,
+                        },
+                    );
+                    self.frames.push(caller);
+                }
+                Opcode::TailCall => {
+                    let site = frame.closure.function.call_sites[a].clone();
+                    let callee = self.stack.len() - site.args - site.names.len() - 1;
+                    let closure = self.callee(callee)?;
+                    // Move the callee and its arguments over the current
+                    // frame.
+                    self.stack.drain(frame.base - 1..callee);
+                    let callee = frame.base - 1;
+                    self.prepare_call(&closure, &site, callee)?;
+                    frame.closure = closure;
+                    frame.ip = 0;
+                    frame.call_site = site.span;
+                }
+                Opcode::Return => {
+                    let result = self.pop();
+                    let caller = match self.frames.pop() {
+                        Some(caller) => caller,
+                        None => return Ok(result),
+                    };
+                    self.stack.truncate(frame.base - 1);
+                    self.push(result);
+                    frame = caller;
+                }
+
+                Opcode::JumpIfArg => {
+                    if !is_undefined(&self.stack[frame.base + a]) {
+                        frame.ip = b;
+                    }
+                }
+                Opcode::RequireArg => {
+                    if is_undefined(&self.stack[frame.base + a]) {
+                        let parameter = &frame.closure.function.parameters[b];
+                        return Err(call_error(
+                            &frame.closure,
+                            "missing argument",
+                            parameter.pattern.clone(),
+                        ));
+                    }
+                }
+                Opcode::DestructureArray => {
+                    let shape = &frame.closure.function.array_shapes[a];
+                    let value = self.pop();
+                    let elements = match &value {
+                        Value::Array(elements) => elements,
+                        _ => {
+                            return Err(destructure_error(
+                                &shape.description,
+                                describe_shape(&value),
+                            ))
+                        }
+                    };
+                    let arity_ok = match shape.rest {
+                        true => elements.len() >= shape.len,
+                        false => elements.len() == shape.len,
+                    };
+                    if !arity_ok {
+                        return Err(destructure_error(
+                            &shape.description,
+                            format!("array of {} elements", elements.len()),
+                        ));
+                    }
+                    if shape.rest {
+                        let rest = elements[shape.len..].to_vec();
+                        self.push(Value::Array(Rc::new(rest)));
+                    }
+                    for element in elements[..shape.len].iter().rev() {
+                        self.stack.push(element.clone());
+                    }
+                }
+                Opcode::CheckHash => {
+                    let value = self.stack.last().unwrap_or(&Value::Null);
+                    if !matches!(value, Value::Hash(_)) {
+                        return Err(destructure_error(
+                            &constant_string(bytecode, a),
+                            describe_shape(value),
+                        ));
+                    }
+                }
+                Opcode::HashEntry => {
+                    let key = constant_string(bytecode, a);
+                    let value = match self.stack.last_mut() {
+                        Some(Value::Hash(pairs)) => {
+                            Rc::make_mut(pairs).remove(&HashKey::String(Rc::from(key.as_str())))
+                        }
+                        _ => None,
+                    };
+                    match value {
+                        Some(value) => self.push(value),
+                        None => {
+                            return Err(destructure_error(
+                                &constant_string(bytecode, b),
+                                format!("hash without key \"{}\"", key),
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn callee(&self, callee: usize) -> Result<Rc<Closure>, String> {
+        match &self.stack[callee] {
+            Value::Closure(closure) => Ok(Rc::clone(closure)),
+            value => Err(format!("not a function: {}", value_type(value))),
+        }
+    }
+
+    // Arranges the arguments above the callee at `callee` into the slots
+    // of its frame.
+    fn prepare_call(
+        &mut self,
+        closure: &Closure,
+        site: &CallSite,
+        callee: usize,
+    ) -> Result<(), String> {
+        let function = closure.function.as_ref();
+        let parameters = function.parameters.len();
+        let required = function
+            .parameters
+            .iter()
+            .filter(|p| !p.has_default)
+            .count();
+        let given = site.args + site.names.len();
+        let too_many = function.rest.is_none() && site.args > parameters;
+        if too_many || given < required {
+            return Err(arity_error(closure, given));
+        }
+
+        if site.names.is_empty() && site.args == parameters && function.rest.is_none() {
+            let locals = callee + 1 + function.num_locals;
+            self.stack.resize(locals, Value::Undefined);
+            return Ok(());
+        }
+
+        let mut args = self.stack.split_off(callee + 1);
+        let named = args.split_off(site.args);
+        let mut slots = vec![Value::Undefined; function.num_locals];
+        let mut rest = Vec::new();
+        for (i, arg) in args.into_iter().enumerate() {
+            match i < parameters {
+                true => slots[i] = arg,
+                false => rest.push(arg),
+            }
+        }
+        for (name, value) in site.names.iter().zip(named) {
+            let index = match function
+                .parameters
+                .iter()
+                .position(|p| p.name.as_deref() == Some(name.as_str()))
+            {
+                Some(index) => index,
+                None => {
+                    return Err(call_error(
+                        closure,
+                        "unexpected keyword argument",
+                        name.clone(),
+                    ))
+                }
+            };
+            if !is_undefined(&slots[index]) {
+                return Err(call_error(
+                    closure,
+                    "multiple values for argument",
+                    name.clone(),
+                ));
+            }
+            slots[index] = value;
+        }
+        if function.rest.is_some() {
+            slots[parameters] = Value::Array(Rc::new(rest));
+        }
+        self.stack.extend(slots);
+        Ok(())
+    }
+
+    fn recursion_error(&self, frame: &Frame, site: &CallSite, callee: usize) -> String {
+        let name = match &self.stack[callee] {
+            Value::Closure(closure) => closure.name.clone(),
+            _ => None,
+        };
+        // The first frame is the program's own.
+        let frames = self
+            .frames
+            .iter()
+            .chain(std::iter::once(frame))
+            .skip(1)
+            .map(|frame| describe_frame(&frame.closure.name, frame.call_site))
+            .chain(std::iter::once(describe_frame(&name, site.span)));
+
+        let mut chain: Vec<(String, usize)> = Vec::new();
+        for frame in frames {
+            match chain.last_mut() {
+                Some((last, count)) if *last == frame => *count += 1,
+                _ => chain.push((frame, 1)),
+            }
+        }
+        let chain: Vec<String> = chain
+            .into_iter()
+            .map(|(frame, count)| match count {
+                1 => frame,
+                _ => format!("{} x{}", frame, count),
+            })
+            .collect();
+        format!(
+            "maximum recursion depth exceeded (limit {}): {}",
+            self.max_depth,
+            chain.join(" -> ")
+        )
+    }
+}
+
+fn describe_frame(name: &Option<Rc<str>>, call_site: Span) -> String {
+    match name {
+        Some(name) => format!("{} (at {})", name, call_site),
+        None => format!("<anonymous> (at {})", call_site),
+    }
+}
+
+fn constant_string(bytecode: &Bytecode, index: usize) -> String {
+    match &bytecode.constants[index] {
+        Constant::String(value) => value.clone(),
+        _ => String::new(),
+    }
+}
+
+fn infix(op: Opcode, left: Value, right: Value) -> Result<Value, String> {
+    let operator = match op {
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Equal => "==",
+        Opcode::NotEqual => "!=",
+        Opcode::GreaterThan => ">",
+        _ => "<",
+    };
+    match (&left, &right) {
+        (Value::Integer(left), Value::Integer(right)) => {
+            let result = match op {
+                Opcode::Add => left.checked_add(*right),
+                Opcode::Sub => left.checked_sub(*right),
+                Opcode::Mul => left.checked_mul(*right),
+                Opcode::Div if *right == 0 => return Err("division by zero".to_string()),
+                Opcode::Div => left.checked_div(*right),
+                Opcode::Equal => return Ok(Value::Boolean(left == right)),
+                Opcode::NotEqual => return Ok(Value::Boolean(left != right)),
+                Opcode::GreaterThan => return Ok(Value::Boolean(left > right)),
+                _ => return Ok(Value::Boolean(left < right)),
+            };
+            match result {
+                Some(value) => Ok(Value::Integer(value)),
+                None => Err(format!("integer overflow: {} {} {}", left, operator, right)),
+            }
+        }
+        (Value::Boolean(left), Value::Boolean(right)) => match op {
+            Opcode::Equal => Ok(Value::Boolean(left == right)),
+            Opcode::NotEqual => Ok(Value::Boolean(left != right)),
+            _ => Err(format!("unknown operator: BOOLEAN {} BOOLEAN", operator)),
+        },
+        (Value::String(left), Value::String(right)) => match op {
+            Opcode::Add => Ok(Value::String(Rc::from(format!("{}{}", left, right)))),
+            Opcode::Equal => Ok(Value::Boolean(left == right)),
+            Opcode::NotEqual => Ok(Value::Boolean(left != right)),
+            _ => Err(format!("unknown operator: STRING {} STRING", operator)),
+        },
+        (Value::Null, Value::Null) => match op {
+            Opcode::Equal => Ok(Value::Boolean(true)),
+            Opcode::NotEqual => Ok(Value::Boolean(false)),
+            _ => Err(format!("unknown operator: NULL {} NULL", operator)),
+        },
+        _ => match op {
+            Opcode::Equal => Ok(Value::Boolean(false)),
+            Opcode::NotEqual => Ok(Value::Boolean(true)),
+            _ => Err(format!(
+                "type mismatch: {} {} {}",
+                value_type(&left),
+                operator,
+                value_type(&right)
+            )),
+        },
+    }
+}
+
+fn index_value(left: Value, index: Value) -> Result<Value, String> {
+    match (&left, &index) {
+        (Value::Array(elements), Value::Integer(i)) => {
+            if *i < 0 {
+                return Ok(Value::Null);
+            }
+            Ok(elements.get(*i as usize).cloned().unwrap_or(Value::Null))
+        }
+        (Value::Hash(pairs), _) => match HashKey::from_value(&index) {
+            Some(key) => Ok(pairs.get(&key).cloned().unwrap_or(Value::Null)),
+            None => Err(unusable_key(&index)),
+        },
+        _ => Err(format!(
+            "index operator not supported: {}[{}]",
+            value_type(&left),
+            value_type(&index)
+        )),
+    }
+}
+
+fn arity_error(closure: &Closure, given: usize) -> String {
+    let function = &closure.function;
+    let total = function.parameters.len();
+    let required = function
+        .parameters
+        .iter()
+        .filter(|p| !p.has_default)
+        .count();
+    let expected = if function.rest.is_some() {
+        format!("at least {}", required)
+    } else if required == total {
+        format!("{}", total)
+    } else {
+        format!("{} to {}", required, total)
+    };
+    call_error(
+        closure,
+        "wrong number of arguments",
+        format!("expected {}, got {}", expected, given),
+    )
+}
+
+fn call_error(closure: &Closure, message: &str, detail: String) -> String {
+    let target = match &closure.name {
+        Some(name) => format!(" in call to {}", name),
+        None => String::new(),
+    };
+    format!("{}{}: {}", message, target, detail)
+}
+
+fn destructure_error(expected: &str, got: String) -> String {
+    format!("cannot destructure: expected {}, got {}", expected, got)
+}
+
+fn describe_shape(value: &Value) -> String {
+    match value {
+        Value::Array(elements) => format!("array of {} elements", elements.len()),
+        _ => value_type(value).to_string(),
+    }
+}
+
+fn unusable_key(key: &Value) -> String {
+    format!("unusable as hash key: {}", value_type(key))
+}
+
+fn undefined(name: &str) -> String {
+    format!("identifier not found: {}", name)
+}
+
+fn is_undefined(value: &Value) -> bool {
+    match value {
+        Value::Cell(cell) => matches!(*cell.borrow(), Value::Undefined),
+        value => matches!(value, Value::Undefined),
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Boolean(value) => *value,
+        Value::Null => false,
+        _ => true,
+    }
+}
+
+fn value_type(value: &Value) -> &'static str {
+    match value {
+        Value::Integer(_) => "INTEGER",
+        Value::Boolean(_) => "BOOLEAN",
+        Value::String(_) => "STRING",
+        Value::Array(_) => "ARRAY",
+        Value::Hash(_) => "HASH",
+        Value::Null => "NULL",
+        Value::Closure(_) => "FUNCTION",
+        Value::Error(_) => "ERROR",
+        Value::Cell(_) => "CELL",
+        Value::Undefined => "UNDEFINED",
+    }
+}
@@ -0,0 +1,99 @@
+use compiler::CompiledFunction;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+// Values are shared rather than copied. Nothing in the language can change
+// a string, array or hash once it has been built, so this can not be told
+// apart from the evaluator copying them.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+    Array(Rc<Vec<Value>>),
+    Hash(Rc<BTreeMap<HashKey, Value>>),
+    Null,
+    Closure(Rc<Closure>),
+    Error(String),
+    // Only ever found in a frame's slots: a local that closures capture,
+    Cell(Rc<RefCell<Value>>),
+    // and a local that has not been bound yet.
+    Undefined,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+impl HashKey {
+    pub(crate) fn from_value(value: &Value) -> Option<HashKey> {
+        match value {
+            Value::Integer(value) => Some(HashKey::Integer(*value)),
+            Value::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Value::String(value) => Some(HashKey::String(Rc::clone(value))),
+            _ => None,
+        }
+    }
+}
+
+impl Display for HashKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            HashKey::Integer(value) => write!(f, "{}", value),
+            HashKey::Boolean(value) => write!(f, "{}", value),
+            HashKey::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub upvalues: Vec<Rc<RefCell<Value>>>,
+    pub name: Option<Rc<str>>,
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Value::Null => write!(f, "null"),
+            Value::Closure(closure) => {
+                let function = &closure.function;
+                let mut params: Vec<String> = function
+                    .parameters
+                    .iter()
+                    .map(|p| match p.has_default {
+                        true => format!("{} = ...", p.pattern),
+                        false => p.pattern.clone(),
+                    })
+                    .collect();
+                if let Some(rest) = &function.rest {
+                    params.push(format!("...{}", rest));
+                }
+                write!(f, "fn({}) {{ ... }}", params.join(", "))
+            }
+            Value::Error(message) => write!(f, "ERROR: {}", message),
+            Value::Cell(cell) => write!(f, "{}", cell.borrow()),
+            Value::Undefined => write!(f, "undefined"),
+        }
+    }
+}